@@ -0,0 +1,359 @@
+//! Compression for bodies.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Write},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use flate2::{
+    Compression,
+    write::{GzEncoder, ZlibEncoder},
+};
+use http::HeaderMap;
+use http_body::{Body, Frame, SizeHint};
+
+/// Content coding applied by a `CompressedBody`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContentEncoding {
+    /// gzip format (RFC 1952)
+    Gzip,
+    /// zlib format (RFC 1950), which HTTP calls `deflate`
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Get the `Content-Encoding` header value for this coding
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+}
+
+impl fmt::Display for ContentEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(encoding: ContentEncoding, level: Compression) -> Self {
+        match encoding {
+            ContentEncoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), level)),
+            ContentEncoding::Deflate => Encoder::Deflate(ZlibEncoder::new(Vec::new(), level)),
+        }
+    }
+
+    /// Compress `data`, returning whatever output is ready
+    fn write(&mut self, mut data: impl Buf) -> io::Result<Bytes> {
+        while data.has_remaining() {
+            let chunk = data.chunk();
+            match self {
+                Encoder::Gzip(encoder) => encoder.write_all(chunk)?,
+                Encoder::Deflate(encoder) => encoder.write_all(chunk)?,
+            }
+            let n = chunk.len();
+            data.advance(n);
+        }
+        let out = match self {
+            Encoder::Gzip(encoder) => encoder.get_mut(),
+            Encoder::Deflate(encoder) => encoder.get_mut(),
+        };
+        Ok(Bytes::from(std::mem::take(out)))
+    }
+
+    /// Flush the compressor, returning all output for the data so far
+    fn flush(&mut self) -> io::Result<Bytes> {
+        let out = match self {
+            Encoder::Gzip(encoder) => {
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+            Encoder::Deflate(encoder) => {
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+        };
+        Ok(Bytes::from(std::mem::take(out)))
+    }
+
+    /// Finish the stream, returning the remaining output and trailer
+    fn finish(self) -> io::Result<Bytes> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Deflate(encoder) => encoder.finish(),
+        }
+        .map(Bytes::from)
+    }
+}
+
+/// Compress `data` with `encoding` at `level`, returning the size of the
+/// output so far
+///
+/// The compressor is flushed but not finished, so this is the size the data
+/// takes up at the start of a longer body, without the format's trailer.
+pub(crate) fn compressed_len(
+    encoding: ContentEncoding,
+    level: u32,
+    data: &[u8],
+) -> io::Result<usize> {
+    match Encoder::new(encoding, Compression::new(level.min(9))) {
+        Encoder::Gzip(mut encoder) => {
+            encoder.write_all(data)?;
+            encoder.flush()?;
+            Ok(encoder.get_ref().len())
+        }
+        Encoder::Deflate(mut encoder) => {
+            encoder.write_all(data)?;
+            encoder.flush()?;
+            Ok(encoder.get_ref().len())
+        }
+    }
+}
+
+/// Body wrapper that compresses the data of the inner body
+///
+/// Data frames are compressed as they arrive, so frames that don't yet
+/// produce compressed output are held back. When the inner body has no
+/// frame ready, the compressor is flushed, so a slowly streamed body isn't
+/// held back waiting for more data. Once the inner body ends, the
+/// compressor is finished and the remaining output, including the format's
+/// trailer, is sent as the last data frame. Trailers frames are passed
+/// through after it, untouched.
+///
+/// Setting the `Content-Encoding` header, and removing `Content-Length`, is
+/// up to the caller; `ContentEncoding::as_str` gives the header value. See
+/// `handler::Compress` for a handler that decides whether to compress
+/// based on the request.
+pub struct CompressedBody<B> {
+    inner: B,
+    encoding: ContentEncoding,
+    encoder: Option<Encoder>,
+    // Whether data was compressed since the last flush
+    unflushed: bool,
+    trailers: Option<HeaderMap>,
+}
+
+impl<B> CompressedBody<B> {
+    /// Wrap a body, compressing it with `encoding` at the default level
+    pub fn new(inner: B, encoding: ContentEncoding) -> Self {
+        Self::with_level(inner, encoding, Compression::default().level())
+    }
+
+    /// Wrap a body, compressing it with `encoding` at `level`, from 0 (none)
+    /// to 9 (best, slowest)
+    pub fn with_level(inner: B, encoding: ContentEncoding, level: u32) -> Self {
+        Self {
+            inner,
+            encoding,
+            encoder: Some(Encoder::new(encoding, Compression::new(level.min(9)))),
+            unflushed: false,
+            trailers: None,
+        }
+    }
+
+    /// Get the content coding applied
+    pub fn encoding(&self) -> ContentEncoding {
+        self.encoding
+    }
+
+    /// Get a reference to the inner body
+    pub fn get_ref(&self) -> &B {
+        &self.inner
+    }
+}
+
+impl<B: fmt::Debug> fmt::Debug for CompressedBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressedBody")
+            .field("inner", &self.inner)
+            .field("encoding", &self.encoding)
+            .field("finished", &self.encoder.is_none())
+            .finish()
+    }
+}
+
+/// Error from a `CompressedBody`
+#[derive(Debug)]
+pub enum CompressedBodyError<E> {
+    /// Compressing the data failed
+    Compression(io::Error),
+    /// The inner body failed
+    Body(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CompressedBodyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressedBodyError::Compression(e) => write!(f, "Compression failed: {}", e),
+            CompressedBodyError::Body(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for CompressedBodyError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CompressedBodyError::Compression(e) => Some(e),
+            CompressedBodyError::Body(e) => Some(e),
+        }
+    }
+}
+
+impl<B> Body for CompressedBody<B>
+where
+    B: Body + Unpin,
+{
+    type Data = Bytes;
+    type Error = CompressedBodyError<B::Error>;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        loop {
+            let Some(encoder) = this.encoder.as_mut() else {
+                return Poll::Ready(this.trailers.take().map(|t| Ok(Frame::trailers(t))));
+            };
+
+            let frame = match Pin::new(&mut this.inner).poll_frame(cx) {
+                Poll::Ready(frame) => frame,
+                // Send what was compressed so far while waiting for more
+                Poll::Pending if this.unflushed => {
+                    this.unflushed = false;
+                    match encoder.flush() {
+                        Ok(out) if out.is_empty() => return Poll::Pending,
+                        Ok(out) => return Poll::Ready(Some(Ok(Frame::data(out)))),
+                        Err(e) => {
+                            return Poll::Ready(Some(Err(CompressedBodyError::Compression(e))));
+                        }
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+            let frame = match frame {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => return Poll::Ready(Some(Err(CompressedBodyError::Body(e)))),
+                None => break,
+            };
+
+            match frame.into_data() {
+                Ok(data) => {
+                    this.unflushed = true;
+                    match encoder.write(data) {
+                        Ok(out) if out.is_empty() => continue,
+                        Ok(out) => return Poll::Ready(Some(Ok(Frame::data(out)))),
+                        Err(e) => {
+                            return Poll::Ready(Some(Err(CompressedBodyError::Compression(e))));
+                        }
+                    }
+                }
+                // Trailers end the data, so finish first and send them after
+                Err(frame) => {
+                    if let Ok(trailers) = frame.into_trailers() {
+                        this.trailers = Some(trailers);
+                    }
+                    break;
+                }
+            }
+        }
+
+        let encoder = this.encoder.take().expect("encoder is present");
+        match encoder.finish() {
+            Ok(out) => Poll::Ready(Some(Ok(Frame::data(out)))),
+            Err(e) => Poll::Ready(Some(Err(CompressedBodyError::Compression(e)))),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.encoder.is_none() && self.trailers.is_none()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        if self.is_end_stream() {
+            return SizeHint::with_exact(0);
+        }
+        SizeHint::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResponseBody;
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use http_body_util::BodyExt;
+    use std::io::Read;
+    use tokio::io::AsyncWriteExt;
+
+    fn decompress(encoding: ContentEncoding, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        match encoding {
+            ContentEncoding::Gzip => GzDecoder::new(data).read_to_end(&mut out),
+            ContentEncoding::Deflate => ZlibDecoder::new(data).read_to_end(&mut out),
+        }
+        .unwrap();
+        out
+    }
+
+    #[tokio::test]
+    async fn test_compressed_body_roundtrip() {
+        let original = "{\"message\":\"hello\"}".repeat(2000);
+        for encoding in [ContentEncoding::Gzip, ContentEncoding::Deflate] {
+            let body = ResponseBody::new();
+            let mut writer = body.clone();
+            let data = original.clone();
+            tokio::spawn(async move {
+                for chunk in data.as_bytes().chunks(1000) {
+                    writer.write_all(chunk).await.unwrap();
+                }
+                writer.shutdown().await.unwrap();
+            });
+
+            let compressed = CompressedBody::new(body, encoding)
+                .collect()
+                .await
+                .unwrap()
+                .to_bytes();
+            assert!(compressed.len() < original.len() / 10);
+            assert_eq!(decompress(encoding, &compressed), original.as_bytes());
+        }
+
+        // An empty body still gets a valid, empty stream
+        let body = ResponseBody::from_data_sync(Bytes::new()).unwrap();
+        let compressed = CompressedBody::new(body, ContentEncoding::Gzip)
+            .collect()
+            .await
+            .unwrap()
+            .to_bytes();
+        assert_eq!(decompress(ContentEncoding::Gzip, &compressed), b"");
+    }
+
+    #[tokio::test]
+    async fn test_compressed_body_passes_trailers() {
+        let body = ResponseBody::from_data_sync(Bytes::from("hello")).unwrap();
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        body.set_trailers(trailers.clone());
+
+        let collected = CompressedBody::new(body, ContentEncoding::Gzip)
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(collected.trailers(), Some(&trailers));
+        let data = collected.to_bytes();
+        assert_eq!(decompress(ContentEncoding::Gzip, &data), b"hello");
+    }
+}
@@ -0,0 +1,111 @@
+//! Bodies with no data.
+
+use std::{
+    convert::Infallible,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use super::ResponseBody;
+
+/// Body that is always empty
+///
+/// Unlike an empty `ResponseBody`, this doesn't allocate a duplex stream, so
+/// it suits responses that never have a body, like `204 No Content`, `304 Not
+/// Modified` and redirects. It ends immediately whether read as a `Body` or
+/// through `AsyncRead`.
+///
+/// Where any body type is accepted, combine it with `ResponseBody` using
+/// `http_body_util::Either`. Where a `ResponseBody` is required, convert it
+/// with `ResponseBody::from`, which allocates an already finished body.
+///
+/// # Examples
+///
+/// ```
+/// use http_body_util::Either;
+/// use http_handler::ResponseBody;
+/// use http_handler::body::Empty;
+///
+/// fn body(data: Option<&'static str>) -> Either<Empty, ResponseBody> {
+///     match data {
+///         Some(data) => Either::Right(ResponseBody::from_data_sync(data.into()).unwrap()),
+///         None => Either::Left(Empty::new()),
+///     }
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Empty;
+
+impl Empty {
+    /// Create an empty body
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Body for Empty {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Poll::Ready(None)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        true
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(0)
+    }
+}
+
+impl AsyncRead for Empty {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl From<Empty> for ResponseBody {
+    fn from(_: Empty) -> Self {
+        ResponseBody::buffered(Bytes::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{BodyExt, Either};
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_empty_body() {
+        let mut body = Empty::new();
+        assert!(body.is_end_stream());
+        assert_eq!(body.size_hint().exact(), Some(0));
+        assert!(body.frame().await.is_none());
+
+        let mut data = Vec::new();
+        assert_eq!(body.read_to_end(&mut data).await.unwrap(), 0);
+
+        // Usable alongside a streaming body
+        let either: Either<Empty, ResponseBody> = Either::Left(body);
+        assert!(either.collect().await.unwrap().to_bytes().is_empty());
+
+        let mut body = ResponseBody::from(Empty);
+        assert!(body.frame().await.is_none());
+        assert!(body.is_end_stream());
+    }
+}
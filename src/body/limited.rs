@@ -0,0 +1,191 @@
+//! Size limits for bodies.
+
+use std::{
+    error::Error,
+    fmt,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use bytes::Buf;
+use http_body::{Body, Frame, SizeHint};
+
+/// Body wrapper that fails once the inner body produces too much data
+///
+/// Data frames are counted as they pass through, without buffering. The
+/// frame that would take the total past `max_bytes` is dropped and an error
+/// is yielded instead, after which the body ends, so no data beyond the
+/// limit is ever produced. Trailers pass through uncounted.
+///
+/// Created with `LimitedBody::new` or `ResponseBody::limited`.
+#[derive(Debug)]
+pub struct LimitedBody<B> {
+    inner: B,
+    max_bytes: usize,
+    read: usize,
+    exceeded: bool,
+}
+
+impl<B> LimitedBody<B> {
+    /// Wrap a body, failing it once it produces more than `max_bytes`
+    pub fn new(inner: B, max_bytes: usize) -> Self {
+        Self {
+            inner,
+            max_bytes,
+            read: 0,
+            exceeded: false,
+        }
+    }
+
+    /// Get the configured limit in bytes
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Get a reference to the inner body
+    pub fn get_ref(&self) -> &B {
+        &self.inner
+    }
+
+    /// Unwrap into the inner body
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+/// Error from a `LimitedBody`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitedBodyError<E> {
+    /// The body exceeded the limit (in bytes)
+    TooLarge(usize),
+    /// The inner body failed
+    Body(E),
+}
+
+impl<E: fmt::Display> fmt::Display for LimitedBodyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitedBodyError::TooLarge(limit) => {
+                write!(f, "Body exceeds size limit of {} bytes", limit)
+            }
+            LimitedBodyError::Body(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for LimitedBodyError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LimitedBodyError::TooLarge(_) => None,
+            LimitedBodyError::Body(e) => Some(e),
+        }
+    }
+}
+
+impl<B> Body for LimitedBody<B>
+where
+    B: Body + Unpin,
+{
+    type Data = B::Data;
+    type Error = LimitedBodyError<B::Error>;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if this.exceeded {
+            return Poll::Ready(None);
+        }
+
+        let frame = match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+            Some(Ok(frame)) => frame,
+            Some(Err(e)) => return Poll::Ready(Some(Err(LimitedBodyError::Body(e)))),
+            None => return Poll::Ready(None),
+        };
+
+        if let Some(data) = frame.data_ref() {
+            let total = this.read.saturating_add(data.remaining());
+            if total > this.max_bytes {
+                this.exceeded = true;
+                return Poll::Ready(Some(Err(LimitedBodyError::TooLarge(this.max_bytes))));
+            }
+            this.read = total;
+        }
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.exceeded || self.inner.is_end_stream()
+    }
+
+    /// Returns the inner body's hint, clamped to what is left of the limit
+    fn size_hint(&self) -> SizeHint {
+        if self.exceeded {
+            return SizeHint::with_exact(0);
+        }
+
+        let remaining = (self.max_bytes - self.read) as u64;
+        let inner = self.inner.size_hint();
+        let mut hint = SizeHint::new();
+        hint.set_lower(inner.lower().min(remaining));
+        hint.set_upper(
+            inner
+                .upper()
+                .map_or(remaining, |upper| upper.min(remaining)),
+        );
+        hint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResponseBody;
+    use bytes::Bytes;
+    use http_body_util::BodyExt;
+    use tokio::io::AsyncWriteExt;
+
+    fn write_chunks(chunks: &[&'static str]) -> ResponseBody {
+        let body = ResponseBody::new();
+        let mut writer = body.clone();
+        let chunks = chunks.to_vec();
+        tokio::spawn(async move {
+            for chunk in chunks {
+                writer.write_all(chunk.as_bytes()).await.unwrap();
+            }
+            writer.shutdown().await.unwrap();
+        });
+        body
+    }
+
+    #[tokio::test]
+    async fn test_limited_body_within_limit() {
+        for max_bytes in [10, 8] {
+            let body = write_chunks(&["abcd", "efgh"]).limited(max_bytes);
+            let collected = body.collect().await.unwrap();
+            assert_eq!(collected.to_bytes(), Bytes::from("abcdefgh"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limited_body_over_limit() {
+        let body = ResponseBody::from_data_sync(Bytes::from("0123456789")).unwrap();
+        let mut body = body.limited(4);
+        assert_eq!(body.size_hint().exact(), Some(4));
+
+        let err = body.frame().await.unwrap().unwrap_err();
+        assert_eq!(err, LimitedBodyError::TooLarge(4));
+        assert!(body.is_end_stream());
+        assert!(body.frame().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_limited_body_size_hint() {
+        let body = ResponseBody::from_data_sync(Bytes::from("hello")).unwrap();
+        assert_eq!(body.limited(10).size_hint().exact(), Some(5));
+
+        let hint = ResponseBody::new().limited(10).size_hint();
+        assert_eq!((hint.lower(), hint.upper()), (0, Some(10)));
+    }
+}
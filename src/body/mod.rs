@@ -0,0 +1,2184 @@
+use std::{
+    any::Any,
+    fmt,
+    future::Future,
+    io,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    task::{Context, Poll, Waker, ready},
+    time::Duration,
+};
+
+use bytes::{Bytes, BytesMut};
+use futures_core::Stream;
+use futures_util::{FutureExt, task::AtomicWaker};
+use http::HeaderMap;
+use http_body::{Body, Frame, SizeHint};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, DuplexStream},
+    sync::{Mutex, OwnedMutexGuard, mpsc},
+    time::{Instant, Sleep},
+};
+
+mod compressed;
+mod empty;
+mod limited;
+pub(crate) mod pump;
+mod sniff;
+mod throttle;
+mod timeout;
+mod writer;
+
+pub(crate) use compressed::compressed_len;
+pub use compressed::{CompressedBody, CompressedBodyError, ContentEncoding};
+pub use empty::Empty;
+pub use limited::{LimitedBody, LimitedBodyError};
+pub use throttle::Throttled;
+pub use timeout::{TimeoutBody, TimeoutBodyError};
+pub use writer::{RequestBodyWriter, ResponseBodyWriter};
+
+/// Error type for stream operations
+#[derive(Debug, Clone)]
+pub enum StreamError {
+    /// The stream has been closed and cannot accept more data
+    StreamClosed,
+    /// The stream receiver has already been consumed and cannot be taken again
+    StreamAlreadyConsumed,
+    /// The body exceeded the configured size limit (in bytes)
+    BodyTooLarge(usize),
+    /// The body was not read completely before its deadline
+    DeadlineExceeded,
+    /// A clone of the body tried to read while another clone is reading
+    ConcurrentRead,
+    /// An I/O error occurred
+    IoError {
+        /// Kind of the underlying I/O error
+        kind: io::ErrorKind,
+        /// Description of the underlying I/O error
+        message: String,
+    },
+}
+
+impl StreamError {
+    /// Get the kind of the underlying I/O error, if this is an I/O error
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            StreamError::IoError { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::StreamClosed => write!(f, "Stream closed"),
+            StreamError::StreamAlreadyConsumed => write!(f, "Stream already consumed"),
+            StreamError::BodyTooLarge(limit) => {
+                write!(f, "Body exceeds size limit of {} bytes", limit)
+            }
+            StreamError::DeadlineExceeded => write!(f, "Body read deadline exceeded"),
+            StreamError::ConcurrentRead => {
+                write!(f, "Body is already being read through another clone")
+            }
+            StreamError::IoError { message, .. } => write!(f, "I/O error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+/// A piece of a body passed over a channel
+///
+/// See `RequestBody::take_request_rx` and
+/// `RequestBody::create_response_channel`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamChunk<T = Bytes> {
+    /// The next part of the body
+    Data(T),
+    /// The body is complete
+    End,
+}
+
+/// Number of chunks a body channel holds before the sender waits
+const CHUNK_CHANNEL_CAPACITY: usize = 16;
+
+/// Extract the message from a caught panic payload
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Default duplex buffer size for request and response bodies (16KB)
+const DEFAULT_BUFFER_SIZE: usize = 16384;
+
+/// Pending lock on a body stream mutex
+type LockFuture = Pin<Box<dyn Future<Output = OwnedMutexGuard<DuplexStream>> + Send + Sync>>;
+
+/// Lock a body stream mutex from a poll function
+///
+/// When the mutex is held elsewhere, the lock future is kept in `pending` so
+/// the task is woken once the mutex is released, rather than being polled
+/// again straight away.
+fn poll_lock(
+    stream: &Arc<Mutex<DuplexStream>>,
+    pending: &mut Option<LockFuture>,
+    cx: &mut Context<'_>,
+) -> Poll<OwnedMutexGuard<DuplexStream>> {
+    if pending.is_none()
+        && let Ok(guard) = Arc::clone(stream).try_lock_owned()
+    {
+        return Poll::Ready(guard);
+    }
+
+    let lock = pending.get_or_insert_with(|| Box::pin(Arc::clone(stream).lock_owned()));
+    let guard = ready!(lock.as_mut().poll(cx));
+    *pending = None;
+    Poll::Ready(guard)
+}
+
+/// Write all of `data` to a freshly created duplex stream and shut it down
+/// without awaiting.
+///
+/// This only succeeds if the data fits in the duplex buffer, as nothing reads
+/// from the other side while writing.
+fn write_now(
+    stream: &Arc<Mutex<DuplexStream>>,
+    buffer_size: usize,
+    data: &[u8],
+) -> Result<(), StreamError> {
+    if data.len() > buffer_size {
+        return Err(StreamError::BodyTooLarge(buffer_size));
+    }
+
+    let mut stream = stream.try_lock().map_err(|_| StreamError::IoError {
+        kind: io::ErrorKind::Other,
+        message: "body stream is locked".to_string(),
+    })?;
+    let mut cx = Context::from_waker(Waker::noop());
+
+    let mut written = 0;
+    while written < data.len() {
+        match Pin::new(&mut *stream).poll_write(&mut cx, &data[written..]) {
+            Poll::Ready(Ok(0)) => return Err(StreamError::StreamClosed),
+            Poll::Ready(Ok(n)) => written += n,
+            Poll::Ready(Err(e)) => return Err(e.into()),
+            Poll::Pending => return Err(StreamError::BodyTooLarge(buffer_size)),
+        }
+    }
+
+    match Pin::new(&mut *stream).poll_shutdown(&mut cx) {
+        Poll::Ready(result) => result.map_err(Into::into),
+        Poll::Pending => Err(StreamError::StreamClosed),
+    }
+}
+
+impl From<io::Error> for StreamError {
+    fn from(err: io::Error) -> Self {
+        // Unwrap stream errors surfaced through AsyncRead/AsyncWrite
+        if let Some(inner) = err.get_ref().and_then(|e| e.downcast_ref::<StreamError>()) {
+            return inner.clone();
+        }
+        StreamError::IoError {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<StreamError> for io::Error {
+    fn from(err: StreamError) -> Self {
+        match err {
+            StreamError::IoError { kind, message } => io::Error::new(kind, message),
+            StreamError::DeadlineExceeded => io::Error::new(io::ErrorKind::TimedOut, err),
+            other => io::Error::other(other),
+        }
+    }
+}
+
+/// Request body with duplex stream for bidirectional I/O
+///
+/// This type holds both halves of a duplex stream pair. One half is used for polling
+/// (by the handler), and the other half is accessible via `writer()` for external writes.
+///
+/// # Cloning Behavior
+///
+/// RequestBody is clonable, and clones share the same underlying streams via Arc<Mutex>.
+/// This allows NAPI to clone Request objects while preserving the streams.
+///
+/// Any clone may write, but only one clone may read at a time, since reads from
+/// several clones would each get part of the data. The first clone to read
+/// becomes the reader. Reads from other clones fail with an I/O error wrapping
+/// `StreamError::ConcurrentRead` until the reader is dropped.
+pub struct RequestBody {
+    // The half used for polling/reading by the handler
+    read_side: Arc<Mutex<DuplexStream>>,
+    // The half used by external code to write data into the body
+    write_side: Arc<Mutex<DuplexStream>>,
+    buffer_size: usize,
+    // Maximum number of bytes that may be read from the body
+    limit: Option<usize>,
+    // Number of bytes read so far, shared between clones
+    bytes_read: Arc<AtomicUsize>,
+    // Error reported by the writer, surfaced to the reader once data runs out
+    error: Arc<std::sync::Mutex<Option<String>>>,
+    // When the whole body must have been read by
+    deadline: Option<Instant>,
+    // Timer for `deadline`, created on first poll since it needs a runtime
+    deadline_timer: Option<Pin<Box<Sleep>>>,
+    // Whether a read failed because the deadline passed, shared between clones
+    deadline_exceeded: Arc<AtomicBool>,
+    // Identifies this handle among its clones
+    id: usize,
+    // Id of the clone currently reading, or 0 if none is, shared between clones
+    reader: Arc<AtomicUsize>,
+    // Data read by `peek` but not yet returned by a read, shared between clones
+    peeked: Arc<std::sync::Mutex<BytesMut>>,
+    // Whether `take_request_rx` was called, shared between clones
+    rx_taken: Arc<AtomicBool>,
+    // Pending locks on the stream mutexes, not shared with clones
+    read_lock: Option<LockFuture>,
+    write_lock: Option<LockFuture>,
+}
+
+/// Source of `RequestBody` handle ids, starting at 1 so 0 can mean "none"
+static NEXT_BODY_ID: AtomicUsize = AtomicUsize::new(1);
+
+fn next_body_id() -> usize {
+    NEXT_BODY_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+impl RequestBody {
+    /// Create a new request body with specified buffer size
+    pub fn new_with_buffer_size(buffer_size: usize) -> Self {
+        let (read_side, write_side) = tokio::io::duplex(buffer_size);
+
+        Self {
+            read_side: Arc::new(Mutex::new(read_side)),
+            write_side: Arc::new(Mutex::new(write_side)),
+            buffer_size,
+            limit: None,
+            bytes_read: Arc::new(AtomicUsize::new(0)),
+            error: Arc::new(std::sync::Mutex::new(None)),
+            deadline: None,
+            deadline_timer: None,
+            deadline_exceeded: Arc::new(AtomicBool::new(false)),
+            id: next_body_id(),
+            reader: Arc::new(AtomicUsize::new(0)),
+            peeked: Arc::new(std::sync::Mutex::new(BytesMut::new())),
+            rx_taken: Arc::new(AtomicBool::new(false)),
+            read_lock: None,
+            write_lock: None,
+        }
+    }
+
+    /// Create a new request body with default buffer size (16KB)
+    pub fn new() -> Self {
+        Self::new_with_buffer_size(DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Create from buffered data (writes data to stream immediately)
+    ///
+    /// The duplex buffer is sized to fit the data, so this never waits for a
+    /// reader, whatever the size of the data.
+    pub async fn from_data(data: Bytes) -> Result<Self, StreamError> {
+        Ok(Self::buffered(data))
+    }
+
+    /// Create from buffered data without awaiting
+    ///
+    /// The data is written into the duplex buffer directly, so it must fit in
+    /// the default buffer size (16KB). Larger data fails with
+    /// `StreamError::BodyTooLarge`.
+    pub fn from_data_sync(data: Bytes) -> Result<Self, StreamError> {
+        let body = Self::new();
+        write_now(&body.write_side, body.buffer_size, &data)?;
+        Ok(body)
+    }
+
+    /// Create from buffered data without awaiting, whatever its size
+    ///
+    /// Data that fits in the default buffer size (16KB) is written directly,
+    /// like `from_data_sync`. Larger data is written by a spawned task as the
+    /// body is read, so this must be called from within a Tokio runtime.
+    pub fn from_data_streaming(data: Bytes) -> Self {
+        let body = Self::new();
+        if data.len() <= body.buffer_size {
+            write_now(&body.write_side, body.buffer_size, &data).expect("data fits in the buffer");
+            return body;
+        }
+
+        let write_side = Arc::clone(&body.write_side);
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            let mut stream = write_side.lock().await;
+            // The reader may go away before all data is written
+            if stream.write_all(&data).await.is_ok() {
+                let _ = stream.shutdown().await;
+            }
+        });
+
+        body
+    }
+
+    /// Create from buffered data, sizing the duplex buffer to fit it
+    pub(crate) fn buffered(data: Bytes) -> Self {
+        let body = Self::new_with_buffer_size(data.len().max(DEFAULT_BUFFER_SIZE));
+        write_now(&body.write_side, body.buffer_size, &data)
+            .expect("buffer is sized to fit the data");
+        body
+    }
+
+    /// Get a writer for pushing data into this body from outside the handler
+    ///
+    /// The writer shares the write side with this body and its clones, the
+    /// same as writing through a clone, but can't read. Call
+    /// `RequestBodyWriter::finish` once done so the reader sees the end.
+    pub fn writer(&self) -> RequestBodyWriter {
+        RequestBodyWriter::new(Arc::clone(&self.write_side), Arc::clone(&self.error))
+    }
+
+    /// Get the buffer size for this request body
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Limit the number of bytes that can be read from this body
+    ///
+    /// Once a read would go past the limit, it fails with an I/O error wrapping
+    /// `StreamError::BodyTooLarge`. None of the data beyond the limit is returned.
+    /// Use `StreamError::from` on the I/O error to recover the stream error.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Get the size limit for this request body, if any
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Require the whole body to be read by `deadline`
+    ///
+    /// Once the deadline passes, reads fail with an I/O error of kind
+    /// `TimedOut` wrapping `StreamError::DeadlineExceeded`, even if data is
+    /// available. Clones keep the same deadline.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self.deadline_timer = None;
+        self
+    }
+
+    /// Get the deadline set with `with_deadline`, if any
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Check whether a read from this body or a clone failed because the
+    /// deadline passed
+    pub fn deadline_exceeded(&self) -> bool {
+        self.deadline_exceeded.load(Ordering::Acquire)
+    }
+
+    /// Read up to `n` bytes from the start of the body without consuming them
+    ///
+    /// Fewer bytes are returned only if the body ends first. The peeked data
+    /// is kept and returned again by the following reads, so the body still
+    /// reads in full afterwards. Peeking counts as reading for the size limit,
+    /// the deadline and which clone is the reader. If reading fails, the error
+    /// is returned and the data peeked so far is kept.
+    pub async fn peek(&mut self, n: usize) -> io::Result<Bytes> {
+        use tokio::io::AsyncReadExt;
+
+        // Take the peeked data out so the reads below go to the stream
+        let mut peeked = std::mem::take(&mut *self.peeked.lock().unwrap());
+        let mut result = Ok(());
+        let mut chunk = vec![0u8; n.saturating_sub(peeked.len())];
+        while peeked.len() < n {
+            let wanted = n - peeked.len();
+            match self.read(&mut chunk[..wanted]).await {
+                Ok(0) => break,
+                Ok(read) => peeked.extend_from_slice(&chunk[..read]),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let data = Bytes::copy_from_slice(&peeked[..n.min(peeked.len())]);
+        *self.peeked.lock().unwrap() = peeked;
+        result.map(|()| data)
+    }
+
+    /// Guess the MIME type of the body from its first bytes
+    ///
+    /// Peeks at up to 512 bytes, so the body still reads in full afterwards.
+    /// PNG, JPEG, GIF, PDF and gzip are recognised by their magic bytes, and
+    /// JSON and HTML by their first non-whitespace characters. This is meant
+    /// for bodies without a useful `Content-Type`, such as uploads sent as
+    /// `application/octet-stream`. Returns `Ok(None)` for anything else.
+    pub async fn sniff_content_type(&mut self) -> io::Result<Option<&'static str>> {
+        let data = self.peek(sniff::SNIFF_LEN).await?;
+        Ok(sniff::sniff(&data))
+    }
+
+    /// Read the rest of the body into memory
+    ///
+    /// Works the same whether the body was built from data or is being
+    /// written to elsewhere, waiting for the writer to shut down. Errors
+    /// from reading, including the size limit and deadline, are returned
+    /// as `StreamError`s.
+    ///
+    /// Reading goes through a clone standing in for this handle, so it only
+    /// needs `&self`, such as a body borrowed from a request.
+    pub async fn collect(&self) -> Result<Bytes, StreamError> {
+        self.reading_handle().collect_up_to(None).await
+    }
+
+    /// Read the rest of the body into memory, failing with
+    /// `StreamError::BodyTooLarge` once more than `max` bytes are read
+    ///
+    /// Unlike `with_limit`, this only applies to this call.
+    pub async fn collect_with_limit(&self, max: usize) -> Result<Bytes, StreamError> {
+        self.reading_handle().collect_up_to(Some(max)).await
+    }
+
+    /// Clone this handle keeping its id, so it reads as this handle would,
+    /// even if this handle is already the reader
+    fn reading_handle(&self) -> Self {
+        let mut handle = self.clone();
+        handle.id = self.id;
+        handle
+    }
+
+    async fn collect_up_to(&mut self, max: Option<usize>) -> Result<Bytes, StreamError> {
+        use tokio::io::AsyncReadExt;
+
+        let mut data = BytesMut::new();
+        loop {
+            data.reserve(8192);
+            if self.read_buf(&mut data).await? == 0 {
+                return Ok(data.freeze());
+            }
+            if let Some(max) = max
+                && data.len() > max
+            {
+                return Err(StreamError::BodyTooLarge(max));
+            }
+        }
+    }
+
+    /// Create response body with the same buffer size
+    /// Returns a new ResponseBody that uses a separate duplex stream
+    ///
+    /// Handlers streaming partial data, like server-sent events, should call
+    /// `ResponseBody::flush` on their writer after each chunk so the reader
+    /// sees it immediately, even if the body is coalesced.
+    pub fn create_response(&self) -> ResponseBody {
+        ResponseBody::new_with_buffer_size(self.buffer_size)
+    }
+
+    /// Create a response body fed through a channel
+    ///
+    /// Each `StreamChunk::Data` sent is written to the body, and
+    /// `StreamChunk::End` completes it. Sending an error ends the body with
+    /// that error for the reader, as does dropping the sender before `End`,
+    /// since the response would otherwise look complete when it is not.
+    ///
+    /// A task is spawned to do the writing, so this must be called from
+    /// within a Tokio runtime.
+    pub fn create_response_channel(
+        &self,
+    ) -> (ResponseBody, mpsc::Sender<Result<StreamChunk, StreamError>>) {
+        let (tx, mut rx) =
+            mpsc::channel::<Result<StreamChunk, StreamError>>(CHUNK_CHANNEL_CAPACITY);
+        let body = self.create_response();
+
+        body.spawn_writer(|mut writer| async move {
+            use tokio::io::AsyncWriteExt;
+            loop {
+                match rx.recv().await {
+                    Some(Ok(StreamChunk::Data(chunk))) => {
+                        // Stop early if the reader has gone away
+                        if writer.write_all(&chunk).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(StreamChunk::End)) => break,
+                    Some(Err(e)) => {
+                        writer.set_error(e.to_string());
+                        break;
+                    }
+                    None => {
+                        writer.set_error("response channel closed before the end of the body");
+                        break;
+                    }
+                }
+            }
+            let _ = writer.shutdown().await;
+        });
+
+        (body, tx)
+    }
+
+    /// Take a channel receiving the body in chunks
+    ///
+    /// A task is spawned that reads the body, sending each chunk read as
+    /// `StreamChunk::Data`, then `StreamChunk::End`, or the error that stopped
+    /// reading. The task becomes the reader, see the cloning notes above, and
+    /// stops if the receiver is dropped. Only one receiver is handed out across
+    /// all clones; later calls fail with `StreamError::StreamAlreadyConsumed`.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn take_request_rx(
+        &self,
+    ) -> Result<mpsc::Receiver<Result<StreamChunk, StreamError>>, StreamError> {
+        if self.rx_taken.swap(true, Ordering::AcqRel) {
+            return Err(StreamError::StreamAlreadyConsumed);
+        }
+
+        let (tx, rx) = mpsc::channel(CHUNK_CHANNEL_CAPACITY);
+        let mut body = self.clone();
+        tokio::spawn(async move {
+            use tokio::io::AsyncReadExt;
+            let mut buf = BytesMut::with_capacity(body.buffer_size);
+            loop {
+                buf.reserve(body.buffer_size);
+                let item = match body.read_buf(&mut buf).await {
+                    Ok(0) => Ok(StreamChunk::End),
+                    Ok(_) => Ok(StreamChunk::Data(buf.split().freeze())),
+                    Err(e) => Err(StreamError::from(e)),
+                };
+                let last = !matches!(item, Ok(StreamChunk::Data(_)));
+                if tx.send(item).await.is_err() || last {
+                    return;
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+}
+
+impl Default for RequestBody {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a stream mutex is currently held, checked without blocking
+fn is_locked(stream: &Mutex<DuplexStream>) -> bool {
+    stream.try_lock().is_err()
+}
+
+// Hand-written so formatting never waits on the stream mutexes
+impl fmt::Debug for RequestBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBody")
+            .field("buffer_size", &self.buffer_size)
+            .field("limit", &self.limit)
+            .field("bytes_read", &self.bytes_read.load(Ordering::Relaxed))
+            .field("deadline", &self.deadline)
+            .field("read_locked", &is_locked(&self.read_side))
+            .field("write_locked", &is_locked(&self.write_side))
+            .field("handles", &Arc::strong_count(&self.read_side))
+            .finish()
+    }
+}
+
+impl Clone for RequestBody {
+    fn clone(&self) -> Self {
+        Self {
+            read_side: Arc::clone(&self.read_side),
+            write_side: Arc::clone(&self.write_side),
+            buffer_size: self.buffer_size,
+            limit: self.limit,
+            bytes_read: Arc::clone(&self.bytes_read),
+            error: Arc::clone(&self.error),
+            deadline: self.deadline,
+            deadline_timer: None,
+            deadline_exceeded: Arc::clone(&self.deadline_exceeded),
+            id: next_body_id(),
+            reader: Arc::clone(&self.reader),
+            peeked: Arc::clone(&self.peeked),
+            rx_taken: Arc::clone(&self.rx_taken),
+            read_lock: None,
+            write_lock: None,
+        }
+    }
+}
+
+// Lets `RequestExt` methods that read the body be called on requests
+impl AsMut<RequestBody> for http::Request<RequestBody> {
+    fn as_mut(&mut self) -> &mut RequestBody {
+        self.body_mut()
+    }
+}
+
+// Let another clone take over reading once the reader is gone
+impl Drop for RequestBody {
+    fn drop(&mut self) {
+        let _ = self
+            .reader
+            .compare_exchange(self.id, 0, Ordering::AcqRel, Ordering::Acquire);
+    }
+}
+
+impl RequestBody {
+    /// Become the reader, unless another clone already is
+    fn claim_reader(&self) -> bool {
+        match self
+            .reader
+            .compare_exchange(0, self.id, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => true,
+            Err(current) => current == self.id,
+        }
+    }
+
+    fn poll_read_stream(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut stream = ready!(poll_lock(&self.read_side, &mut self.read_lock, cx));
+
+        let Some(limit) = self.limit else {
+            return Pin::new(&mut *stream).poll_read(cx, buf);
+        };
+
+        // Read at most one byte past the limit so exceeding it can be detected
+        // without handing any of the excess data to the caller.
+        let read_so_far = self.bytes_read.load(Ordering::Acquire);
+        let allowed = limit.saturating_sub(read_so_far).saturating_add(1);
+        let mut limited = buf.take(allowed.min(buf.remaining()));
+        match Pin::new(&mut *stream).poll_read(cx, &mut limited) {
+            Poll::Ready(Ok(())) => {}
+            other => return other,
+        }
+
+        let n = limited.filled().len();
+        if read_so_far + n > limit {
+            return Poll::Ready(Err(io::Error::other(StreamError::BodyTooLarge(limit))));
+        }
+
+        // SAFETY: The `n` bytes were filled by the read into `limited`, which
+        // borrows the unfilled portion of `buf`.
+        unsafe {
+            buf.assume_init(n);
+        }
+        buf.advance(n);
+        self.bytes_read.fetch_add(n, Ordering::AcqRel);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for RequestBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if !self.claim_reader() {
+            return Poll::Ready(Err(io::Error::other(StreamError::ConcurrentRead)));
+        }
+
+        if let Some(deadline) = self.deadline {
+            let timer = self
+                .deadline_timer
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            if timer.as_mut().poll(cx).is_ready() {
+                self.deadline_exceeded.store(true, Ordering::Release);
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    StreamError::DeadlineExceeded,
+                )));
+            }
+        }
+
+        // Hand back peeked data before reading more
+        {
+            let mut peeked = self.peeked.lock().unwrap();
+            if !peeked.is_empty() {
+                let n = peeked.len().min(buf.remaining());
+                buf.put_slice(&peeked.split_to(n));
+                return Poll::Ready(Ok(()));
+            }
+        }
+
+        let initial_filled = buf.filled().len();
+        let result = self.poll_read_stream(cx, buf);
+
+        // At EOF, report any error from the writer instead of a clean end
+        if let Poll::Ready(Ok(())) = result
+            && buf.filled().len() == initial_filled
+            && let Some(error) = self.error.lock().unwrap().take()
+        {
+            return Poll::Ready(Err(io::Error::other(error)));
+        }
+
+        result
+    }
+}
+
+impl AsyncWrite for RequestBody {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.write_side, &mut this.write_lock, cx));
+        Pin::new(&mut *stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.write_side, &mut this.write_lock, cx));
+        Pin::new(&mut *stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.write_side, &mut this.write_lock, cx));
+        Pin::new(&mut *stream).poll_shutdown(cx)
+    }
+}
+
+/// Response body with duplex stream for bidirectional I/O
+///
+/// This type holds both halves of a duplex stream pair and implements `http-body::Body`.
+/// One half is used for polling (reading frames), and the other half is accessible via
+/// `stream()` for external writes (e.g., handler writing response data).
+///
+/// # Cloning Behavior
+///
+/// ResponseBody is clonable, and clones share the same underlying streams via Arc<Mutex>.
+/// This allows NAPI to clone Response objects. The `poll_frame` implementation handles
+/// concurrent access via the mutex.
+///
+/// ## Reading Frames
+/// To read frames from this body, use `BodyExt::frame()` from http-body-util.
+pub struct ResponseBody {
+    // The half used for polling/reading frames
+    read_side: Arc<Mutex<DuplexStream>>,
+    // The half used by handlers to write response data
+    write_side: Arc<Mutex<DuplexStream>>,
+    buffer_size: usize,
+    // When the body was created, used as the start of time-to-first-byte
+    created_at: Instant,
+    // When the first non-empty data frame was produced
+    first_byte_at: Arc<OnceLock<Instant>>,
+    // Error reported by the writer, surfaced to the reader once data runs out
+    error: Arc<std::sync::Mutex<Option<String>>>,
+    // When the whole body must have been read by
+    deadline: Option<Instant>,
+    // Timer for `deadline`, created on first poll since it needs a runtime
+    deadline_timer: Option<Pin<Box<Sleep>>>,
+    // Minimum data frame size when coalescing small writes
+    coalesce: Option<usize>,
+    // Data read but not yet emitted while coalescing
+    coalesce_buffer: BytesMut,
+    // Flush generation emitted up to while coalescing
+    flushed: usize,
+    // Signals flushes from writers to a coalescing reader
+    flush_signal: Arc<FlushSignal>,
+    // Whether the reader has seen the end of the body
+    ended: bool,
+    // Total length, when the body was fully written on creation
+    exact_len: Option<u64>,
+    // Callback for when reading this handle finishes, not shared with clones
+    finish: Option<FinishObserver>,
+    // Trailers to send after the data, shared between clones
+    trailers: Arc<std::sync::Mutex<Option<HeaderMap>>>,
+    // Pending locks on the stream mutexes, not shared with clones
+    read_lock: Option<LockFuture>,
+    write_lock: Option<LockFuture>,
+}
+
+/// Counts the bytes read through a response body handle, reporting them once
+/// reading reaches the end or the handle is dropped
+struct FinishObserver {
+    bytes: u64,
+    callback: Box<dyn FnOnce(u64, bool) + Send + Sync>,
+}
+
+/// Tells a coalescing reader that a writer flushed
+#[derive(Default)]
+struct FlushSignal {
+    generation: AtomicUsize,
+    waker: AtomicWaker,
+}
+
+impl FlushSignal {
+    fn notify(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+        self.waker.wake();
+    }
+}
+
+impl ResponseBody {
+    /// Create a new response body with specified buffer size
+    pub fn new_with_buffer_size(buffer_size: usize) -> Self {
+        let (read_side, write_side) = tokio::io::duplex(buffer_size);
+
+        Self {
+            read_side: Arc::new(Mutex::new(read_side)),
+            write_side: Arc::new(Mutex::new(write_side)),
+            buffer_size,
+            created_at: Instant::now(),
+            first_byte_at: Arc::new(OnceLock::new()),
+            error: Arc::new(std::sync::Mutex::new(None)),
+            deadline: None,
+            deadline_timer: None,
+            coalesce: None,
+            coalesce_buffer: BytesMut::new(),
+            flushed: 0,
+            flush_signal: Arc::new(FlushSignal::default()),
+            ended: false,
+            exact_len: None,
+            finish: None,
+            trailers: Arc::new(std::sync::Mutex::new(None)),
+            read_lock: None,
+            write_lock: None,
+        }
+    }
+
+    /// Create a new response body with default buffer size (16KB)
+    pub fn new() -> Self {
+        Self::new_with_buffer_size(DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Create from buffered data without awaiting
+    ///
+    /// The data is written into the duplex buffer directly, so it must fit in
+    /// the default buffer size (16KB). Larger data fails with
+    /// `StreamError::BodyTooLarge`.
+    pub fn from_data_sync(data: Bytes) -> Result<Self, StreamError> {
+        let mut body = Self::new();
+        write_now(&body.write_side, body.buffer_size, &data)?;
+        body.exact_len = Some(data.len() as u64);
+        Ok(body)
+    }
+
+    /// Create from buffered data, sizing the duplex buffer to fit it
+    pub(crate) fn buffered(data: Bytes) -> Self {
+        let mut body = Self::new_with_buffer_size(data.len().max(DEFAULT_BUFFER_SIZE));
+        write_now(&body.write_side, body.buffer_size, &data)
+            .expect("buffer is sized to fit the data");
+        body.exact_len = Some(data.len() as u64);
+        body
+    }
+
+    /// Create a response body fed by a stream of chunks
+    ///
+    /// A task is spawned to write each chunk into the body as it is read, so
+    /// this must be called from within a Tokio runtime. The body ends when the
+    /// stream does. If the stream yields an error, the body ends there and the
+    /// reader receives the error after any data already written.
+    pub fn from_stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+        E: fmt::Display + Send,
+    {
+        let body = Self::new();
+        let write_side = Arc::clone(&body.write_side);
+        let error = Arc::clone(&body.error);
+
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            let mut stream = std::pin::pin!(stream);
+            let mut writer = write_side.lock().await;
+
+            while let Some(item) = std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+                match item {
+                    Ok(chunk) => {
+                        // Stop early if the reader has gone away
+                        if writer.write_all(&chunk).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        *error.lock().unwrap() = Some(e.to_string());
+                        break;
+                    }
+                }
+            }
+
+            let _ = writer.shutdown().await;
+        });
+
+        body
+    }
+
+    /// Spawn a task that writes into this body
+    ///
+    /// The closure is given a clone of the body to write to. If the task
+    /// panics, the panic is caught, the reader receives an error describing it
+    /// and the body is shut down, rather than leaving the reader waiting for
+    /// data that will never come.
+    pub fn spawn_writer<F, Fut>(&self, f: F) -> tokio::task::JoinHandle<()>
+    where
+        F: FnOnce(ResponseBody) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let task = f(self.clone());
+        let mut body = self.clone();
+
+        tokio::spawn(async move {
+            if let Err(payload) = AssertUnwindSafe(task).catch_unwind().await {
+                body.set_error(format!(
+                    "body writer panicked: {}",
+                    panic_message(&*payload)
+                ));
+                use tokio::io::AsyncWriteExt;
+                let _ = body.shutdown().await;
+            }
+        })
+    }
+
+    /// Record an error for the reader to receive once the data runs out
+    pub(crate) fn set_error(&self, message: impl Into<String>) {
+        *self.error.lock().unwrap() = Some(message.into());
+    }
+
+    /// Get a write-only handle to this body
+    ///
+    /// Writing through it is the same as writing through a clone, except
+    /// that it doesn't keep the body readable: once this body and all its
+    /// clones are dropped, writes fail. Use it for tasks that feed the body,
+    /// so they stop when the reader goes away. Call
+    /// `ResponseBodyWriter::finish` once done so the reader sees the end.
+    pub fn writer(&self) -> ResponseBodyWriter {
+        ResponseBodyWriter::new(
+            Arc::clone(&self.write_side),
+            Arc::clone(&self.error),
+            Arc::clone(&self.trailers),
+            Arc::clone(&self.flush_signal),
+        )
+    }
+
+    /// Set trailers to send once the data ends
+    ///
+    /// `poll_frame` yields them as a final trailers frame after the writer
+    /// shuts down, replacing any set before. They can be set from any clone
+    /// until then, and are sent once across all clones.
+    pub fn set_trailers(&self, trailers: HeaderMap) {
+        *self.trailers.lock().unwrap() = Some(trailers);
+    }
+
+    /// Take the trailers frame to end the body with, if any
+    fn take_trailers(&self) -> Option<Result<Frame<Bytes>, String>> {
+        let trailers = self.trailers.lock().unwrap().take()?;
+        Some(Ok(Frame::trailers(trailers)))
+    }
+
+    /// Get the buffer size for this response body
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Limit how fast this body produces data, in bytes per second
+    ///
+    /// See `Throttled` for how the limit is applied.
+    pub fn throttled(self, bytes_per_sec: u64) -> Throttled<Self> {
+        Throttled::new(self, bytes_per_sec)
+    }
+
+    /// Fail this body once it produces more than `max_bytes`
+    ///
+    /// See `LimitedBody` for how the limit is applied.
+    pub fn limited(self, max_bytes: usize) -> LimitedBody<Self> {
+        LimitedBody::new(self, max_bytes)
+    }
+
+    /// Fail this body if it goes longer than `timeout` without a frame
+    ///
+    /// See `TimeoutBody` for how the timeout is applied.
+    pub fn idle_timeout(self, timeout: Duration) -> TimeoutBody<Self> {
+        TimeoutBody::new(self, timeout)
+    }
+
+    /// Stream this body through while keeping a copy of it, for caching
+    ///
+    /// Returns a body producing the same data, passed on as soon as it is
+    /// read, so the client is not held up. Once the body ends cleanly,
+    /// `on_complete` is called with the whole copy. If reading fails, the
+    /// returned body gets the error after the data read so far and
+    /// `on_complete` is never called, and likewise if the returned body is
+    /// dropped before the end, so partial bodies never reach a cache. A task is
+    /// spawned to do the copying, so this must be called from within a Tokio
+    /// runtime. Trailers and flushes are passed on too.
+    pub fn tee<F>(self, on_complete: F) -> ResponseBody
+    where
+        F: FnOnce(Bytes) + Send + 'static,
+    {
+        let body = Self::new_with_buffer_size(self.buffer_size);
+        let writer = body.writer();
+
+        tokio::spawn(async move {
+            let mut copy = BytesMut::new();
+            let end = pump::pump(self, writer, &mut copy).await;
+            if end == pump::PumpEnd::Complete {
+                on_complete(copy.freeze());
+            }
+        });
+
+        body
+    }
+
+    /// Get the time between creating this body and its first data frame
+    ///
+    /// Returns `None` until a non-empty data frame has been read from the body.
+    /// Clones share the measurement, so it can be read from a clone held by
+    /// whoever records the access log while another clone is being polled.
+    pub fn time_to_first_byte(&self) -> Option<Duration> {
+        self.first_byte_at
+            .get()
+            .map(|at| at.saturating_duration_since(self.created_at))
+    }
+
+    /// Require the whole body to be read by `deadline`
+    ///
+    /// Once the deadline passes, `poll_frame` yields a timeout error instead
+    /// of waiting for more data, capping the total time spent on a slow
+    /// producer. This is separate from any per-read timeout. Clones keep the
+    /// same deadline.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self.deadline_timer = None;
+        self
+    }
+
+    /// Get the deadline set with `with_deadline`, if any
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Make everything written so far available to the reader
+    ///
+    /// Once this returns, the reader's next `poll_frame` produces the written
+    /// data without waiting for more, including when the body is coalesced.
+    /// Written data is always readable straight away otherwise, so this only
+    /// matters for coalesced bodies, but streaming writers should call it
+    /// after each chunk they want delivered promptly, whatever the reader.
+    pub async fn flush(&self) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        self.write_side.lock().await.flush().await?;
+        self.flush_signal.notify();
+        Ok(())
+    }
+
+    /// Collect the whole body, blocking the current thread until it ends
+    ///
+    /// This is for synchronous callers, like FFI getters, that hold a runtime
+    /// handle but are not running inside it. The collection is driven with
+    /// [`Handle::block_on`](tokio::runtime::Handle::block_on), so this panics
+    /// if called from within an async context, and it never returns if the
+    /// writer needs the blocked thread to make progress, as it would on a
+    /// current-thread runtime driven by this same thread. Fails with the
+    /// body's error message if reading fails.
+    pub fn collect_blocking(self, runtime: &tokio::runtime::Handle) -> Result<Bytes, String> {
+        use http_body_util::BodyExt;
+
+        runtime.block_on(async move { self.collect().await.map(|collected| collected.to_bytes()) })
+    }
+
+    /// Call `f` once reading this body finishes
+    ///
+    /// `f` receives the number of bytes read and whether the end of the body
+    /// was reached. It runs when a read finds the end, or when this handle is
+    /// dropped first, for instance because the client went away or reading
+    /// failed, in which case the body counts as incomplete. Only this handle
+    /// is observed: clones, such as those used to write the body, don't carry
+    /// the callback.
+    pub fn on_finish<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(u64, bool) + Send + Sync + 'static,
+    {
+        self.finish = Some(FinishObserver {
+            bytes: 0,
+            callback: Box::new(f),
+        });
+        self
+    }
+
+    /// Coalesce small writes into data frames of at least `min_frame` bytes
+    ///
+    /// Data is held back until `min_frame` bytes are available, a writer
+    /// flushes, or the body ends, so a handler writing many tiny chunks
+    /// produces fewer, larger frames. This only affects `poll_frame`; reading
+    /// through `AsyncRead` is unchanged.
+    pub fn coalesced(mut self, min_frame: usize) -> Self {
+        self.coalesce = Some(min_frame);
+        self
+    }
+
+    /// Record the total length of a body that is written over time
+    ///
+    /// `size_hint` then reports it exactly, so servers can send a
+    /// `Content-Length` instead of using chunked encoding. The writer must
+    /// write exactly `len` bytes. Clones keep the same length.
+    pub fn with_known_length(mut self, len: u64) -> Self {
+        self.exact_len = Some(len);
+        self
+    }
+
+    /// Read until `min_frame` bytes are buffered, a flush, or the end
+    fn poll_coalesced(
+        &mut self,
+        cx: &mut Context<'_>,
+        min_frame: usize,
+    ) -> Poll<Option<Result<Bytes, String>>> {
+        self.flush_signal.waker.register(cx.waker());
+
+        loop {
+            if !self.coalesce_buffer.is_empty() && self.coalesce_buffer.len() >= min_frame {
+                return Poll::Ready(Some(Ok(self.coalesce_buffer.split().freeze())));
+            }
+
+            // Flushes seen before reading cover everything that read returns
+            let generation = self.flush_signal.generation.load(Ordering::Acquire);
+
+            // Read straight into the coalesce buffer, which is lent out
+            // since the read also borrows the body
+            let mut buffer = std::mem::take(&mut self.coalesce_buffer);
+            buffer.reserve(self.buffer_size);
+            let read = tokio_util::io::poll_read_buf(Pin::new(&mut *self), cx, &mut buffer);
+            self.coalesce_buffer = buffer;
+            match read {
+                Poll::Ready(Ok(0)) => {
+                    // EOF, emit whatever is left
+                    self.ended = true;
+                    if self.coalesce_buffer.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    return Poll::Ready(Some(Ok(self.coalesce_buffer.split().freeze())));
+                }
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.to_string()))),
+                Poll::Pending => {
+                    if generation != self.flushed {
+                        self.flushed = generation;
+                        if !self.coalesce_buffer.is_empty() {
+                            return Poll::Ready(Some(Ok(self.coalesce_buffer.split().freeze())));
+                        }
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+impl Default for ResponseBody {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Hand-written so formatting never waits on the stream mutexes
+impl fmt::Debug for ResponseBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseBody")
+            .field("buffer_size", &self.buffer_size)
+            .field("read_locked", &is_locked(&self.read_side))
+            .field("write_locked", &is_locked(&self.write_side))
+            .field("handles", &Arc::strong_count(&self.read_side))
+            .field("time_to_first_byte", &self.time_to_first_byte())
+            .field("deadline", &self.deadline)
+            .field("coalesce", &self.coalesce)
+            .field("exact_len", &self.exact_len)
+            .finish()
+    }
+}
+
+impl Clone for ResponseBody {
+    fn clone(&self) -> Self {
+        Self {
+            read_side: Arc::clone(&self.read_side),
+            write_side: Arc::clone(&self.write_side),
+            buffer_size: self.buffer_size,
+            created_at: self.created_at,
+            first_byte_at: Arc::clone(&self.first_byte_at),
+            error: Arc::clone(&self.error),
+            deadline: self.deadline,
+            deadline_timer: None,
+            coalesce: self.coalesce,
+            coalesce_buffer: BytesMut::new(),
+            flushed: self.flush_signal.generation.load(Ordering::Acquire),
+            flush_signal: Arc::clone(&self.flush_signal),
+            ended: self.ended,
+            exact_len: self.exact_len,
+            finish: None,
+            trailers: Arc::clone(&self.trailers),
+            read_lock: None,
+            write_lock: None,
+        }
+    }
+}
+
+// Report bodies abandoned before the end to the finish callback
+impl Drop for ResponseBody {
+    fn drop(&mut self) {
+        if let Some(observer) = self.finish.take() {
+            (observer.callback)(observer.bytes, false);
+        }
+    }
+}
+
+impl AsyncRead for ResponseBody {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.read_side, &mut this.read_lock, cx));
+
+        let initial_filled = buf.filled().len();
+        let result = Pin::new(&mut *stream).poll_read(cx, buf);
+
+        // At EOF, report any error from the writer instead of a clean end
+        if let Poll::Ready(Ok(())) = result
+            && buf.filled().len() == initial_filled
+            && let Some(error) = this.error.lock().unwrap().take()
+        {
+            return Poll::Ready(Err(io::Error::other(error)));
+        }
+
+        if let Poll::Ready(Ok(())) = result
+            && let Some(observer) = this.finish.as_mut()
+        {
+            let read = buf.filled().len() - initial_filled;
+            observer.bytes += read as u64;
+            if read == 0 && buf.remaining() > 0 {
+                let observer = this.finish.take().expect("observer is present");
+                (observer.callback)(observer.bytes, true);
+            }
+        }
+
+        result
+    }
+}
+
+impl AsyncWrite for ResponseBody {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.write_side, &mut this.write_lock, cx));
+        Pin::new(&mut *stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.write_side, &mut this.write_lock, cx));
+        let result = Pin::new(&mut *stream).poll_flush(cx);
+        if result.is_ready() {
+            this.flush_signal.notify();
+        }
+        result
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.write_side, &mut this.write_lock, cx));
+        Pin::new(&mut *stream).poll_shutdown(cx)
+    }
+}
+
+impl Body for ResponseBody {
+    type Data = Bytes;
+    type Error = String;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        if self.ended {
+            return Poll::Ready(self.take_trailers());
+        }
+
+        // Give up once the deadline passes, even if more data is pending
+        if let Some(deadline) = self.deadline {
+            let timer = self
+                .deadline_timer
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            if timer.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Some(Err("response body deadline exceeded".to_string())));
+            }
+        }
+
+        if let Some(min_frame) = self.coalesce {
+            let Some(result) = ready!(self.poll_coalesced(cx, min_frame)) else {
+                return Poll::Ready(self.take_trailers());
+            };
+            if result.is_ok() {
+                self.first_byte_at.get_or_init(Instant::now);
+            }
+            return Poll::Ready(Some(result.map(Frame::data)));
+        }
+
+        // Try to read data from the stream
+        let capacity = self.buffer_size;
+        match ready!(poll_read_frame(self.as_mut(), cx, capacity)) {
+            Ok(FrameRead::End) => {
+                self.ended = true;
+                Poll::Ready(self.take_trailers())
+            }
+            Ok(FrameRead::Data { data, ended, error }) => {
+                self.ended = ended;
+                // Deliver the error on the next poll instead
+                if let Some(e) = error {
+                    self.set_error(e.to_string());
+                }
+                self.first_byte_at.get_or_init(Instant::now);
+                Poll::Ready(Some(Ok(Frame::data(data))))
+            }
+            Err(e) => Poll::Ready(Some(Err(e.to_string()))),
+        }
+    }
+
+    /// Returns `true` once the end of the body has been read and any
+    /// trailers were sent
+    ///
+    /// The end is noticed when a read finds no more data after the write side
+    /// shut down, which may be while producing the last data frame.
+    fn is_end_stream(&self) -> bool {
+        self.ended && self.trailers.lock().unwrap().is_none()
+    }
+
+    /// Returns the exact length for bodies fully written on creation, like
+    /// those from `from_data_sync`, or given one with `with_known_length`,
+    /// until data is read from them
+    ///
+    /// Other bodies written to over time have an unknown length.
+    fn size_hint(&self) -> SizeHint {
+        if self.ended {
+            return SizeHint::with_exact(0);
+        }
+        match self.exact_len {
+            Some(len) if self.first_byte_at.get().is_none() => SizeHint::with_exact(len),
+            _ => SizeHint::default(),
+        }
+    }
+}
+
+/// Result of reading one frame's worth of data from a body stream
+#[derive(Debug)]
+enum FrameRead {
+    /// The stream ended before any data
+    End,
+    /// Data was read
+    Data {
+        data: Bytes,
+        /// Whether the end of the stream was noticed right after the data
+        ended: bool,
+        /// Error from reading past the data, to be reported after it
+        error: Option<io::Error>,
+    },
+}
+
+/// Read up to `capacity` bytes for a body frame
+///
+/// Reads into a fresh buffer through `poll_read_buf`, which only keeps the
+/// bytes the reader marked as filled, so the frame never contains memory the
+/// reader did not initialize. After some data is read, reads again while
+/// there's room, so reaching the end is noticed now and `is_end_stream` can
+/// report it.
+fn poll_read_frame<R: AsyncRead + ?Sized>(
+    mut reader: Pin<&mut R>,
+    cx: &mut Context<'_>,
+    capacity: usize,
+) -> Poll<io::Result<FrameRead>> {
+    let mut buffer = BytesMut::with_capacity(capacity);
+    if ready!(tokio_util::io::poll_read_buf(
+        reader.as_mut(),
+        cx,
+        &mut buffer
+    ))? == 0
+    {
+        return Poll::Ready(Ok(FrameRead::End));
+    }
+
+    let mut ended = false;
+    let mut error = None;
+    if buffer.len() < buffer.capacity() {
+        match tokio_util::io::poll_read_buf(reader.as_mut(), cx, &mut buffer) {
+            Poll::Ready(Ok(0)) => ended = true,
+            Poll::Ready(Err(e)) => error = Some(e),
+            _ => {}
+        }
+    }
+
+    Poll::Ready(Ok(FrameRead::Data {
+        data: buffer.freeze(),
+        ended,
+        error,
+    }))
+}
+
+/// Implement Stream for ResponseBody to enable async iteration in Rust
+impl Stream for ResponseBody {
+    type Item = Result<Bytes, String>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Use poll_frame and extract data
+        match self.poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Ok(data) = frame.into_data() {
+                    Poll::Ready(Some(Ok(data)))
+                } else {
+                    // Frame was not data (e.g., trailers) - skip it
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_response_on_finish() {
+        let finished = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let log = Arc::clone(&finished);
+        let body = ResponseBody::buffered(Bytes::from("Hello, World!"))
+            .on_finish(move |bytes, completed| log.lock().unwrap().push((bytes, completed)));
+        let data = body.collect().await.unwrap().to_bytes();
+        assert_eq!(data, "Hello, World!");
+        assert_eq!(*finished.lock().unwrap(), [(13, true)]);
+
+        // Dropped part way through
+        finished.lock().unwrap().clear();
+        let log = Arc::clone(&finished);
+        let mut body = ResponseBody::new()
+            .on_finish(move |bytes, completed| log.lock().unwrap().push((bytes, completed)));
+        let mut writer = body.clone();
+        writer.write_all(b"partial").await.unwrap();
+        let mut chunk = [0u8; 7];
+        body.read_exact(&mut chunk).await.unwrap();
+        drop(writer);
+        assert!(finished.lock().unwrap().is_empty());
+        drop(body);
+        assert_eq!(*finished.lock().unwrap(), [(7, false)]);
+    }
+
+    #[test]
+    fn test_read_frame_from_misbehaving_reader() {
+        /// Claims every read filled 100 bytes without writing any of them
+        struct Liar;
+
+        impl AsyncRead for Liar {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &mut tokio::io::ReadBuf<'_>,
+            ) -> Poll<io::Result<()>> {
+                let n = buf.remaining().min(100);
+                buf.initialize_unfilled_to(n);
+                buf.advance(n);
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let Poll::Ready(Ok(FrameRead::Data { data, ended, error })) =
+            poll_read_frame(Pin::new(&mut Liar), &mut cx, 150)
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(data.len(), 150);
+        assert!(data.iter().all(|&b| b == 0));
+        assert!(!ended);
+        assert!(error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_response_frames_respect_buffer_size() {
+        let body = ResponseBody::new_with_buffer_size(64);
+        let mut writer = body.clone();
+        writer.write_all(&[7; 64]).await.unwrap();
+        let mut body = std::pin::pin!(body);
+
+        let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(frame.len(), 64);
+        assert!(frame.iter().all(|&b| b == 7));
+    }
+
+    #[tokio::test]
+    async fn test_request_rx() {
+        let body = RequestBody::from_data(Bytes::from("Hello, World!"))
+            .await
+            .unwrap();
+        let mut rx = body.take_request_rx().unwrap();
+        assert!(matches!(
+            body.clone().take_request_rx(),
+            Err(StreamError::StreamAlreadyConsumed)
+        ));
+
+        let mut data = Vec::new();
+        while let StreamChunk::Data(chunk) = rx.recv().await.unwrap().unwrap() {
+            data.extend_from_slice(&chunk);
+        }
+        assert_eq!(data, b"Hello, World!");
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_response_channel() {
+        let request = RequestBody::new();
+        let (body, tx) = request.create_response_channel();
+        tx.send(Ok(StreamChunk::Data(Bytes::from("Hello, "))))
+            .await
+            .unwrap();
+        tx.send(Ok(StreamChunk::Data(Bytes::from("World!"))))
+            .await
+            .unwrap();
+        tx.send(Ok(StreamChunk::End)).await.unwrap();
+        assert_eq!(body.collect().await.unwrap().to_bytes(), "Hello, World!");
+
+        // Dropping the sender early is an error, not a complete body
+        let (mut body, tx) = request.create_response_channel();
+        tx.send(Ok(StreamChunk::Data(Bytes::from("partial"))))
+            .await
+            .unwrap();
+        drop(tx);
+        let mut data = Vec::new();
+        let err = body.read_to_end(&mut data).await.unwrap_err();
+        assert_eq!(data, b"partial");
+        assert!(err.to_string().contains("closed before the end"));
+    }
+
+    #[tokio::test]
+    async fn test_response_tee() {
+        let (cached_tx, cached_rx) = tokio::sync::oneshot::channel();
+        let source = ResponseBody::from_stream(futures_util::stream::iter([
+            Ok::<_, String>(Bytes::from("Hello, ")),
+            Ok(Bytes::from("World!")),
+        ]));
+        let body = source.tee(move |copy| {
+            let _ = cached_tx.send(copy);
+        });
+
+        let delivered = body.collect().await.unwrap().to_bytes();
+        assert_eq!(delivered, "Hello, World!");
+        assert_eq!(cached_rx.await.unwrap(), "Hello, World!");
+
+        // A body failing part way is delivered up to the error but not cached
+        let (cached_tx, cached_rx) = tokio::sync::oneshot::channel::<Bytes>();
+        let source = ResponseBody::from_stream(futures_util::stream::iter([
+            Ok(Bytes::from("partial")),
+            Err("upstream reset".to_string()),
+        ]));
+        let mut body = source.tee(move |copy| {
+            let _ = cached_tx.send(copy);
+        });
+
+        let mut delivered = Vec::new();
+        let err = body.read_to_end(&mut delivered).await.unwrap_err();
+        assert_eq!(delivered, b"partial");
+        assert_eq!(err.to_string(), "upstream reset");
+        assert!(cached_rx.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_response_tee_dropped_early() {
+        let (finished_tx, finished_rx) = tokio::sync::oneshot::channel();
+        let source = ResponseBody::new().on_finish(move |_, completed| {
+            let _ = finished_tx.send(completed);
+        });
+        let mut writer = source.writer();
+        tokio::spawn(async move {
+            // Far more than the buffers hold, so the copy must wait for room
+            for _ in 0..1024 {
+                if writer.write_all(&[b'x'; 1024]).await.is_err() {
+                    return;
+                }
+            }
+            let _ = writer.finish().await;
+        });
+
+        let (cached_tx, cached_rx) = tokio::sync::oneshot::channel::<Bytes>();
+        let mut body = source.tee(move |copy| {
+            let _ = cached_tx.send(copy);
+        });
+        let frame = body.frame().await.unwrap().unwrap();
+        assert!(!frame.into_data().unwrap().is_empty());
+        drop(body);
+
+        // The copying task stops and drops the source without caching
+        let completed = tokio::time::timeout(Duration::from_secs(5), finished_rx)
+            .await
+            .expect("tee task should end")
+            .unwrap();
+        assert!(!completed);
+        assert!(cached_rx.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_response_tee_passes_trailers() {
+        let source = ResponseBody::buffered(Bytes::from("cached"));
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-checksum", "abc".parse().unwrap());
+        source.set_trailers(trailers.clone());
+
+        let collected = source.tee(|_| {}).collect().await.unwrap();
+        assert_eq!(collected.trailers(), Some(&trailers));
+        assert_eq!(collected.to_bytes(), "cached");
+    }
+
+    #[test]
+    fn test_response_collect_blocking() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let body = ResponseBody::new();
+        let mut writer = body.clone();
+        runtime.spawn(async move {
+            writer.write_all(b"Hello, ").await.unwrap();
+            writer.write_all(b"World!").await.unwrap();
+            writer.shutdown().await.unwrap();
+        });
+
+        let data = body.collect_blocking(runtime.handle()).unwrap();
+        assert_eq!(data, "Hello, World!");
+
+        let buffered = ResponseBody::buffered(Bytes::from("sync"));
+        assert_eq!(buffered.collect_blocking(runtime.handle()).unwrap(), "sync");
+    }
+
+    #[test]
+    fn test_stream_error_keeps_io_kind() {
+        let err = StreamError::from(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection ended early",
+        ));
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::UnexpectedEof));
+        assert_eq!(err.to_string(), "I/O error: connection ended early");
+
+        let io_err = io::Error::from(err);
+        assert_eq!(io_err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            StreamError::from(io_err).io_kind(),
+            Some(io::ErrorKind::UnexpectedEof)
+        );
+
+        // Other errors survive the round trip as themselves
+        let io_err = io::Error::from(StreamError::BodyTooLarge(10));
+        assert!(matches!(
+            StreamError::from(io_err),
+            StreamError::BodyTooLarge(10)
+        ));
+        assert_eq!(StreamError::ConcurrentRead.io_kind(), None);
+    }
+
+    #[tokio::test]
+    async fn test_request_sniff_content_type() {
+        let png = Bytes::from_static(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01");
+        let mut body = RequestBody::from_data(png.clone()).await.unwrap();
+        assert_eq!(body.sniff_content_type().await.unwrap(), Some("image/png"));
+        let mut data = Vec::new();
+        body.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, png);
+
+        // Large enough that peeking stops short of the end
+        let json = Bytes::from(format!("{{\"items\": [{}]}}", "1, ".repeat(500) + "1"));
+        let mut body = RequestBody::from_data_streaming(json.clone());
+        assert_eq!(body.peek(4).await.unwrap(), "{\"it");
+        assert_eq!(
+            body.sniff_content_type().await.unwrap(),
+            Some("application/json")
+        );
+        let mut data = Vec::new();
+        body.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, json);
+    }
+
+    #[tokio::test]
+    async fn test_request_body_limit() {
+        let mut body = RequestBody::from_data(Bytes::from("0123456789"))
+            .await
+            .unwrap()
+            .with_limit(10);
+        let mut data = Vec::new();
+        body.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"0123456789");
+
+        let mut body = RequestBody::from_data(Bytes::from("0123456789a"))
+            .await
+            .unwrap()
+            .with_limit(10);
+        let mut data = Vec::new();
+        let err = body.read_to_end(&mut data).await.unwrap_err();
+        assert!(matches!(
+            StreamError::from(err),
+            StreamError::BodyTooLarge(10)
+        ));
+        assert!(data.len() <= 10);
+    }
+
+    #[tokio::test]
+    async fn test_request_from_large_data() {
+        let data = Bytes::from(vec![7u8; 64 * 1024]);
+        let body =
+            tokio::time::timeout(Duration::from_secs(5), RequestBody::from_data(data.clone()))
+                .await
+                .expect("from_data should not wait for a reader")
+                .unwrap();
+        assert_eq!(body.collect().await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_request_collect() {
+        let body = RequestBody::from_data(Bytes::new()).await.unwrap();
+        assert_eq!(body.collect().await.unwrap(), "");
+
+        // Written in chunks by another clone
+        let body = RequestBody::new();
+        let mut writer = body.clone();
+        tokio::spawn(async move {
+            for chunk in ["one ", "two ", "three"] {
+                writer.write_all(chunk.as_bytes()).await.unwrap();
+            }
+            writer.shutdown().await.unwrap();
+        });
+        assert_eq!(body.collect().await.unwrap(), "one two three");
+
+        let body = RequestBody::from_data(Bytes::from("0123456789"))
+            .await
+            .unwrap();
+        assert_eq!(body.collect_with_limit(10).await.unwrap(), "0123456789");
+
+        let body = RequestBody::from_data(Bytes::from("0123456789a"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            body.collect_with_limit(10).await,
+            Err(StreamError::BodyTooLarge(10))
+        ));
+
+        // Collecting from a handle that is already reading carries on from
+        // where it stopped
+        let mut body = RequestBody::from_data(Bytes::from("hello world"))
+            .await
+            .unwrap();
+        let mut buf = [0u8; 6];
+        body.read_exact(&mut buf).await.unwrap();
+        let request = http::Request::new(body);
+        assert_eq!(request.body().collect().await.unwrap(), "world");
+    }
+
+    #[tokio::test]
+    async fn test_request_body_concurrent_read() {
+        let mut body = RequestBody::new();
+        let mut clone = body.clone();
+        let mut writer = body.clone();
+
+        writer.write_all(b"hello ").await.unwrap();
+        let mut buf = [0u8; 6];
+        body.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello ");
+
+        // A second clone can't read while the first is reading
+        writer.write_all(b"world").await.unwrap();
+        writer.shutdown().await.unwrap();
+        let err = clone.read(&mut buf).await.unwrap_err();
+        assert!(matches!(
+            StreamError::from(err),
+            StreamError::ConcurrentRead
+        ));
+
+        // Once the reader is dropped, another clone can carry on
+        drop(body);
+        let mut rest = Vec::new();
+        clone.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"world");
+    }
+
+    #[tokio::test]
+    async fn test_request_body_from_data_sync() {
+        let mut body = RequestBody::from_data_sync(Bytes::from("hello")).unwrap();
+        let mut data = Vec::new();
+        body.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"hello");
+
+        let large = Bytes::from(vec![0u8; DEFAULT_BUFFER_SIZE + 1]);
+        assert!(matches!(
+            RequestBody::from_data_sync(large),
+            Err(StreamError::BodyTooLarge(DEFAULT_BUFFER_SIZE))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_request_body_from_data_streaming() {
+        let mut body = RequestBody::from_data_streaming(Bytes::from("small"));
+        let mut data = Vec::new();
+        body.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"small");
+
+        // Four times the buffer size, so a writer task has to feed it
+        let large: Bytes = (0..DEFAULT_BUFFER_SIZE * 4).map(|i| i as u8).collect();
+        let mut body = RequestBody::from_data_streaming(large.clone());
+        let mut data = Vec::new();
+        body.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, large);
+    }
+
+    /// Minimal `Stream` over an mpsc receiver
+    struct ReceiverStream<T>(tokio::sync::mpsc::Receiver<T>);
+
+    impl<T> Stream for ReceiverStream<T> {
+        type Item = T;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+            self.0.poll_recv(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_response_body_from_stream() {
+        let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, String>>(4);
+        let body = ResponseBody::from_stream(ReceiverStream(rx));
+
+        tokio::spawn(async move {
+            for chunk in ["one", "two", "three"] {
+                tx.send(Ok(Bytes::from(chunk))).await.unwrap();
+            }
+        });
+
+        let collected = body.collect().await.unwrap();
+        assert_eq!(collected.to_bytes(), "onetwothree");
+    }
+
+    #[tokio::test]
+    async fn test_response_body_from_stream_error() {
+        let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, String>>(4);
+        let mut body = ResponseBody::from_stream(ReceiverStream(rx));
+
+        tokio::spawn(async move {
+            tx.send(Ok(Bytes::from("partial"))).await.unwrap();
+            tx.send(Err("upstream failed".to_string())).await.unwrap();
+        });
+
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "partial");
+
+        let err = body.frame().await.unwrap().unwrap_err();
+        assert_eq!(err, "upstream failed");
+        assert!(body.frame().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_response_body_spawn_writer_panic() {
+        let mut body = ResponseBody::new();
+        body.spawn_writer(|mut writer| async move {
+            writer.write_all(b"partial").await.unwrap();
+            panic!("writer exploded");
+        });
+
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "partial");
+
+        let err = body.frame().await.unwrap().unwrap_err();
+        assert_eq!(err, "body writer panicked: writer exploded");
+        assert!(body.frame().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_debug_does_not_block_on_locked_body() {
+        let body = RequestBody::new_with_buffer_size(4096);
+        let _clone = body.clone();
+        let _guard = body.read_side.lock().await;
+
+        let debug = format!("{:?}", body);
+        assert!(debug.contains("buffer_size: 4096"), "{debug}");
+        assert!(debug.contains("read_locked: true"), "{debug}");
+        assert!(debug.contains("write_locked: false"), "{debug}");
+        assert!(debug.contains("handles: 2"), "{debug}");
+
+        let response = ResponseBody::new();
+        let _guard = response.write_side.lock().await;
+        let debug = format!("{:?}", response);
+        assert!(debug.contains("buffer_size: 16384"), "{debug}");
+        assert!(debug.contains("write_locked: true"), "{debug}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_response_time_to_first_byte() {
+        let mut body = ResponseBody::new();
+        assert!(body.time_to_first_byte().is_none());
+
+        let mut writer = body.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            writer.write_all(b"hello").await.unwrap();
+            writer.shutdown().await.unwrap();
+        });
+
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "hello");
+
+        let ttfb = body.time_to_first_byte().unwrap();
+        assert!(ttfb >= Duration::from_millis(250));
+
+        // Later frames don't move the measurement
+        assert!(body.frame().await.is_none());
+        assert_eq!(body.time_to_first_byte(), Some(ttfb));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_response_deadline() {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let mut body = ResponseBody::new().with_deadline(deadline);
+        assert_eq!(body.deadline(), Some(deadline));
+
+        // Drip a chunk every 400ms, running past the deadline
+        let mut writer = body.clone();
+        tokio::spawn(async move {
+            for _ in 0..5 {
+                tokio::time::sleep(Duration::from_millis(400)).await;
+                writer.write_all(b"drip").await.unwrap();
+            }
+            writer.shutdown().await.unwrap();
+        });
+
+        let mut collected = Vec::new();
+        let error = loop {
+            match body.frame().await {
+                Some(Ok(frame)) => collected.extend_from_slice(&frame.into_data().unwrap()),
+                Some(Err(error)) => break error,
+                None => panic!("body ended before the deadline"),
+            }
+        };
+
+        assert_eq!(error, "response body deadline exceeded");
+        assert_eq!(collected, b"dripdrip");
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[tokio::test]
+    async fn test_response_coalesced() {
+        let mut body = ResponseBody::new().coalesced(64);
+
+        let mut writer = body.clone();
+        tokio::spawn(async move {
+            for _ in 0..10 {
+                writer.write_all(&[b'x'; 10]).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+            writer.shutdown().await.unwrap();
+        });
+
+        let mut sizes = Vec::new();
+        while let Some(frame) = body.frame().await {
+            sizes.push(frame.unwrap().into_data().unwrap().len());
+        }
+
+        assert_eq!(sizes.iter().sum::<usize>(), 100);
+        assert!(sizes.len() < 10, "frame sizes {:?}", sizes);
+        // Only the frame at the end of the body may be short
+        assert!(sizes[..sizes.len() - 1].iter().all(|&size| size >= 64));
+    }
+
+    #[tokio::test]
+    async fn test_response_coalesced_flush() {
+        let mut body = ResponseBody::new().coalesced(64);
+        let mut writer = body.clone();
+
+        writer.write_all(b"tiny").await.unwrap();
+        writer.flush().await.unwrap();
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "tiny");
+
+        writer.write_all(b"more").await.unwrap();
+        writer.shutdown().await.unwrap();
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "more");
+        assert!(body.frame().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_response_flush() {
+        for coalesce in [None, Some(1024)] {
+            let body = ResponseBody::new();
+            let mut body = match coalesce {
+                Some(min_frame) => body.coalesced(min_frame),
+                None => body,
+            };
+            let mut writer = body.clone();
+
+            writer.write_all(b"data: 1\n\n").await.unwrap();
+            ResponseBody::flush(&writer).await.unwrap();
+
+            // Seen before the writer closes
+            let frame = tokio::time::timeout(Duration::from_secs(1), body.frame())
+                .await
+                .expect("flushed data should be readable")
+                .unwrap()
+                .unwrap();
+            assert_eq!(frame.into_data().unwrap(), "data: 1\n\n");
+            assert!(!body.is_end_stream());
+
+            writer.shutdown().await.unwrap();
+            assert!(body.frame().await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_response_is_end_stream() {
+        let mut body = ResponseBody::from_data_sync(Bytes::from("hello")).unwrap();
+        assert!(!body.is_end_stream());
+
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "hello");
+        assert!(body.is_end_stream());
+        assert!(body.frame().await.is_none());
+
+        // A streaming body ends once the writer shuts down and it is drained
+        let mut body = ResponseBody::new();
+        let mut writer = body.clone();
+        writer.write_all(b"chunk").await.unwrap();
+        body.frame().await.unwrap().unwrap();
+        assert!(!body.is_end_stream());
+
+        writer.shutdown().await.unwrap();
+        assert!(body.frame().await.is_none());
+        assert!(body.is_end_stream());
+    }
+
+    #[tokio::test]
+    async fn test_response_size_hint() {
+        let mut body = ResponseBody::from_data_sync(Bytes::from("hello")).unwrap();
+        assert_eq!(Body::size_hint(&body).exact(), Some(5));
+        body.frame().await.unwrap().unwrap();
+        assert_eq!(Body::size_hint(&body).exact(), Some(0));
+
+        // A streaming body's length isn't known up front, unless given
+        let body = ResponseBody::new();
+        assert_eq!(Body::size_hint(&body).exact(), None);
+
+        let mut body = ResponseBody::new().with_known_length(10);
+        assert_eq!(Body::size_hint(&body).exact(), Some(10));
+        let mut writer = body.clone();
+        writer.write_all(b"0123456789").await.unwrap();
+        writer.shutdown().await.unwrap();
+        body.frame().await.unwrap().unwrap();
+        assert!(body.frame().await.is_none());
+        assert_eq!(Body::size_hint(&body).exact(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_contended_body_waits_for_lock() {
+        struct CountingWaker(AtomicUsize);
+
+        impl std::task::Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&wakes));
+        let mut cx = Context::from_waker(&waker);
+
+        // A writer polled while another clone holds the write side sleeps
+        // until the lock is released instead of waking itself
+        let body = ResponseBody::new();
+        let guard = body.write_side.lock().await;
+        let mut writer = body.clone();
+        for _ in 0..10 {
+            assert!(
+                Pin::new(&mut writer)
+                    .poll_write(&mut cx, b"data")
+                    .is_pending()
+            );
+        }
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+        drop(guard);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        assert!(matches!(
+            Pin::new(&mut writer).poll_write(&mut cx, b"data"),
+            Poll::Ready(Ok(4))
+        ));
+
+        // Same for a reader waiting on the read side
+        let mut body = RequestBody::from_data(Bytes::from("hello")).await.unwrap();
+        let guard = Arc::clone(&body.read_side).lock_owned().await;
+        let mut buf = [0u8; 8];
+        let mut read_buf = tokio::io::ReadBuf::new(&mut buf);
+        for _ in 0..10 {
+            assert!(
+                Pin::new(&mut body)
+                    .poll_read(&mut cx, &mut read_buf)
+                    .is_pending()
+            );
+        }
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        drop(guard);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 2);
+        assert!(
+            Pin::new(&mut body)
+                .poll_read(&mut cx, &mut read_buf)
+                .is_ready()
+        );
+        assert_eq!(read_buf.filled(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_response_trailers() {
+        let mut body = ResponseBody::new();
+        let mut writer = body.clone();
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        writer.set_trailers(trailers.clone());
+        writer.write_all(b"hello").await.unwrap();
+        writer.shutdown().await.unwrap();
+
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "hello");
+        assert!(!body.is_end_stream());
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_trailers().unwrap(), trailers);
+        assert!(body.is_end_stream());
+        assert!(body.frame().await.is_none());
+
+        // Streaming the body skips the trailers
+        let mut body = ResponseBody::new();
+        let mut writer = body.clone();
+        writer.set_trailers(trailers);
+        writer.write_all(b"hello").await.unwrap();
+        writer.shutdown().await.unwrap();
+        let mut chunks = Vec::new();
+        while let Some(chunk) = futures_util::StreamExt::next(&mut body).await {
+            chunks.push(chunk.unwrap());
+        }
+        assert_eq!(chunks, [Bytes::from("hello")]);
+    }
+}
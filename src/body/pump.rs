@@ -0,0 +1,121 @@
+//! Copying a body into another body from a background task.
+
+use std::{fmt, future::Future};
+
+use bytes::{Bytes, BytesMut};
+use http::HeaderMap;
+use http_body::Body;
+use http_body_util::BodyExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::{RequestBodyWriter, ResponseBodyWriter};
+
+/// Body writer that `pump` can copy frames into
+pub(crate) trait PumpTarget: AsyncWrite + Unpin {
+    /// Record an error for the reader to receive once the data runs out
+    fn set_error(&self, message: String);
+
+    /// Record trailers to send once the data ends
+    fn set_trailers(&self, trailers: HeaderMap);
+}
+
+impl PumpTarget for ResponseBodyWriter {
+    fn set_error(&self, message: String) {
+        *self.error.lock().unwrap() = Some(message);
+    }
+
+    fn set_trailers(&self, trailers: HeaderMap) {
+        ResponseBodyWriter::set_trailers(self, trailers);
+    }
+}
+
+impl PumpTarget for RequestBodyWriter {
+    fn set_error(&self, message: String) {
+        *self.error.lock().unwrap() = Some(message);
+    }
+
+    /// Request bodies have no trailers, so these are dropped
+    fn set_trailers(&self, _trailers: HeaderMap) {}
+}
+
+/// Sees each data frame `pump` copies, before it is written
+pub(crate) trait Tap: Send {
+    /// Inspect a data frame; an error ends the target with that message
+    fn data(&mut self, data: &Bytes) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// Passes every frame through untouched
+impl Tap for () {
+    fn data(&mut self, _data: &Bytes) -> impl Future<Output = Result<(), String>> + Send {
+        std::future::ready(Ok(()))
+    }
+}
+
+/// Collects a copy of every frame
+impl Tap for BytesMut {
+    fn data(&mut self, data: &Bytes) -> impl Future<Output = Result<(), String>> + Send {
+        self.extend_from_slice(data);
+        std::future::ready(Ok(()))
+    }
+}
+
+/// How a `pump` ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PumpEnd {
+    /// All of the source was copied and the target ended
+    Complete,
+    /// The source or the tap failed, and the target ended with
+    /// that error
+    Failed,
+    /// The target's reader went away, so copying stopped early
+    Abandoned,
+}
+
+/// Copy every frame of `source` into `target`, then end it
+///
+/// Data is written and flushed frame by frame, so a coalescing reader sees
+/// the same boundaries the source produced. Each data frame is passed to
+/// `tap` before it is written; an error from it, or from the source,
+/// ends the target with that error. Trailers are handed over to the target.
+///
+/// `target` must be a write-only handle, so a reader dropping its end
+/// makes writes fail and the copy stop instead of waiting for room forever.
+pub(crate) async fn pump<B, W>(mut source: B, mut target: W, tap: &mut impl Tap) -> PumpEnd
+where
+    B: Body<Data = Bytes> + Unpin,
+    B::Error: fmt::Display,
+    W: PumpTarget,
+{
+    while let Some(frame) = source.frame().await {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                target.set_error(e.to_string());
+                let _ = target.shutdown().await;
+                return PumpEnd::Failed;
+            }
+        };
+        match frame.into_data() {
+            Ok(data) => {
+                if let Err(message) = tap.data(&data).await {
+                    target.set_error(message);
+                    let _ = target.shutdown().await;
+                    return PumpEnd::Failed;
+                }
+                if target.write_all(&data).await.is_err() || target.flush().await.is_err() {
+                    return PumpEnd::Abandoned;
+                }
+            }
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    target.set_trailers(trailers);
+                }
+            }
+        }
+    }
+
+    if target.shutdown().await.is_err() {
+        return PumpEnd::Abandoned;
+    }
+    PumpEnd::Complete
+}
@@ -0,0 +1,67 @@
+//! Content type sniffing from leading body bytes.
+
+/// Number of leading bytes inspected when sniffing
+pub(crate) const SNIFF_LEN: usize = 512;
+
+/// Binary formats recognised by their magic bytes
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x1f\x8b", "application/gzip"),
+];
+
+/// Opening tags that mark a document as HTML, compared case-insensitively
+const HTML_TAGS: &[&[u8]] = &[b"<!doctype html", b"<html", b"<head", b"<body"];
+
+/// Guess the MIME type of a body from its first bytes
+///
+/// Recognises PNG, JPEG, GIF, PDF and gzip by their magic bytes, then JSON
+/// objects and arrays and HTML documents by their first non-whitespace
+/// characters. Returns `None` for anything else.
+pub(crate) fn sniff(data: &[u8]) -> Option<&'static str> {
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return Some(mime);
+    }
+
+    let text = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    let start = text.iter().position(|b| !b.is_ascii_whitespace())?;
+    let text = &text[start..];
+
+    if matches!(text[0], b'{' | b'[') {
+        return Some("application/json");
+    }
+    let is_html = HTML_TAGS.iter().any(|tag| {
+        text.len() >= tag.len()
+            && text[..tag.len()].eq_ignore_ascii_case(tag)
+            && text
+                .get(tag.len())
+                .is_none_or(|b| *b == b'>' || b.is_ascii_whitespace())
+    });
+    is_html.then_some("text/html")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_formats() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0\0\x10JFIF"), Some("image/jpeg"));
+        assert_eq!(sniff(b"GIF89a\x01\0"), Some("image/gif"));
+        assert_eq!(sniff(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff(b"\x1f\x8b\x08\0"), Some("application/gzip"));
+        assert_eq!(sniff(b"\xef\xbb\xbf  {\"a\": 1}"), Some("application/json"));
+        assert_eq!(sniff(b"\n[1, 2]"), Some("application/json"));
+        assert_eq!(sniff(b"<!DOCTYPE html><p>"), Some("text/html"));
+        assert_eq!(sniff(b"  <HTML lang=en>"), Some("text/html"));
+
+        assert_eq!(sniff(b"<htmlfoo>"), None);
+        assert_eq!(sniff(b"hello"), None);
+        assert_eq!(sniff(b"   "), None);
+        assert_eq!(sniff(b""), None);
+    }
+}
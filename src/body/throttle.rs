@@ -0,0 +1,190 @@
+//! Bandwidth limiting for response bodies.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use bytes::{Buf, Bytes};
+use http_body::{Body, Frame, SizeHint};
+use tokio::time::{Instant, Sleep};
+
+/// Body wrapper that limits how fast data frames are produced
+///
+/// Throttling uses a token bucket refilled at `bytes_per_sec`, holding at most
+/// one second worth of tokens. The bucket starts empty, so after `t` seconds
+/// no more than `bytes_per_sec * t` bytes have been produced. Data frames
+/// larger than the available budget are split. Trailers pass through
+/// unthrottled.
+///
+/// A rate of zero disables throttling.
+///
+/// Created with `ResponseBody::throttled`.
+#[derive(Debug)]
+pub struct Throttled<B> {
+    inner: B,
+    bytes_per_sec: u64,
+    tokens: f64,
+    last_refill: Option<Instant>,
+    pending: Bytes,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<B> Throttled<B> {
+    /// Wrap a body, limiting it to `bytes_per_sec`
+    pub fn new(inner: B, bytes_per_sec: u64) -> Self {
+        Self {
+            inner,
+            bytes_per_sec,
+            tokens: 0.0,
+            last_refill: None,
+            pending: Bytes::new(),
+            sleep: None,
+        }
+    }
+
+    /// Get the configured rate in bytes per second
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Get a reference to the inner body
+    pub fn get_ref(&self) -> &B {
+        &self.inner
+    }
+
+    /// Unwrap into the inner body, dropping any data held back by the throttle
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let capacity = self.bytes_per_sec as f64;
+        if let Some(last) = self.last_refill {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * capacity).min(capacity);
+        }
+        self.last_refill = Some(now);
+    }
+
+    /// Take the next piece of pending data the budget allows, or arrange to
+    /// be woken once enough budget is available.
+    fn poll_budget(&mut self, cx: &mut Context<'_>) -> Poll<Bytes> {
+        loop {
+            if let Some(sleep) = self.sleep.as_mut() {
+                ready!(sleep.as_mut().poll(cx));
+                self.sleep = None;
+            }
+
+            self.refill(Instant::now());
+
+            // Wait for a full second's budget (or the whole chunk, if smaller)
+            // so large chunks aren't split into tiny frames.
+            let wanted = self.pending.len().min(self.bytes_per_sec as usize).max(1);
+            let available = self.tokens.floor() as usize;
+            if available >= wanted {
+                let n = available.min(self.pending.len());
+                self.tokens -= n as f64;
+                return Poll::Ready(self.pending.split_to(n));
+            }
+
+            // Always schedule a timer before returning pending, so an empty
+            // budget can't stall the body.
+            let missing = wanted as f64 - self.tokens;
+            let wait = Duration::from_secs_f64(missing / self.bytes_per_sec as f64)
+                .max(Duration::from_nanos(1));
+            self.sleep = Some(Box::pin(tokio::time::sleep(wait)));
+        }
+    }
+}
+
+impl<B> Body for Throttled<B>
+where
+    B: Body + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        if this.pending.is_empty() {
+            let frame = match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => frame,
+                other => return Poll::Ready(other.map(|r| r.map(|f| f.map_data(to_bytes)))),
+            };
+
+            match frame.into_data() {
+                Ok(mut data) if this.bytes_per_sec > 0 => {
+                    this.pending = data.copy_to_bytes(data.remaining());
+                    if this.pending.is_empty() {
+                        return Poll::Ready(Some(Ok(Frame::data(Bytes::new()))));
+                    }
+                }
+                Ok(data) => return Poll::Ready(Some(Ok(Frame::data(to_bytes(data))))),
+                Err(frame) => return Poll::Ready(Some(Ok(frame.map_data(to_bytes)))),
+            }
+        }
+
+        let data = ready!(this.poll_budget(cx));
+        Poll::Ready(Some(Ok(Frame::data(data))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pending.is_empty() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let mut hint = self.inner.size_hint();
+        let pending = self.pending.len() as u64;
+        hint.set_lower(hint.lower() + pending);
+        if let Some(upper) = hint.upper() {
+            hint.set_upper(upper + pending);
+        }
+        hint
+    }
+}
+
+fn to_bytes<D: Buf>(mut data: D) -> Bytes {
+    data.copy_to_bytes(data.remaining())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResponseBody;
+    use http_body_util::BodyExt;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_rate() {
+        let body = ResponseBody::new();
+        let mut writer = body.clone();
+        tokio::spawn(async move {
+            writer.write_all(&[7u8; 10 * 1024]).await.unwrap();
+            writer.shutdown().await.unwrap();
+        });
+
+        let start = Instant::now();
+        let collected = body.throttled(1024).collect().await.unwrap();
+        let elapsed = start.elapsed();
+
+        assert_eq!(collected.to_bytes().len(), 10 * 1024);
+        assert!(elapsed >= Duration::from_secs(10), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(11), "{elapsed:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_rate_is_unthrottled() {
+        let body = ResponseBody::from_data_sync(Bytes::from("hello")).unwrap();
+
+        let start = Instant::now();
+        let collected = body.throttled(0).collect().await.unwrap();
+        assert_eq!(collected.to_bytes(), "hello");
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,169 @@
+//! Idle timeouts for bodies.
+
+use std::{
+    error::Error,
+    fmt,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use http_body::{Body, Frame, SizeHint};
+use tokio::time::Sleep;
+
+/// Body wrapper that fails if the inner body goes too long without a frame
+///
+/// Each wait for a frame gets `timeout` to complete, starting when the
+/// wrapper is first polled for it. This fails slow-loris style bodies that
+/// trickle data or stall, without capping the total time of a body that
+/// keeps making progress. Once the timeout fires, the error is yielded and
+/// the body ends.
+///
+/// Created with `TimeoutBody::new` or `ResponseBody::idle_timeout`.
+#[derive(Debug)]
+pub struct TimeoutBody<B> {
+    inner: B,
+    timeout: Duration,
+    sleep: Option<Pin<Box<Sleep>>>,
+    timed_out: bool,
+}
+
+impl<B> TimeoutBody<B> {
+    /// Wrap a body, failing it if no frame arrives within `timeout`
+    pub fn new(inner: B, timeout: Duration) -> Self {
+        Self {
+            inner,
+            timeout,
+            sleep: None,
+            timed_out: false,
+        }
+    }
+
+    /// Get the configured idle timeout
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Get a reference to the inner body
+    pub fn get_ref(&self) -> &B {
+        &self.inner
+    }
+
+    /// Unwrap into the inner body
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+/// Error from a `TimeoutBody`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeoutBodyError<E> {
+    /// No frame arrived within the idle timeout
+    Timeout(Duration),
+    /// The inner body failed
+    Body(E),
+}
+
+impl<E: fmt::Display> fmt::Display for TimeoutBodyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeoutBodyError::Timeout(timeout) => {
+                write!(f, "No body data received within {:?}", timeout)
+            }
+            TimeoutBodyError::Body(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for TimeoutBodyError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TimeoutBodyError::Timeout(_) => None,
+            TimeoutBodyError::Body(e) => Some(e),
+        }
+    }
+}
+
+impl<B> Body for TimeoutBody<B>
+where
+    B: Body + Unpin,
+{
+    type Data = B::Data;
+    type Error = TimeoutBodyError<B::Error>;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if this.timed_out {
+            return Poll::Ready(None);
+        }
+
+        if let Poll::Ready(frame) = Pin::new(&mut this.inner).poll_frame(cx) {
+            this.sleep = None;
+            return Poll::Ready(frame.map(|r| r.map_err(TimeoutBodyError::Body)));
+        }
+
+        let timeout = this.timeout;
+        let sleep = this
+            .sleep
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        ready!(sleep.as_mut().poll(cx));
+        this.sleep = None;
+        this.timed_out = true;
+        Poll::Ready(Some(Err(TimeoutBodyError::Timeout(timeout))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.timed_out || self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        if self.timed_out {
+            return SizeHint::with_exact(0);
+        }
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResponseBody;
+    use bytes::Bytes;
+    use http_body_util::BodyExt;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_body() {
+        // A body producing data in time passes through unchanged
+        let body = ResponseBody::new();
+        let mut writer = body.clone();
+        tokio::spawn(async move {
+            for chunk in ["one", "two"] {
+                tokio::time::sleep(Duration::from_secs(4)).await;
+                writer.write_all(chunk.as_bytes()).await.unwrap();
+            }
+            writer.shutdown().await.unwrap();
+        });
+        let collected = body
+            .idle_timeout(Duration::from_secs(5))
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(collected.to_bytes(), Bytes::from("onetwo"));
+
+        // A stalled body fails once the timeout passes, then ends
+        let body = ResponseBody::new();
+        let mut writer = body.clone();
+        writer.write_all(b"one").await.unwrap();
+        let mut body = body.idle_timeout(Duration::from_secs(5));
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "one");
+        let err = body.frame().await.unwrap().unwrap_err();
+        assert_eq!(err, TimeoutBodyError::Timeout(Duration::from_secs(5)));
+        assert!(body.is_end_stream());
+        assert!(body.frame().await.is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! Write-only handles to request and response bodies.
+
+use std::{
+    fmt, io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
+
+use http::HeaderMap;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt, DuplexStream},
+    sync::Mutex,
+};
+
+use super::{FlushSignal, LockFuture, poll_lock};
+
+/// Write half of a `RequestBody`, for producers outside the handler
+///
+/// Created with `RequestBody::writer`. Data written here is what the
+/// handler reads from the body, so a proxy can forward an upload chunk by
+/// chunk while the handler is already reading it. Writes wait while the
+/// handler hasn't read enough of the data to make room in the buffer.
+///
+/// The writer shares the write side with the body and all its clones, so
+/// dropping it does not end the body. Call `finish` once all data is
+/// written so the reader sees the end, or `abort` to end it with an error.
+pub struct RequestBodyWriter {
+    stream: Arc<Mutex<DuplexStream>>,
+    pub(super) error: Arc<std::sync::Mutex<Option<String>>>,
+    lock: Option<LockFuture>,
+}
+
+impl RequestBodyWriter {
+    pub(super) fn new(
+        stream: Arc<Mutex<DuplexStream>>,
+        error: Arc<std::sync::Mutex<Option<String>>>,
+    ) -> Self {
+        Self {
+            stream,
+            error,
+            lock: None,
+        }
+    }
+
+    /// End the body, so the reader sees the end once it has read all data
+    pub async fn finish(mut self) -> io::Result<()> {
+        self.shutdown().await
+    }
+
+    /// End the body with an error, which the reader gets instead of the end
+    /// once it has read all data written so far
+    pub async fn abort(mut self, message: impl Into<String>) -> io::Result<()> {
+        *self.error.lock().unwrap() = Some(message.into());
+        self.shutdown().await
+    }
+}
+
+impl fmt::Debug for RequestBodyWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBodyWriter").finish_non_exhaustive()
+    }
+}
+
+impl AsyncWrite for RequestBodyWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.stream, &mut this.lock, cx));
+        Pin::new(&mut *stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.stream, &mut this.lock, cx));
+        Pin::new(&mut *stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.stream, &mut this.lock, cx));
+        Pin::new(&mut *stream).poll_shutdown(cx)
+    }
+}
+
+/// Write half of a `ResponseBody`, for producers that shouldn't keep the
+/// body's reader alive
+///
+/// Created with `ResponseBody::writer`. Unlike a clone of the body, the
+/// writer holds only the write side, so once every handle that can read the
+/// body is dropped, writes fail with a broken pipe error instead of waiting
+/// for room in the buffer forever. Tasks copying data into a body use this
+/// to notice the client went away and stop.
+///
+/// Dropping the writer does not end the body, as clones of the body may
+/// still write. Call `finish` once all data is written so the reader sees
+/// the end, or `abort` to end it with an error.
+pub struct ResponseBodyWriter {
+    stream: Arc<Mutex<DuplexStream>>,
+    pub(super) error: Arc<std::sync::Mutex<Option<String>>>,
+    trailers: Arc<std::sync::Mutex<Option<HeaderMap>>>,
+    flush_signal: Arc<FlushSignal>,
+    lock: Option<LockFuture>,
+}
+
+impl ResponseBodyWriter {
+    pub(super) fn new(
+        stream: Arc<Mutex<DuplexStream>>,
+        error: Arc<std::sync::Mutex<Option<String>>>,
+        trailers: Arc<std::sync::Mutex<Option<HeaderMap>>>,
+        flush_signal: Arc<FlushSignal>,
+    ) -> Self {
+        Self {
+            stream,
+            error,
+            trailers,
+            flush_signal,
+            lock: None,
+        }
+    }
+
+    /// Set trailers to send once the data ends
+    ///
+    /// See `ResponseBody::set_trailers`.
+    pub fn set_trailers(&self, trailers: HeaderMap) {
+        *self.trailers.lock().unwrap() = Some(trailers);
+    }
+
+    /// End the body, so the reader sees the end once it has read all data
+    pub async fn finish(mut self) -> io::Result<()> {
+        self.shutdown().await
+    }
+
+    /// End the body with an error, which the reader gets instead of the end
+    /// once it has read all data written so far
+    pub async fn abort(mut self, message: impl Into<String>) -> io::Result<()> {
+        *self.error.lock().unwrap() = Some(message.into());
+        self.shutdown().await
+    }
+}
+
+impl fmt::Debug for ResponseBodyWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseBodyWriter").finish_non_exhaustive()
+    }
+}
+
+impl AsyncWrite for ResponseBodyWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.stream, &mut this.lock, cx));
+        Pin::new(&mut *stream).poll_write(cx, buf)
+    }
+
+    /// Flushes like `ResponseBody::flush`, so a coalescing reader emits the
+    /// data written so far
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.stream, &mut this.lock, cx));
+        let result = Pin::new(&mut *stream).poll_flush(cx);
+        if result.is_ready() {
+            this.flush_signal.notify();
+        }
+        result
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut stream = ready!(poll_lock(&this.stream, &mut this.lock, cx));
+        Pin::new(&mut *stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RequestBody, StreamError};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_request_body_writer() {
+        let body = RequestBody::new_with_buffer_size(8);
+        let mut writer = body.writer();
+        let producer = tokio::spawn(async move {
+            for chunk in ["first ", "second ", "third"] {
+                writer.write_all(chunk.as_bytes()).await.unwrap();
+            }
+            writer.finish().await.unwrap();
+        });
+
+        assert_eq!(body.collect().await.unwrap(), "first second third");
+        producer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_request_body_writer_abort() {
+        let mut body = RequestBody::new();
+        let mut writer = body.writer();
+        writer.write_all(b"partial").await.unwrap();
+        writer.abort("client disconnected").await.unwrap();
+
+        let mut data = Vec::new();
+        let err = body.read_to_end(&mut data).await.unwrap_err();
+        assert_eq!(data, b"partial");
+        assert!(matches!(
+            StreamError::from(err),
+            StreamError::IoError { message, .. } if message == "client disconnected"
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+//! HTTP client adapter, for sending requests upstream with hyper.
+
+use std::{fmt, io};
+
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody, combinators::UnsyncBoxBody};
+use hyper_util::{
+    client::legacy::{Client, connect::Connect, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+use tokio_util::io::ReaderStream;
+
+use crate::{Handler, Request, Response, ResponseBody, body::pump::pump};
+
+/// Request body type sent by `HyperClient`
+pub type ClientBody = UnsyncBoxBody<Bytes, io::Error>;
+
+/// Handler sending each request to the absolute URI it carries with a hyper
+/// client
+///
+/// This is the network side of `handler::Proxy`, which rewrites requests
+/// for the upstream before handing them over. Bodies are streamed both
+/// ways: the request body is sent as it is read, and the response body is
+/// fed by a spawned task as the upstream sends it, trailers included. The
+/// task stops once the response body is dropped. Connections are pooled
+/// by the hyper client.
+///
+/// `new` speaks plain HTTP/1.1. For TLS or other transports, build a client
+/// with another connector and pass it to `from_client`.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::HyperClient;
+/// use http_handler::handler::Proxy;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let proxy = Proxy::new("http://backend:8080/api".parse().unwrap(), HyperClient::new());
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct HyperClient<C = HttpConnector> {
+    client: Client<C, ClientBody>,
+}
+
+impl HyperClient {
+    /// Create a client for plain HTTP/1.1 upstreams
+    ///
+    /// The client spawns its connection tasks, so this must be called from
+    /// within a Tokio runtime.
+    pub fn new() -> Self {
+        Self::from_client(Client::builder(TokioExecutor::new()).build_http())
+    }
+}
+
+impl Default for HyperClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> HyperClient<C> {
+    /// Wrap an already configured hyper client
+    pub fn from_client(client: Client<C, ClientBody>) -> Self {
+        Self { client }
+    }
+
+    /// Get a reference to the hyper client
+    pub fn client(&self) -> &Client<C, ClientBody> {
+        &self.client
+    }
+}
+
+impl<C> fmt::Debug for HyperClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HyperClient").finish_non_exhaustive()
+    }
+}
+
+impl<C> Handler for HyperClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    type Error = hyper_util::client::legacy::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let request = request.map(|body| {
+            StreamBody::new(ReaderStream::new(body).map_ok(Frame::data)).boxed_unsync()
+        });
+        let response = self.client.request(request).await?;
+
+        Ok(response.map(|incoming| {
+            let body = ResponseBody::new();
+            let writer = body.writer();
+            tokio::spawn(async move {
+                pump(incoming, writer, &mut ()).await;
+            });
+            body
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::Proxy;
+    use crate::{RequestBody, RequestExt, ResponseExt, SocketInfo};
+    use http::StatusCode;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Read one request from a connection, returning its head and body
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> (String, Vec<u8>) {
+        let mut data = Vec::new();
+        let mut chunk = [0u8; 1024];
+        let head_end = loop {
+            if let Some(at) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                break at + 4;
+            }
+            let n = stream.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "connection closed before the request head ended");
+            data.extend_from_slice(&chunk[..n]);
+        };
+
+        let head = String::from_utf8(data[..head_end].to_vec()).unwrap();
+        let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .map_or(0, |value| value.parse::<usize>().unwrap());
+        let mut body = data[head_end..].to_vec();
+        while body.len() < length {
+            let n = stream.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "connection closed before the request body ended");
+            body.extend_from_slice(&chunk[..n]);
+        }
+        (head, body)
+    }
+
+    #[tokio::test]
+    async fn test_proxy_to_loopback_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let received = read_request(&mut stream).await;
+            stream
+                .write_all(
+                    b"HTTP/1.1 201 Created\r\n\
+                      content-length: 8\r\n\
+                      x-upstream: yes\r\n\
+                      keep-alive: timeout=5\r\n\
+                      \r\n\
+                      upstream",
+                )
+                .await
+                .unwrap();
+            received
+        });
+
+        let upstream = format!("http://{}/api", addr).parse().unwrap();
+        let proxy = Proxy::new(upstream, HyperClient::new());
+        let mut request = http::Request::builder()
+            .method("POST")
+            .uri("/users?page=2")
+            .header("Host", "example.com")
+            .header("X-Forwarded-For", "203.0.113.7")
+            .header("X-Forwarded-For", "198.51.100.2")
+            .header("Content-Length", "7")
+            .body(
+                RequestBody::from_data(Bytes::from("payload"))
+                    .await
+                    .unwrap(),
+            )
+            .unwrap();
+        request.set_socket_info(SocketInfo::with_remote("192.0.2.1:5000".parse().unwrap()));
+
+        let response = proxy.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["x-upstream"], "yes");
+        assert!(!response.headers().contains_key("keep-alive"));
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "upstream");
+
+        let (head, body) = server.await.unwrap();
+        assert!(
+            head.starts_with("POST /api/users?page=2 HTTP/1.1\r\n"),
+            "{head}"
+        );
+        assert!(head.contains(&format!("host: {}\r\n", addr)), "{head}");
+        assert!(
+            head.contains("x-forwarded-for: 203.0.113.7, 198.51.100.2, 192.0.2.1\r\n"),
+            "{head}"
+        );
+        assert!(
+            head.contains("forwarded: for=192.0.2.1;host=\"example.com\";proto=http\r\n"),
+            "{head}"
+        );
+        assert_eq!(body, b"payload");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_upstream_is_bad_gateway() {
+        // Bind and drop a listener to find a port nobody is listening on
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let upstream = format!("http://{}", addr).parse().unwrap();
+        let proxy = Proxy::new(upstream, HyperClient::new());
+        let request = http::Request::new(RequestBody::new());
+
+        let response = proxy.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        let message = response.exception().unwrap().message().to_string();
+        assert!(message.starts_with("upstream request failed"), "{message}");
+    }
+}
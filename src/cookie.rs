@@ -0,0 +1,315 @@
+//! Cookies, as sent in `Set-Cookie` headers and parsed from `Cookie` headers.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use http::{HeaderMap, header};
+
+/// Value of the `SameSite` cookie attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SameSite {
+    /// Sent only with same-site requests
+    Strict,
+    /// Also sent with top-level navigations from other sites
+    Lax,
+    /// Sent with all requests, which browsers only allow with `Secure`
+    None,
+}
+
+impl SameSite {
+    /// Get the attribute value, e.g. `Lax`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// A cookie to send in a `Set-Cookie` header
+///
+/// Built with `Cookie::new` and the attribute methods, then added to a
+/// response with `ResponseExt::add_set_cookie`. Formatting it with `Display`
+/// gives the header value.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use http_handler::{Cookie, SameSite};
+///
+/// let cookie = Cookie::new("session", "abc123")
+///     .path("/")
+///     .http_only(true)
+///     .secure(true)
+///     .same_site(SameSite::Lax)
+///     .max_age(Duration::from_secs(3600));
+/// assert_eq!(
+///     cookie.to_string(),
+///     "session=abc123; Path=/; Max-Age=3600; Secure; HttpOnly; SameSite=Lax"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    /// Cookie name
+    pub name: String,
+    /// Cookie value
+    pub value: String,
+    /// Path the cookie is sent for
+    pub path: Option<String>,
+    /// Domain the cookie is sent to, including subdomains
+    pub domain: Option<String>,
+    /// How long the cookie lasts, taking precedence over `expires`
+    pub max_age: Option<Duration>,
+    /// When the cookie expires
+    pub expires: Option<SystemTime>,
+    /// Whether the cookie is only sent over HTTPS
+    pub secure: bool,
+    /// Whether the cookie is hidden from scripts
+    pub http_only: bool,
+    /// When the cookie is sent with cross-site requests
+    pub same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// Create a cookie with no attributes
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            path: None,
+            domain: None,
+            max_age: None,
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        }
+    }
+
+    /// Set the `Path` attribute
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Set the `Domain` attribute
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Set the `Max-Age` attribute, in whole seconds
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Set the `Expires` attribute
+    pub fn expires(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// Set whether the `Secure` attribute is sent
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Set whether the `HttpOnly` attribute is sent
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Set the `SameSite` attribute
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", http_date(expires))?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the cookies sent in `Cookie` headers into a map of name to value
+///
+/// Cookies are separated by `;`, and surrounding double quotes are removed
+/// from values. Pieces without a `=` are ignored. If a name appears more than
+/// once, the first value is kept, as browsers send the most specific cookie
+/// first.
+pub fn parse_cookies(headers: &HeaderMap) -> HashMap<String, String> {
+    let mut cookies = HashMap::new();
+    let pairs = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'));
+    for pair in pairs {
+        let Some((name, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+        cookies
+            .entry(name.to_string())
+            .or_insert_with(|| value.to_string());
+    }
+    cookies
+}
+
+/// Format a time as an HTTP date, e.g. `Wed, 21 Oct 2015 07:28:00 GMT`
+///
+/// Times before the Unix epoch are formatted as the epoch.
+fn http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let days = secs / 86_400;
+    let secs_of_day = secs % 86_400;
+
+    // Civil date from days since the epoch, in 400 year eras starting in March
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RequestExt, ResponseExt};
+
+    #[test]
+    fn test_set_cookie_round_trip() {
+        let cookie = Cookie::new("session", "abc123")
+            .path("/app")
+            .domain("example.com")
+            .max_age(Duration::from_secs(3600))
+            .expires(SystemTime::UNIX_EPOCH + Duration::from_secs(1_445_412_480))
+            .secure(true)
+            .http_only(true)
+            .same_site(SameSite::Strict);
+
+        let mut response = http::Response::new(());
+        response.add_set_cookie(cookie).unwrap();
+        response
+            .add_set_cookie(Cookie::new("theme", "dark"))
+            .unwrap();
+
+        let values: Vec<_> = response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            values,
+            [
+                "session=abc123; Path=/app; Domain=example.com; Max-Age=3600; \
+                 Expires=Wed, 21 Oct 2015 07:28:00 GMT; Secure; HttpOnly; SameSite=Strict",
+                "theme=dark",
+            ]
+        );
+
+        // Header values can't hold control characters
+        assert!(
+            response
+                .add_set_cookie(Cookie::new("bad", "a\r\nb"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_http_date() {
+        assert_eq!(
+            http_date(SystemTime::UNIX_EPOCH),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        assert_eq!(
+            http_date(SystemTime::UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn test_parse_cookies() {
+        let request = http::Request::builder()
+            .header(
+                header::COOKIE,
+                "session=abc123; theme=\"dark mode\"; empty=",
+            )
+            .header(header::COOKIE, "session=ignored;flag; lang=en")
+            .body(())
+            .unwrap();
+        let cookies = request.cookies();
+        assert_eq!(cookies.len(), 4);
+        assert_eq!(cookies["session"], "abc123");
+        assert_eq!(cookies["theme"], "dark mode");
+        assert_eq!(cookies["empty"], "");
+        assert_eq!(cookies["lang"], "en");
+        assert!(!cookies.contains_key("flag"));
+
+        let request = http::Request::new(());
+        assert!(request.cookies().is_empty());
+    }
+}
@@ -1,14 +1,23 @@
 //! Extension types for storing additional data in http Request/Response
 
 use bytes::{Bytes, BytesMut};
+use http::{
+    HeaderMap, HeaderName, HeaderValue, Method, Uri, Version,
+    header::{self, InvalidHeaderValue},
+};
 use std::{
+    collections::HashMap,
+    fmt, io,
     net::SocketAddr,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
     sync::Arc,
+    time::SystemTime,
 };
 use tokio::sync::Mutex;
 
+use crate::{RequestBody, cookie::Cookie, websocket::WebSocketRole};
+
 /// Socket information for a request
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct SocketInfo {
@@ -39,6 +48,198 @@ impl SocketInfo {
             remote: Some(remote),
         }
     }
+
+    /// Encode as a compact header value
+    ///
+    /// The format is `local=<addr>;remote=<addr>`, with a missing address
+    /// leaving out its part, e.g. `local=127.0.0.1:8080;remote=[::1]:5000`.
+    pub fn to_header_value(&self) -> String {
+        let parts: Vec<String> = [("local", self.local), ("remote", self.remote)]
+            .into_iter()
+            .filter_map(|(key, addr)| addr.map(|addr| format!("{}={}", key, addr)))
+            .collect();
+        parts.join(";")
+    }
+
+    /// Decode a header value produced by `to_header_value`
+    pub fn from_header_value(value: &str) -> Result<Self, SocketInfoParseError> {
+        let mut info = Self::default();
+
+        for part in value.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, addr) = part
+                .split_once('=')
+                .ok_or_else(|| SocketInfoParseError::InvalidPart(part.to_string()))?;
+            let slot = match key.trim() {
+                "local" => &mut info.local,
+                "remote" => &mut info.remote,
+                other => return Err(SocketInfoParseError::UnknownKey(other.to_string())),
+            };
+            if slot.is_some() {
+                return Err(SocketInfoParseError::DuplicateKey(key.trim().to_string()));
+            }
+            let addr = addr.trim();
+            *slot = Some(
+                addr.parse()
+                    .map_err(|_| SocketInfoParseError::InvalidAddress(addr.to_string()))?,
+            );
+        }
+
+        Ok(info)
+    }
+}
+
+/// Error returned when decoding a `SocketInfo` header value fails
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SocketInfoParseError {
+    /// The header value is not valid visible ASCII
+    InvalidHeaderValue,
+    /// A part is not in `key=value` form
+    InvalidPart(String),
+    /// A key other than `local` or `remote` was found
+    UnknownKey(String),
+    /// The same key appears more than once
+    DuplicateKey(String),
+    /// An address could not be parsed as a socket address
+    InvalidAddress(String),
+}
+
+impl fmt::Display for SocketInfoParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocketInfoParseError::InvalidHeaderValue => write!(f, "Invalid header value"),
+            SocketInfoParseError::InvalidPart(part) => {
+                write!(f, "Invalid socket info part: {}", part)
+            }
+            SocketInfoParseError::UnknownKey(key) => write!(f, "Unknown socket info key: {}", key),
+            SocketInfoParseError::DuplicateKey(key) => {
+                write!(f, "Duplicate socket info key: {}", key)
+            }
+            SocketInfoParseError::InvalidAddress(addr) => {
+                write!(f, "Invalid socket address: {}", addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SocketInfoParseError {}
+
+/// Client certificate presented during a mutual TLS handshake
+///
+/// Set by the TLS terminator so handlers can authorize on the certificate.
+/// The raw DER encoding is kept for handlers that need to validate it
+/// themselves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientCertificate {
+    /// Subject distinguished name, e.g. `CN=client,O=Example`
+    pub subject: String,
+    /// Issuer distinguished name
+    pub issuer: String,
+    /// Serial number, as hex
+    pub serial: String,
+    /// Start of the validity period
+    pub not_before: SystemTime,
+    /// End of the validity period
+    pub not_after: SystemTime,
+    /// DER encoding of the certificate
+    pub der: Bytes,
+}
+
+impl ClientCertificate {
+    /// Check whether `time` falls within the validity period
+    pub fn is_valid_at(&self, time: SystemTime) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+}
+
+/// TLS session details for a request received over TLS
+///
+/// Set by the TLS terminator alongside `SocketInfo`. Fields the terminator
+/// doesn't know are left empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// Negotiated protocol version, e.g. `TLSv1.3`
+    pub protocol: Option<String>,
+    /// Negotiated cipher suite, e.g. `TLS_AES_128_GCM_SHA256`
+    pub cipher: Option<String>,
+    /// Server name the client asked for with SNI
+    pub sni: Option<String>,
+    /// Application protocol negotiated with ALPN, e.g. `h2`
+    pub alpn: Option<String>,
+    /// DER encoded certificate chain presented by the peer, leaf first,
+    /// empty if it presented none
+    pub peer_certificates: Vec<Bytes>,
+}
+
+impl TlsInfo {
+    /// Check whether the peer presented a certificate
+    pub fn has_peer_certificate(&self) -> bool {
+        !self.peer_certificates.is_empty()
+    }
+}
+
+/// Form of the request-target on the request line (RFC 7230 Section 5.3)
+///
+/// The `http` crate stores the target as a `Uri`, which may be rewritten into
+/// absolute form along the way. Recording the original form lets proxies
+/// forward the target as it was received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestTargetForm {
+    /// A path and optional query, e.g. `/search?q=rust`
+    Origin,
+    /// A full URI, e.g. `http://example.com/search?q=rust`, as sent to proxies
+    Absolute,
+    /// Only host and port, e.g. `example.com:443`, used by `CONNECT`
+    Authority,
+    /// `*`, used by server-wide `OPTIONS` requests
+    Asterisk,
+}
+
+impl RequestTargetForm {
+    /// Work out the form of a request-target from its method and URI
+    pub fn detect(method: &Method, uri: &Uri) -> Self {
+        if uri.scheme().is_some() {
+            RequestTargetForm::Absolute
+        } else if *method == Method::CONNECT && uri.authority().is_some() {
+            RequestTargetForm::Authority
+        } else if uri.authority().is_none() && uri.path() == "*" {
+            RequestTargetForm::Asterisk
+        } else {
+            RequestTargetForm::Origin
+        }
+    }
+
+    /// Render a URI as a request-target of this form
+    ///
+    /// Falls back to the URI as-is if it lacks the parts the form needs.
+    pub fn render(&self, uri: &Uri) -> String {
+        match self {
+            RequestTargetForm::Origin => uri
+                .path_and_query()
+                .map(|path| path.as_str())
+                .filter(|path| path.starts_with('/'))
+                .unwrap_or("/")
+                .to_string(),
+            RequestTargetForm::Absolute => uri.to_string(),
+            RequestTargetForm::Authority => match uri.authority() {
+                Some(authority) => authority.to_string(),
+                None => uri.to_string(),
+            },
+            RequestTargetForm::Asterisk => "*".to_string(),
+        }
+    }
+}
+
+/// Method and URI of a request as first received
+///
+/// Middleware that rewrites the method or URI records this first, so access
+/// logs and signature checks can still see what the client sent. Only the
+/// first snapshot is kept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OriginalRequestLine {
+    /// Method as received
+    pub method: Method,
+    /// URI as received
+    pub uri: Uri,
 }
 
 /// Document root for a request
@@ -50,11 +251,63 @@ pub struct DocumentRoot {
 
 impl DocumentRoot {
     /// Create a new DocumentRoot with the given path
+    ///
+    /// The path is stored as given. Use `new_canonical` for roots that files
+    /// will be served from.
     pub fn new(path: impl AsRef<Path>) -> Self {
         Self {
             path: path.as_ref().to_path_buf(),
         }
     }
+
+    /// Create a DocumentRoot from the canonical form of `path`
+    ///
+    /// The path is made absolute with symlinks and `..` resolved. Fails if
+    /// it doesn't exist, or with `ErrorKind::NotADirectory` if it isn't a
+    /// directory.
+    pub fn new_canonical(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = std::fs::canonicalize(path)?;
+        if !path.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotADirectory,
+                format!("document root {} is not a directory", path.display()),
+            ));
+        }
+        Ok(Self { path })
+    }
+
+    /// Check whether `candidate` stays within this root once resolved
+    ///
+    /// Relative candidates are taken relative to the root. Symlinks and `..`
+    /// are resolved as far as the path exists; a part that doesn't exist yet
+    /// may not use `..`, since where it leads can't be known. Use this
+    /// before serving a file built from request input.
+    pub fn contains(&self, candidate: &Path) -> bool {
+        let root = self
+            .path
+            .canonicalize()
+            .unwrap_or_else(|_| self.path.clone());
+        resolve_path(&self.path.join(candidate)).is_some_and(|resolved| resolved.starts_with(&root))
+    }
+}
+
+/// Canonicalize the longest existing prefix of `path` and append the rest
+///
+/// Returns `None` if a part past the existing prefix is `..`.
+fn resolve_path(path: &Path) -> Option<PathBuf> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            return Some(
+                rest.into_iter()
+                    .rev()
+                    .fold(resolved, |acc, part| acc.join(part)),
+            );
+        }
+        rest.push(existing.file_name()?);
+        existing = existing.parent()?;
+    }
 }
 
 impl Deref for DocumentRoot {
@@ -77,16 +330,131 @@ impl From<String> for DocumentRoot {
     }
 }
 
-/// WebSocket mode marker for a request/response
+/// Path parameters captured by the router
+///
+/// Values are percent-decoded. Parameters are kept in the order they appear
+/// in the route pattern.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathParams {
+    params: Vec<(String, String)>,
+}
+
+impl PathParams {
+    /// Create an empty set of path parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a parameter
+    pub fn push(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.params.push((name.into(), value.into()));
+    }
+
+    /// Get the value of a parameter by name
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterate over parameter names and values
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Get the number of parameters
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Check if there are no parameters
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+/// Ad-hoc string metadata attached to a request
+///
+/// Lets middleware pass values such as an authenticated user id to inner
+/// handlers without defining a new extension type. Prefer a dedicated
+/// extension type for structured data.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestMetadata {
+    entries: HashMap<String, String>,
+}
+
+impl RequestMetadata {
+    /// Create empty metadata
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the value for a key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// Set the value for a key, returning the previous value if any
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.entries.insert(key.into(), value.into())
+    }
+
+    /// Remove a key, returning its value if it was set
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.entries.remove(key)
+    }
+
+    /// Iterate over keys and values, in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Get the number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// WebSocket mode of a request/response
 ///
-/// This extension indicates that the request/response should be treated as a WebSocket
-/// connection, where each write() call represents a complete WebSocket message rather
-/// than HTTP chunks.
+/// This extension indicates whether the request/response should be treated as a
+/// WebSocket connection, where each write() call represents a complete WebSocket message
+/// rather than HTTP chunks, and which side of the connection this end plays.
 ///
-/// The presence of this extension in the request/response extensions indicates WebSocket mode is enabled.
-/// To check if WebSocket mode is enabled, use: `request.extensions().get::<WebSocketMode>().is_some()`
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-pub struct WebSocketMode;
+/// A missing extension means `WebSocketMode::None`. Use `RequestExt::websocket_mode` to
+/// read it, and `WebSocketMode::role` to configure a `WebSocketCodec` for the connection.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WebSocketMode {
+    /// Plain HTTP, not a WebSocket connection
+    #[default]
+    None,
+    /// WebSocket connection accepted by this end
+    Server,
+    /// WebSocket connection opened by this end
+    Client,
+}
+
+impl WebSocketMode {
+    /// Check if WebSocket mode is enabled, in either role
+    pub fn is_enabled(&self) -> bool {
+        *self != WebSocketMode::None
+    }
+
+    /// Get the codec role for this mode, or `None` if not a WebSocket
+    pub fn role(&self) -> Option<WebSocketRole> {
+        match self {
+            WebSocketMode::None => None,
+            WebSocketMode::Server => Some(WebSocketRole::Server),
+            WebSocketMode::Client => Some(WebSocketRole::Client),
+        }
+    }
+}
 
 /// WebSocket decoder state for response body decoding
 ///
@@ -118,10 +486,66 @@ impl WebSocketDecoderState {
     }
 }
 
-/// Response log buffer
+impl Default for WebSocketDecoderState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Severity of a `LogEntry`, ordered from least to most severe
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    /// Fine-grained tracing
+    Trace,
+    /// Debugging details
+    Debug,
+    /// Normal operation
+    Info,
+    /// Something unexpected that was handled
+    Warn,
+    /// A failure
+    Error,
+}
+
+impl LogLevel {
+    /// Get the lowercase name of the level, e.g. `warn`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single entry in a `ResponseLog`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Severity of the entry
+    pub level: LogLevel,
+    /// Message, without a trailing newline
+    pub message: String,
+    /// When the entry was logged
+    pub timestamp: SystemTime,
+}
+
+/// Response log
+///
+/// Holds structured entries with a level and timestamp, along with the
+/// messages as newline-terminated lines for consumers that read the log as
+/// bytes.
 #[derive(Clone, Debug, Default)]
 pub struct ResponseLog {
     buffer: BytesMut,
+    entries: Vec<LogEntry>,
 }
 
 impl ResponseLog {
@@ -131,17 +555,82 @@ impl ResponseLog {
     }
 
     /// Create a log with initial content
+    ///
+    /// The content is kept as is, and each line of it becomes an `Info`
+    /// entry.
     pub fn from_bytes(bytes: impl Into<Bytes>) -> Self {
         let bytes = bytes.into();
         let mut buffer = BytesMut::with_capacity(bytes.len());
         buffer.extend_from_slice(&bytes);
-        Self { buffer }
+        let timestamp = SystemTime::now();
+        let entries = String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(|line| LogEntry {
+                level: LogLevel::Info,
+                message: line.to_string(),
+                timestamp,
+            })
+            .collect();
+        Self { buffer, entries }
+    }
+
+    /// Add an entry with the given level, timestamped now
+    pub fn push(&mut self, level: LogLevel, message: impl Into<String>) {
+        let message = message.into();
+        self.buffer.extend_from_slice(message.as_bytes());
+        self.buffer.extend_from_slice(b"\n");
+        self.entries.push(LogEntry {
+            level,
+            message,
+            timestamp: SystemTime::now(),
+        });
     }
 
     /// Append data to the log with a trailing newline
+    ///
+    /// This adds an `Info` entry. Data that isn't UTF-8 is kept as is in the
+    /// bytes, and lossily converted for the entry.
     pub fn append(&mut self, data: impl AsRef<[u8]>) {
-        self.buffer.extend_from_slice(data.as_ref());
+        let data = data.as_ref();
+        self.buffer.extend_from_slice(data);
         self.buffer.extend_from_slice(b"\n");
+        self.entries.push(LogEntry {
+            level: LogLevel::Info,
+            message: String::from_utf8_lossy(data).into_owned(),
+            timestamp: SystemTime::now(),
+        });
+    }
+
+    /// Get the entries, oldest first
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    /// Iterate over the entries at `level` or more severe, oldest first
+    pub fn entries_at_least(&self, level: LogLevel) -> impl Iterator<Item = &LogEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.level >= level)
+    }
+
+    /// Serialize the entries as JSON lines
+    ///
+    /// Each entry becomes an object with `level`, `message` and `timestamp`
+    /// (milliseconds since the Unix epoch) fields, on a line of its own.
+    pub fn to_json_lines(&self) -> String {
+        let mut out = String::new();
+        for entry in &self.entries {
+            let timestamp = entry
+                .timestamp
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis());
+            out.push_str("{\"level\":\"");
+            out.push_str(entry.level.as_str());
+            out.push_str("\",\"message\":");
+            push_json_string(&mut out, &entry.message);
+            out.push_str(&format!(",\"timestamp\":{}}}\n", timestamp));
+        }
+        out
     }
 
     /// Get the log content as bytes
@@ -154,7 +643,7 @@ impl ResponseLog {
         self.buffer.freeze()
     }
 
-    /// Get the length of the log
+    /// Get the length of the log in bytes
     pub fn len(&self) -> usize {
         self.buffer.len()
     }
@@ -167,39 +656,141 @@ impl ResponseLog {
     /// Clear the log
     pub fn clear(&mut self) {
         self.buffer.clear();
+        self.entries.clear();
+    }
+}
+
+/// Append `value` to `out` as a quoted JSON string
+fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
     }
+    out.push('"');
 }
 
 /// Response exception information
+///
+/// An exception may have a cause, forming a chain from the outermost error
+/// down to the one that started it.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct ResponseException(pub String);
+pub struct ResponseException {
+    message: String,
+    kind: Option<String>,
+    cause: Option<Box<ResponseException>>,
+}
 
 impl ResponseException {
     /// Create a new exception
     pub fn new(message: impl Into<String>) -> Self {
-        Self(message.into())
+        Self {
+            message: message.into(),
+            kind: None,
+            cause: None,
+        }
+    }
+
+    /// Create an exception chain from an error and its `source()` chain
+    pub fn from_error<E: std::error::Error + ?Sized>(err: &E) -> Self {
+        Self {
+            message: err.to_string(),
+            kind: None,
+            cause: err
+                .source()
+                .map(|source| Box::new(Self::from_error(source))),
+        }
+    }
+
+    /// Set the kind of exception, e.g. `TimeoutError`
+    pub fn with_kind(mut self, kind: impl Into<String>) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+
+    /// Set the exception that caused this one
+    pub fn with_cause(mut self, cause: ResponseException) -> Self {
+        self.cause = Some(Box::new(cause));
+        self
     }
 
     /// Get the exception message
     pub fn message(&self) -> &str {
-        &self.0
+        &self.message
+    }
+
+    /// Get the kind of exception, if set
+    pub fn kind(&self) -> Option<&str> {
+        self.kind.as_deref()
+    }
+
+    /// Get the exception that caused this one, if any
+    pub fn cause(&self) -> Option<&ResponseException> {
+        self.cause.as_deref()
+    }
+
+    /// Iterate over this exception and its causes, outermost first
+    pub fn chain(&self) -> impl Iterator<Item = &ResponseException> {
+        std::iter::successors(Some(self), |exception| exception.cause())
+    }
+
+    /// Get the innermost cause, or this exception if it has none
+    pub fn root_cause(&self) -> &ResponseException {
+        self.chain().last().expect("chain includes self")
+    }
+
+    /// Add `cause` at the end of the chain, as the new root cause
+    pub fn push_cause(&mut self, cause: ResponseException) {
+        let mut last = self;
+        while let Some(ref mut next) = last.cause {
+            last = next;
+        }
+        last.cause = Some(Box::new(cause));
     }
 }
 
 impl From<String> for ResponseException {
     fn from(s: String) -> Self {
-        Self(s)
+        Self::new(s)
     }
 }
 
 impl From<&str> for ResponseException {
     fn from(s: &str) -> Self {
-        Self(s.to_string())
+        Self::new(s)
     }
 }
 
 /// Response body buffer for if you need to accumulate response
 /// body chunks before you're ready to build a response object.
+///
+/// It can also cache a request body that was read in full, since reading a
+/// streaming `RequestBody` consumes it. Store the data with
+/// `RequestExt::set_body_buffer` so later middleware can read it again with
+/// `RequestExt::body_buffer`. The extension is only a copy: the request body
+/// itself stays consumed, so replace it with a fresh `RequestBody` built from
+/// the same data if the handler still needs to stream it. For bodies too
+/// large to keep in memory, see `handler::BufferBody`.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::{BodyBuffer, Request, RequestBody, RequestExt};
+///
+/// async fn cache_body(request: &mut Request) -> Result<(), http_handler::StreamError> {
+///     let data = request.body_mut().collect().await?;
+///     *request.body_mut() = RequestBody::from_data(data.clone()).await?;
+///     request.set_body_buffer(BodyBuffer::from_bytes(data));
+///     Ok(())
+/// }
+/// ```
 #[derive(Clone, Debug, Default)]
 pub struct BodyBuffer {
     buffer: BytesMut,
@@ -255,6 +846,183 @@ impl BodyBuffer {
     }
 }
 
+/// W3C Trace Context for correlating a request with a distributed trace
+///
+/// Parsed from the `traceparent` and `tracestate` headers as described in
+/// <https://www.w3.org/TR/trace-context/>.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    /// Trace id, shared by every span in the trace (never zero)
+    pub trace_id: u128,
+    /// Id of the parent span (never zero)
+    pub span_id: u64,
+    /// Trace flags, where bit 0 is the sampled flag
+    pub trace_flags: u8,
+    /// Vendor-specific `tracestate` list, passed along unchanged
+    pub tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Parse a `traceparent` header value, with an optional `tracestate`
+    ///
+    /// Returns `None` if the `traceparent` is malformed, in which case the
+    /// spec requires `tracestate` to be ignored as well.
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let traceparent = traceparent.trim();
+        let mut parts = traceparent.splitn(5, '-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+        let rest = parts.next();
+
+        if version.len() != 2 || !is_lower_hex(version) || version == "ff" {
+            return None;
+        }
+        // Version 00 has exactly four fields, later versions may append more
+        if version == "00" && rest.is_some() {
+            return None;
+        }
+        if trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+            return None;
+        }
+        if !is_lower_hex(trace_id) || !is_lower_hex(span_id) || !is_lower_hex(flags) {
+            return None;
+        }
+
+        let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+        let span_id = u64::from_str_radix(span_id, 16).ok()?;
+        let trace_flags = u8::from_str_radix(flags, 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+
+        let tracestate = tracestate
+            .map(str::trim)
+            .filter(|state| !state.is_empty())
+            .map(str::to_string);
+
+        Some(Self {
+            trace_id,
+            span_id,
+            trace_flags,
+            tracestate,
+        })
+    }
+
+    /// Parse the trace context from `traceparent` and `tracestate` headers
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let traceparent = headers.get(TRACEPARENT)?.to_str().ok()?;
+
+        // Multiple tracestate headers are combined into one list
+        let tracestate = headers
+            .get_all(TRACESTATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        Self::parse(traceparent, Some(&tracestate))
+    }
+
+    /// Whether the caller may have recorded this trace
+    pub fn is_sampled(&self) -> bool {
+        self.trace_flags & 0x01 != 0
+    }
+
+    /// Format as a version 00 `traceparent` header value
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.span_id, self.trace_flags
+        )
+    }
+
+    /// Write the `traceparent` and `tracestate` headers, replacing any present
+    pub fn inject(&self, headers: &mut HeaderMap) {
+        let traceparent = HeaderValue::from_str(&self.traceparent())
+            .expect("traceparent is always a valid header value");
+        headers.insert(TRACEPARENT, traceparent);
+
+        headers.remove(TRACESTATE);
+        if let Some(state) = &self.tracestate
+            && let Ok(value) = HeaderValue::from_str(state)
+        {
+            headers.insert(TRACESTATE, value);
+        }
+    }
+}
+
+/// Identifier for correlating a request across logs and services
+///
+/// Usually taken from an incoming `X-Request-Id` header, or generated with
+/// `RequestId::generate` (see `RequestExt::ensure_request_id`).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// Create a request id from an existing value
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    /// Generate a random id formatted as a version 4 UUID
+    pub fn generate() -> Self {
+        let mut bits: u128 = rand::random();
+        // Version 4, RFC 4122 variant
+        bits = (bits & !(0xf << 76)) | (0x4 << 76);
+        bits = (bits & !(0x3 << 62)) | (0x2 << 62);
+        let hex = format!("{:032x}", bits);
+        Self(format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        ))
+    }
+
+    /// Get the id as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+
+fn is_lower_hex(s: &str) -> bool {
+    s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Error returned when a request URI is longer than allowed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UriTooLongError {
+    /// Serialized length of the URI
+    pub length: usize,
+    /// Maximum allowed length
+    pub max: usize,
+}
+
+impl fmt::Display for UriTooLongError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "URI length {} exceeds maximum of {}",
+            self.length, self.max
+        )
+    }
+}
+
+impl std::error::Error for UriTooLongError {}
+
 /// Extension trait for http::Request
 ///
 /// This trait provides methods to access and modify socket information related
@@ -270,6 +1038,14 @@ pub trait RequestExt {
     /// Set socket info in request extensions
     fn set_socket_info(&mut self, info: SocketInfo);
 
+    /// Decode socket info from a header written with `SocketInfo::to_header_value`
+    ///
+    /// Returns `Ok(None)` if the header is not present.
+    fn socket_info_from_header(
+        &self,
+        name: &str,
+    ) -> Result<Option<SocketInfo>, SocketInfoParseError>;
+
     /// Get document root from request extensions
     fn document_root(&self) -> Option<&DocumentRoot>;
 
@@ -278,6 +1054,268 @@ pub trait RequestExt {
 
     /// Set document root in request extensions
     fn set_document_root(&mut self, root: DocumentRoot);
+
+    /// Get path parameters captured by the router
+    fn path_params(&self) -> Option<&PathParams>;
+
+    /// Set path parameters in request extensions
+    fn set_path_params(&mut self, params: PathParams);
+
+    /// Parse the query string into decoded key-value pairs
+    ///
+    /// Pairs are returned in order, including repeated keys. `+` decodes to
+    /// a space and percent-encoded bytes are decoded as UTF-8, with invalid
+    /// sequences replaced. A key without `=` has an empty value. Returns an
+    /// empty vec if there is no query.
+    fn query_params(&self) -> Vec<(String, String)>;
+
+    /// Get the decoded value of the first query parameter named `key`
+    fn query_param(&self, key: &str) -> Option<String>;
+
+    /// Get the original request-target form
+    ///
+    /// Returns the `RequestTargetForm` extension if one was set, otherwise
+    /// detects the form from the method and URI.
+    fn request_target_form(&self) -> RequestTargetForm;
+
+    /// Record the original request-target form in request extensions
+    fn set_request_target_form(&mut self, form: RequestTargetForm);
+
+    /// Render the request-target as it goes on the request line
+    fn request_target(&self) -> String;
+
+    /// Record the current method and URI as the original request line
+    ///
+    /// Does nothing if a snapshot was already taken, so calling this before
+    /// every rewrite keeps the values first received.
+    fn snapshot_request_line(&mut self);
+
+    /// Get the method as first received
+    ///
+    /// Returns the method from the `OriginalRequestLine` extension if one was
+    /// recorded, otherwise the current method.
+    fn original_method(&self) -> &Method;
+
+    /// Get the URI as first received
+    ///
+    /// Returns the URI from the `OriginalRequestLine` extension if one was
+    /// recorded, otherwise the current URI.
+    fn original_uri(&self) -> &Uri;
+
+    /// Get the mutual TLS client certificate from request extensions
+    fn client_certificate(&self) -> Option<&ClientCertificate>;
+
+    /// Set the mutual TLS client certificate in request extensions
+    fn set_client_certificate(&mut self, certificate: ClientCertificate);
+
+    /// Get TLS info from request extensions
+    fn tls_info(&self) -> Option<&TlsInfo>;
+
+    /// Get mutable TLS info from request extensions
+    fn tls_info_mut(&mut self) -> &mut TlsInfo;
+
+    /// Set TLS info in request extensions
+    fn set_tls_info(&mut self, info: TlsInfo);
+
+    /// Get metadata from request extensions
+    fn metadata(&self) -> Option<&RequestMetadata>;
+
+    /// Get mutable metadata from request extensions
+    fn metadata_mut(&mut self) -> &mut RequestMetadata;
+
+    /// Get a metadata value by key
+    fn meta_get(&self, key: &str) -> Option<&str>;
+
+    /// Set a metadata value, returning the previous value if any
+    fn meta_set(&mut self, key: impl Into<String>, value: impl Into<String>) -> Option<String>;
+
+    /// Remove all headers whose name matches the predicate
+    ///
+    /// Header names are always lowercase, so a prefix check such as
+    /// `name.as_str().starts_with("x-internal-")` matches any casing.
+    fn remove_headers_matching(&mut self, predicate: impl FnMut(&HeaderName) -> bool);
+
+    /// Remove hop-by-hop headers, which must not be forwarded by proxies
+    ///
+    /// This removes every header named in `Connection`, then the standard
+    /// hop-by-hop set from RFC 7230 Section 6.1: `Connection`, `Keep-Alive`,
+    /// `Proxy-Authenticate`, `Proxy-Authorization`, `TE`, `Trailer`,
+    /// `Transfer-Encoding` and `Upgrade`, plus the legacy `Proxy-Connection`.
+    fn remove_hop_by_hop(&mut self);
+
+    /// Copy all values of the named headers onto a response
+    ///
+    /// Values already present on the response for those names are replaced.
+    fn copy_headers_to<B>(&self, response: &mut http::Response<B>, names: &[HeaderName]);
+
+    /// Get the trace context for this request
+    ///
+    /// Returns the `TraceContext` extension if one was set, otherwise parses
+    /// the `traceparent` and `tracestate` headers. Malformed headers are
+    /// ignored and yield `None`.
+    fn trace_context(&self) -> Option<TraceContext>;
+
+    /// Set trace context in request extensions
+    fn set_trace_context(&mut self, context: TraceContext);
+
+    /// Write trace context headers, for propagating it on an outbound request
+    fn inject_trace_context(&mut self, context: &TraceContext);
+
+    /// Get the request id from request extensions
+    fn request_id(&self) -> Option<&RequestId>;
+
+    /// Set the request id in request extensions
+    fn set_request_id(&mut self, id: RequestId);
+
+    /// Get the request id, generating and storing one first if none is set
+    ///
+    /// Later calls return the same id, so middleware can call this freely.
+    fn ensure_request_id(&mut self) -> &RequestId;
+
+    /// Check that the serialized URI is at most `max` bytes long
+    ///
+    /// The full URI is measured, including scheme and authority when present,
+    /// so servers can reject overlong requests with `414 URI Too Long` before
+    /// dispatching them.
+    fn validate_uri_length(&self, max: usize) -> Result<(), UriTooLongError>;
+
+    /// Check if the request method is safe (RFC 7231 Section 4.2.1)
+    ///
+    /// Safe methods are `GET`, `HEAD`, `OPTIONS` and `TRACE`.
+    fn is_safe_method(&self) -> bool;
+
+    /// Check if the request method is idempotent (RFC 7231 Section 4.2.2)
+    ///
+    /// Idempotent methods are the safe methods plus `PUT` and `DELETE`.
+    fn is_idempotent_method(&self) -> bool;
+
+    /// Convert a request received over HTTP/2 or later for forwarding over
+    /// HTTP/1.1
+    ///
+    /// Sets `Host` from the URI authority (the `:authority` pseudo-header)
+    /// unless already present, reduces the URI to origin form, and joins
+    /// `Cookie` fields split by HTTP/2 into one, as HTTP/1.1 requires. The
+    /// version is set to HTTP/1.1. `CONNECT` requests keep their authority
+    /// form URI. The original request line is snapshotted first.
+    fn downgrade_to_http11(&mut self);
+
+    /// Get the username and password from `Basic` credentials in the
+    /// `Authorization` header
+    ///
+    /// Returns `None` if the header is missing, uses another scheme, or isn't
+    /// valid base64 of UTF-8 `username:password`.
+    fn auth_basic(&self) -> Option<(String, String)>;
+
+    /// Get the token from `Bearer` credentials in the `Authorization` header
+    ///
+    /// Returns `None` if the header is missing, uses another scheme, or
+    /// doesn't hold exactly one token.
+    fn auth_bearer(&self) -> Option<String>;
+
+    /// Parse the `Cookie` headers into a map of name to value
+    ///
+    /// See `cookie::parse_cookies` for how the headers are parsed.
+    fn cookies(&self) -> HashMap<String, String>;
+
+    /// Get the buffered body from request extensions
+    fn body_buffer(&self) -> Option<&BodyBuffer>;
+
+    /// Set the buffered body in request extensions
+    fn set_body_buffer(&mut self, body: BodyBuffer);
+
+    /// Get the WebSocket mode, `WebSocketMode::None` if not set
+    fn websocket_mode(&self) -> WebSocketMode;
+
+    /// Set the WebSocket mode in request extensions
+    fn set_websocket_mode(&mut self, mode: WebSocketMode);
+
+    /// Check if the request asks to upgrade to a WebSocket connection
+    ///
+    /// Requires `websocket` in `Upgrade`, `upgrade` in `Connection` (both
+    /// compared case-insensitively) and a `Sec-WebSocket-Key` header. The key
+    /// itself isn't validated.
+    fn is_websocket_upgrade(&self) -> bool;
+
+    /// Guess the MIME type of the body from its first bytes
+    ///
+    /// Only available for requests with a `RequestBody`. This peeks at the
+    /// body, so the handler still reads it in full afterwards. See
+    /// `RequestBody::sniff_content_type` for the formats recognised.
+    fn sniff_content_type(
+        &mut self,
+    ) -> impl Future<Output = io::Result<Option<&'static str>>> + Send
+    where
+        Self: AsMut<RequestBody>;
+}
+
+/// Headers that are hop-by-hop regardless of the `Connection` header
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+fn remove_headers_matching(
+    headers: &mut HeaderMap,
+    mut predicate: impl FnMut(&HeaderName) -> bool,
+) {
+    let matching: Vec<HeaderName> = headers
+        .keys()
+        .filter(|name| predicate(name))
+        .cloned()
+        .collect();
+    for name in matching {
+        headers.remove(&name);
+    }
+}
+
+/// Headers HTTP/2 forbids, from RFC 9113 Section 8.2.2
+const CONNECTION_SPECIFIC_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Remove the headers named in `Connection`, which apply only to this hop
+fn remove_connection_listed(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|token| HeaderName::from_bytes(token.trim().as_bytes()).ok())
+        .collect();
+    for name in listed {
+        headers.remove(&name);
+    }
+}
+
+/// Get the credentials from the `Authorization` header if it uses `scheme`,
+/// compared case-insensitively
+fn authorization_credentials<'a>(headers: &'a HeaderMap, scheme: &str) -> Option<&'a str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?.trim();
+    let (found, credentials) = value.split_once(|c: char| c.is_ascii_whitespace())?;
+    if !found.eq_ignore_ascii_case(scheme) {
+        return None;
+    }
+    let credentials = credentials.trim();
+    (!credentials.is_empty()).then_some(credentials)
+}
+
+pub(crate) fn remove_hop_by_hop(headers: &mut HeaderMap) {
+    remove_connection_listed(headers);
+
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(*name);
+    }
 }
 
 impl<T> RequestExt for http::Request<T> {
@@ -296,6 +1334,19 @@ impl<T> RequestExt for http::Request<T> {
         self.extensions_mut().insert(info);
     }
 
+    fn socket_info_from_header(
+        &self,
+        name: &str,
+    ) -> Result<Option<SocketInfo>, SocketInfoParseError> {
+        let Some(value) = self.headers().get(name) else {
+            return Ok(None);
+        };
+        let value = value
+            .to_str()
+            .map_err(|_| SocketInfoParseError::InvalidHeaderValue)?;
+        SocketInfo::from_header_value(value).map(Some)
+    }
+
     fn document_root(&self) -> Option<&DocumentRoot> {
         self.extensions().get::<DocumentRoot>()
     }
@@ -310,33 +1361,352 @@ impl<T> RequestExt for http::Request<T> {
     fn set_document_root(&mut self, root: DocumentRoot) {
         self.extensions_mut().insert(root);
     }
-}
 
-/// Extension trait for http::request::Builder
-///
-/// This trait provides methods to access and modify socket information related
-/// to the request. This includes the local and remote socket IP addresses,
-/// ports, and IP address families.
-pub trait RequestBuilderExt {
-    /// Set socket info in request builder
-    fn socket_info(self, info: SocketInfo) -> http::request::Builder;
+    fn path_params(&self) -> Option<&PathParams> {
+        self.extensions().get::<PathParams>()
+    }
 
-    /// Set document root in request builder
-    fn document_root(self, root: DocumentRoot) -> http::request::Builder;
-}
+    fn set_path_params(&mut self, params: PathParams) {
+        self.extensions_mut().insert(params);
+    }
 
-impl RequestBuilderExt for http::request::Builder {
-    fn socket_info(self, info: SocketInfo) -> http::request::Builder {
-        self.extension(info)
+    fn query_params(&self) -> Vec<(String, String)> {
+        let Some(query) = self.uri().query() else {
+            return Vec::new();
+        };
+        query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode_query_component(key), decode_query_component(value))
+            })
+            .collect()
     }
 
-    fn document_root(self, root: DocumentRoot) -> http::request::Builder {
-        self.extension(root)
+    fn query_param(&self, key: &str) -> Option<String> {
+        self.query_params()
+            .into_iter()
+            .find_map(|(name, value)| (name == key).then_some(value))
     }
-}
 
-/// Extension trait for http::Response
-///
+    fn request_target_form(&self) -> RequestTargetForm {
+        match self.extensions().get::<RequestTargetForm>() {
+            Some(form) => *form,
+            None => RequestTargetForm::detect(self.method(), self.uri()),
+        }
+    }
+
+    fn set_request_target_form(&mut self, form: RequestTargetForm) {
+        self.extensions_mut().insert(form);
+    }
+
+    fn request_target(&self) -> String {
+        self.request_target_form().render(self.uri())
+    }
+
+    fn snapshot_request_line(&mut self) {
+        if self.extensions().get::<OriginalRequestLine>().is_none() {
+            let original = OriginalRequestLine {
+                method: self.method().clone(),
+                uri: self.uri().clone(),
+            };
+            self.extensions_mut().insert(original);
+        }
+    }
+
+    fn original_method(&self) -> &Method {
+        match self.extensions().get::<OriginalRequestLine>() {
+            Some(original) => &original.method,
+            None => self.method(),
+        }
+    }
+
+    fn original_uri(&self) -> &Uri {
+        match self.extensions().get::<OriginalRequestLine>() {
+            Some(original) => &original.uri,
+            None => self.uri(),
+        }
+    }
+
+    fn client_certificate(&self) -> Option<&ClientCertificate> {
+        self.extensions().get::<ClientCertificate>()
+    }
+
+    fn set_client_certificate(&mut self, certificate: ClientCertificate) {
+        self.extensions_mut().insert(certificate);
+    }
+
+    fn tls_info(&self) -> Option<&TlsInfo> {
+        self.extensions().get::<TlsInfo>()
+    }
+
+    fn tls_info_mut(&mut self) -> &mut TlsInfo {
+        if self.extensions().get::<TlsInfo>().is_none() {
+            self.extensions_mut().insert(TlsInfo::default());
+        }
+        self.extensions_mut().get_mut::<TlsInfo>().unwrap()
+    }
+
+    fn set_tls_info(&mut self, info: TlsInfo) {
+        self.extensions_mut().insert(info);
+    }
+
+    fn metadata(&self) -> Option<&RequestMetadata> {
+        self.extensions().get::<RequestMetadata>()
+    }
+
+    fn metadata_mut(&mut self) -> &mut RequestMetadata {
+        if self.extensions().get::<RequestMetadata>().is_none() {
+            self.extensions_mut().insert(RequestMetadata::default());
+        }
+        self.extensions_mut().get_mut::<RequestMetadata>().unwrap()
+    }
+
+    fn meta_get(&self, key: &str) -> Option<&str> {
+        self.metadata()?.get(key)
+    }
+
+    fn meta_set(&mut self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.metadata_mut().insert(key, value)
+    }
+
+    fn remove_headers_matching(&mut self, predicate: impl FnMut(&HeaderName) -> bool) {
+        remove_headers_matching(self.headers_mut(), predicate);
+    }
+
+    fn remove_hop_by_hop(&mut self) {
+        remove_hop_by_hop(self.headers_mut());
+    }
+
+    fn copy_headers_to<B>(&self, response: &mut http::Response<B>, names: &[HeaderName]) {
+        let target = response.headers_mut();
+        for name in names {
+            let mut values = self.headers().get_all(name).iter();
+            let Some(first) = values.next() else {
+                continue;
+            };
+            target.insert(name.clone(), first.clone());
+            for value in values {
+                target.append(name.clone(), value.clone());
+            }
+        }
+    }
+
+    fn trace_context(&self) -> Option<TraceContext> {
+        if let Some(context) = self.extensions().get::<TraceContext>() {
+            return Some(context.clone());
+        }
+        TraceContext::from_headers(self.headers())
+    }
+
+    fn set_trace_context(&mut self, context: TraceContext) {
+        self.extensions_mut().insert(context);
+    }
+
+    fn inject_trace_context(&mut self, context: &TraceContext) {
+        context.inject(self.headers_mut());
+    }
+
+    fn request_id(&self) -> Option<&RequestId> {
+        self.extensions().get::<RequestId>()
+    }
+
+    fn set_request_id(&mut self, id: RequestId) {
+        self.extensions_mut().insert(id);
+    }
+
+    fn ensure_request_id(&mut self) -> &RequestId {
+        if self.extensions().get::<RequestId>().is_none() {
+            self.extensions_mut().insert(RequestId::generate());
+        }
+        self.extensions().get::<RequestId>().unwrap()
+    }
+
+    fn validate_uri_length(&self, max: usize) -> Result<(), UriTooLongError> {
+        let length = self.uri().to_string().len();
+        if length > max {
+            return Err(UriTooLongError { length, max });
+        }
+        Ok(())
+    }
+
+    fn is_safe_method(&self) -> bool {
+        matches!(
+            *self.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        )
+    }
+
+    fn is_idempotent_method(&self) -> bool {
+        self.is_safe_method() || matches!(*self.method(), Method::PUT | Method::DELETE)
+    }
+
+    fn downgrade_to_http11(&mut self) {
+        self.snapshot_request_line();
+        *self.version_mut() = Version::HTTP_11;
+
+        if !self.headers().contains_key(header::HOST)
+            && let Some(authority) = self.uri().authority()
+            && let Ok(host) = HeaderValue::from_str(authority.as_str())
+        {
+            self.headers_mut().insert(header::HOST, host);
+        }
+
+        if self.method() != Method::CONNECT
+            && let Some(path_and_query) = self.uri().path_and_query().cloned()
+        {
+            *self.uri_mut() = path_and_query.into();
+        }
+
+        if self.headers().get_all(header::COOKIE).iter().count() > 1 {
+            let joined = self
+                .headers()
+                .get_all(header::COOKIE)
+                .iter()
+                .map(HeaderValue::as_bytes)
+                .collect::<Vec<_>>()
+                .join(&b"; "[..]);
+            if let Ok(value) = HeaderValue::from_bytes(&joined) {
+                self.headers_mut().insert(header::COOKIE, value);
+            }
+        }
+    }
+
+    fn auth_basic(&self) -> Option<(String, String)> {
+        use base64::Engine;
+
+        let credentials = authorization_credentials(self.headers(), "Basic")?;
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(credentials)
+            .ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+        Some((username.to_string(), password.to_string()))
+    }
+
+    fn auth_bearer(&self) -> Option<String> {
+        let token = authorization_credentials(self.headers(), "Bearer")?;
+        if token.contains(|c: char| c.is_ascii_whitespace()) {
+            return None;
+        }
+        Some(token.to_string())
+    }
+
+    fn cookies(&self) -> HashMap<String, String> {
+        crate::cookie::parse_cookies(self.headers())
+    }
+
+    fn body_buffer(&self) -> Option<&BodyBuffer> {
+        self.extensions().get::<BodyBuffer>()
+    }
+
+    fn set_body_buffer(&mut self, body: BodyBuffer) {
+        self.extensions_mut().insert(body);
+    }
+
+    fn websocket_mode(&self) -> WebSocketMode {
+        self.extensions()
+            .get::<WebSocketMode>()
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn set_websocket_mode(&mut self, mode: WebSocketMode) {
+        self.extensions_mut().insert(mode);
+    }
+
+    fn is_websocket_upgrade(&self) -> bool {
+        let headers = self.headers();
+        has_header_token(headers, &header::UPGRADE, "websocket")
+            && has_header_token(headers, &header::CONNECTION, "upgrade")
+            && headers.contains_key(header::SEC_WEBSOCKET_KEY)
+    }
+
+    fn sniff_content_type(
+        &mut self,
+    ) -> impl Future<Output = io::Result<Option<&'static str>>> + Send
+    where
+        Self: AsMut<RequestBody>,
+    {
+        self.as_mut().sniff_content_type()
+    }
+}
+
+/// Check if a comma-separated header lists `token`, compared case-insensitively
+fn has_header_token(headers: &HeaderMap, name: &HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|found| found.trim().eq_ignore_ascii_case(token))
+}
+
+/// Decode a query string key or value, treating `+` as a space
+fn decode_query_component(component: &str) -> String {
+    let component = component.replace('+', " ");
+    percent_encoding::percent_decode_str(&component)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// Extension trait for http::request::Builder
+///
+/// This trait provides methods to access and modify socket information related
+/// to the request. This includes the local and remote socket IP addresses,
+/// ports, and IP address families.
+pub trait RequestBuilderExt {
+    /// Set socket info in request builder
+    fn socket_info(self, info: SocketInfo) -> http::request::Builder;
+
+    /// Set document root in request builder
+    fn document_root(self, root: DocumentRoot) -> http::request::Builder;
+
+    /// Set TLS info in request builder
+    fn tls_info(self, info: TlsInfo) -> http::request::Builder;
+
+    /// Set the request id in request builder
+    fn request_id(self, id: RequestId) -> http::request::Builder;
+
+    /// Set the WebSocket mode in request builder
+    fn websocket_mode(self, mode: WebSocketMode) -> http::request::Builder;
+
+    /// Finish the builder with a body containing the given buffered data
+    ///
+    /// The data is written into the body without awaiting, so this can be
+    /// used outside of async contexts.
+    fn buffered_body(self, data: impl Into<Bytes>) -> Result<crate::Request, http::Error>;
+}
+
+impl RequestBuilderExt for http::request::Builder {
+    fn socket_info(self, info: SocketInfo) -> http::request::Builder {
+        self.extension(info)
+    }
+
+    fn document_root(self, root: DocumentRoot) -> http::request::Builder {
+        self.extension(root)
+    }
+
+    fn tls_info(self, info: TlsInfo) -> http::request::Builder {
+        self.extension(info)
+    }
+
+    fn request_id(self, id: RequestId) -> http::request::Builder {
+        self.extension(id)
+    }
+
+    fn websocket_mode(self, mode: WebSocketMode) -> http::request::Builder {
+        self.extension(mode)
+    }
+
+    fn buffered_body(self, data: impl Into<Bytes>) -> Result<crate::Request, http::Error> {
+        self.body(crate::RequestBody::buffered(data.into()))
+    }
+}
+
+/// Extension trait for http::Response
+///
 /// This trait provides methods to access and modify response logs and
 /// exceptions.
 pub trait ResponseExt {
@@ -349,7 +1719,7 @@ pub trait ResponseExt {
     /// Set log in response extensions
     fn set_log(&mut self, log: impl Into<Bytes>);
 
-    /// Append to the log
+    /// Append to the log, as an `Info` entry
     fn append_log(&mut self, data: impl AsRef<[u8]>);
 
     /// Get exception from response extensions
@@ -357,6 +1727,34 @@ pub trait ResponseExt {
 
     /// Set exception in response extensions
     fn set_exception(&mut self, exception: impl Into<String>);
+
+    /// Set exception from an error, capturing its `source()` chain as causes
+    fn set_exception_from<E: std::error::Error + ?Sized>(&mut self, err: &E);
+
+    /// Add an exception without losing any already set
+    ///
+    /// If an exception is set, the new one is linked as its root cause,
+    /// otherwise it is set.
+    fn push_exception(&mut self, exception: impl Into<ResponseException>);
+
+    /// Write trace context headers onto the response
+    fn inject_trace_context(&mut self, context: &TraceContext);
+
+    /// Remove headers the given HTTP version doesn't allow, and set the
+    /// response version to it
+    ///
+    /// For HTTP/2 and later this removes connection-specific headers
+    /// (`Connection` and the headers it names, `Keep-Alive`,
+    /// `Proxy-Connection`, `Transfer-Encoding` and `Upgrade`). For HTTP/1.0
+    /// and earlier it removes `Transfer-Encoding` and `Trailer`, since chunked
+    /// transfer coding isn't available.
+    fn prepare_for_version(&mut self, version: Version);
+
+    /// Append a `Set-Cookie` header for `cookie`
+    ///
+    /// Fails if the cookie doesn't make a valid header value, e.g. because
+    /// it contains control characters.
+    fn add_set_cookie(&mut self, cookie: Cookie) -> Result<(), InvalidHeaderValue>;
 }
 
 impl<T> ResponseExt for http::Response<T> {
@@ -387,6 +1785,45 @@ impl<T> ResponseExt for http::Response<T> {
         self.extensions_mut()
             .insert(ResponseException::new(exception));
     }
+
+    fn push_exception(&mut self, exception: impl Into<ResponseException>) {
+        let exception = exception.into();
+        match self.extensions_mut().get_mut::<ResponseException>() {
+            Some(existing) => existing.push_cause(exception),
+            None => {
+                self.extensions_mut().insert(exception);
+            }
+        }
+    }
+
+    fn set_exception_from<E: std::error::Error + ?Sized>(&mut self, err: &E) {
+        self.extensions_mut()
+            .insert(ResponseException::from_error(err));
+    }
+
+    fn inject_trace_context(&mut self, context: &TraceContext) {
+        context.inject(self.headers_mut());
+    }
+
+    fn prepare_for_version(&mut self, version: Version) {
+        let headers = self.headers_mut();
+        if version >= Version::HTTP_2 {
+            remove_connection_listed(headers);
+            for name in CONNECTION_SPECIFIC_HEADERS {
+                headers.remove(*name);
+            }
+        } else if version <= Version::HTTP_10 {
+            headers.remove(header::TRANSFER_ENCODING);
+            headers.remove(header::TRAILER);
+        }
+        *self.version_mut() = version;
+    }
+
+    fn add_set_cookie(&mut self, cookie: Cookie) -> Result<(), InvalidHeaderValue> {
+        let value = HeaderValue::from_str(&cookie.to_string())?;
+        self.headers_mut().append(header::SET_COOKIE, value);
+        Ok(())
+    }
 }
 
 /// Extension trait for http::response::Builder
@@ -414,6 +1851,23 @@ pub trait ResponseBuilderExt {
 
     /// Append to the body extension
     fn append_body(&mut self, data: impl AsRef<[u8]>) -> &mut Self;
+
+    /// Finish the builder with a body containing the given buffered data
+    ///
+    /// Unlike `body_buffer`, which only records the data as an extension, this
+    /// writes the data into the streaming `ResponseBody` without awaiting.
+    /// `Content-Length` is set to the length of the data, unless a
+    /// `Transfer-Encoding` header is present.
+    fn buffered_body(self, data: impl Into<Bytes>) -> Result<crate::Response, http::Error>;
+
+    /// Accept a WebSocket upgrade request
+    ///
+    /// Sets the `101 Switching Protocols` status, the `Upgrade` and
+    /// `Connection` headers and the `Sec-WebSocket-Accept` key computed from
+    /// the request's `Sec-WebSocket-Key`, and marks the response with
+    /// `WebSocketMode::Server`. Returns `None` if the request isn't a
+    /// WebSocket upgrade, as checked by `RequestExt::is_websocket_upgrade`.
+    fn websocket_accept<T>(self, request: &http::Request<T>) -> Option<http::response::Builder>;
 }
 
 impl ResponseBuilderExt for http::response::Builder {
@@ -454,6 +1908,37 @@ impl ResponseBuilderExt for http::response::Builder {
         self.body_buffer_mut().append(data);
         self
     }
+
+    fn buffered_body(mut self, data: impl Into<Bytes>) -> Result<crate::Response, http::Error> {
+        let data = data.into();
+        if let Some(headers) = self.headers_mut()
+            && !headers.contains_key(header::TRANSFER_ENCODING)
+        {
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(data.len()));
+        }
+        self.body(crate::ResponseBody::buffered(data))
+    }
+
+    fn websocket_accept<T>(self, request: &http::Request<T>) -> Option<http::response::Builder> {
+        if !request.is_websocket_upgrade() {
+            return None;
+        }
+        let key = request
+            .headers()
+            .get(header::SEC_WEBSOCKET_KEY)?
+            .to_str()
+            .ok()?;
+        Some(
+            self.status(http::StatusCode::SWITCHING_PROTOCOLS)
+                .header(header::UPGRADE, "websocket")
+                .header(header::CONNECTION, "Upgrade")
+                .header(
+                    header::SEC_WEBSOCKET_ACCEPT,
+                    crate::websocket::compute_accept_key(key),
+                )
+                .extension(WebSocketMode::Server),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -479,6 +1964,418 @@ mod tests {
         assert_eq!(info.remote, Some(remote));
     }
 
+    #[test]
+    fn test_socket_info_header_round_trip() {
+        let local = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
+        let remote: SocketAddr = "[2001:db8::1]:5000".parse().unwrap();
+
+        let cases = [
+            (
+                SocketInfo::new(Some(local), Some(remote)),
+                "local=127.0.0.1:8080;remote=[2001:db8::1]:5000",
+            ),
+            (SocketInfo::with_local(local), "local=127.0.0.1:8080"),
+            (SocketInfo::with_remote(remote), "remote=[2001:db8::1]:5000"),
+            (SocketInfo::default(), ""),
+        ];
+
+        for (info, encoded) in cases {
+            assert_eq!(info.to_header_value(), encoded);
+            assert_eq!(SocketInfo::from_header_value(encoded), Ok(info));
+        }
+
+        let request = http::Request::builder()
+            .header("x-socket-info", "remote=[::1]:443; local=10.0.0.1:80")
+            .body(())
+            .unwrap();
+        let info = request.socket_info_from_header("x-socket-info").unwrap();
+        assert_eq!(
+            info,
+            Some(SocketInfo::new(
+                Some("10.0.0.1:80".parse().unwrap()),
+                Some("[::1]:443".parse().unwrap())
+            ))
+        );
+        assert_eq!(request.socket_info_from_header("x-missing"), Ok(None));
+    }
+
+    #[test]
+    fn test_request_target_origin_form() {
+        let mut request = http::Request::builder()
+            .uri("/search?q=rust")
+            .body(())
+            .unwrap();
+        assert_eq!(request.request_target_form(), RequestTargetForm::Origin);
+        assert_eq!(request.request_target(), "/search?q=rust");
+
+        // Received in origin form, then rewritten to an absolute URI
+        request.set_request_target_form(RequestTargetForm::Origin);
+        *request.uri_mut() = "https://example.com/search?q=rust".parse().unwrap();
+        assert_eq!(request.request_target(), "/search?q=rust");
+    }
+
+    #[test]
+    fn test_original_request_line() {
+        let mut request = http::Request::builder()
+            .method(Method::POST)
+            .uri("/items/1?_method=DELETE")
+            .body(())
+            .unwrap();
+        assert_eq!(request.original_method(), Method::POST);
+
+        request.snapshot_request_line();
+        *request.method_mut() = Method::DELETE;
+        *request.uri_mut() = "/items/1".parse().unwrap();
+
+        // A later snapshot keeps the values first received
+        request.snapshot_request_line();
+        assert_eq!(request.method(), Method::DELETE);
+        assert_eq!(request.original_method(), Method::POST);
+        assert_eq!(request.original_uri(), "/items/1?_method=DELETE");
+    }
+
+    #[test]
+    fn test_downgrade_keeps_original_uri() {
+        let mut request = http::Request::builder()
+            .uri("https://example.com/path")
+            .version(Version::HTTP_2)
+            .body(())
+            .unwrap();
+        request.downgrade_to_http11();
+        assert_eq!(request.uri(), "/path");
+        assert_eq!(request.original_uri(), "https://example.com/path");
+    }
+
+    #[test]
+    fn test_request_target_absolute_form() {
+        let request = http::Request::builder()
+            .uri("http://example.com:8080/path?x=1")
+            .body(())
+            .unwrap();
+        assert_eq!(request.request_target_form(), RequestTargetForm::Absolute);
+        assert_eq!(request.request_target(), "http://example.com:8080/path?x=1");
+    }
+
+    #[test]
+    fn test_request_target_authority_form() {
+        let mut request = http::Request::builder()
+            .method(Method::CONNECT)
+            .uri("example.com:443")
+            .body(())
+            .unwrap();
+        assert_eq!(request.request_target_form(), RequestTargetForm::Authority);
+        assert_eq!(request.request_target(), "example.com:443");
+
+        request.set_request_target_form(RequestTargetForm::Authority);
+        *request.uri_mut() = "https://example.com:443/".parse().unwrap();
+        assert_eq!(request.request_target(), "example.com:443");
+    }
+
+    #[test]
+    fn test_request_target_asterisk_form() {
+        let mut request = http::Request::builder()
+            .method(Method::OPTIONS)
+            .uri("*")
+            .body(())
+            .unwrap();
+        assert_eq!(request.request_target_form(), RequestTargetForm::Asterisk);
+        assert_eq!(request.request_target(), "*");
+
+        request.set_request_target_form(RequestTargetForm::Asterisk);
+        *request.uri_mut() = "https://example.com/".parse().unwrap();
+        assert_eq!(request.request_target(), "*");
+    }
+
+    #[test]
+    fn test_client_certificate() {
+        use std::time::Duration;
+
+        let issued = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let certificate = ClientCertificate {
+            subject: "CN=client,O=Example".to_string(),
+            issuer: "CN=Example CA".to_string(),
+            serial: "0a1b2c".to_string(),
+            not_before: issued,
+            not_after: issued + Duration::from_secs(86_400),
+            der: Bytes::from_static(&[0x30, 0x82, 0x01, 0x0a]),
+        };
+
+        let mut request = http::Request::builder().body(()).unwrap();
+        assert!(request.client_certificate().is_none());
+
+        request.set_client_certificate(certificate.clone());
+        let stored = request.client_certificate().unwrap();
+        assert_eq!(stored, &certificate);
+        assert_eq!(stored.der, &[0x30, 0x82, 0x01, 0x0a][..]);
+
+        assert!(stored.is_valid_at(issued + Duration::from_secs(60)));
+        assert!(!stored.is_valid_at(issued - Duration::from_secs(1)));
+        assert!(!stored.is_valid_at(issued + Duration::from_secs(86_401)));
+    }
+
+    #[test]
+    fn test_tls_info() {
+        let info = TlsInfo {
+            protocol: Some("TLSv1.3".to_string()),
+            cipher: Some("TLS_AES_128_GCM_SHA256".to_string()),
+            sni: Some("example.com".to_string()),
+            alpn: Some("h2".to_string()),
+            peer_certificates: vec![Bytes::from_static(&[0x30, 0x82, 0x01, 0x0a])],
+        };
+
+        let mut request = http::Request::builder()
+            .tls_info(info.clone())
+            .body(())
+            .unwrap();
+        assert_eq!(request.tls_info(), Some(&info));
+        assert!(request.tls_info().unwrap().has_peer_certificate());
+
+        request.tls_info_mut().peer_certificates.clear();
+        assert!(!request.tls_info().unwrap().has_peer_certificate());
+
+        let mut request = http::Request::builder().body(()).unwrap();
+        assert!(request.tls_info().is_none());
+        request.tls_info_mut().sni = Some("example.org".to_string());
+        assert_eq!(
+            request.tls_info().unwrap().sni.as_deref(),
+            Some("example.org")
+        );
+        request.set_tls_info(info.clone());
+        assert_eq!(request.tls_info(), Some(&info));
+    }
+
+    #[test]
+    fn test_document_root_canonical() {
+        let dir = tempfile::tempdir().unwrap();
+        let public = dir.path().join("public");
+        std::fs::create_dir_all(public.join("assets")).unwrap();
+        std::fs::write(public.join("index.html"), "hi").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+
+        let root = DocumentRoot::new_canonical(public.join("assets/..")).unwrap();
+        assert_eq!(root.path, public.canonicalize().unwrap());
+        assert!(root.path.is_absolute());
+
+        let err = DocumentRoot::new_canonical(public.join("index.html")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotADirectory);
+        let err = DocumentRoot::new_canonical(dir.path().join("missing")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        assert!(root.contains(Path::new("index.html")));
+        assert!(root.contains(Path::new("assets/../index.html")));
+        assert!(root.contains(Path::new("assets/new/file.css")));
+        assert!(root.contains(&public.join("index.html")));
+        assert!(!root.contains(Path::new("../secret.txt")));
+        assert!(!root.contains(Path::new("assets/../../secret.txt")));
+        assert!(!root.contains(Path::new("missing/../../secret.txt")));
+        assert!(!root.contains(&dir.path().join("secret.txt")));
+
+        // A symlink leading out of the root doesn't count as inside it
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), public.join("escape")).unwrap();
+            assert!(!root.contains(Path::new("escape/secret.txt")));
+            assert!(!root.contains(Path::new("escape/new.txt")));
+        }
+    }
+
+    #[test]
+    fn test_query_params() {
+        let request = http::Request::builder()
+            .uri("/search?q=rust+lang&tag=a&tag=b&empty=&flag&&caf%C3%A9=cr%C3%A8me%20br%C3%BBl%C3%A9e&plus=1%2B1")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            request.query_params(),
+            [
+                ("q", "rust lang"),
+                ("tag", "a"),
+                ("tag", "b"),
+                ("empty", ""),
+                ("flag", ""),
+                ("café", "crème brûlée"),
+                ("plus", "1+1"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+        assert_eq!(request.query_param("q").as_deref(), Some("rust lang"));
+        assert_eq!(request.query_param("tag").as_deref(), Some("a"));
+        assert_eq!(request.query_param("flag").as_deref(), Some(""));
+        assert_eq!(request.query_param("café").as_deref(), Some("crème brûlée"));
+        assert_eq!(request.query_param("missing"), None);
+
+        let request = http::Request::builder().uri("/search").body(()).unwrap();
+        assert!(request.query_params().is_empty());
+        assert_eq!(request.query_param("q"), None);
+    }
+
+    #[tokio::test]
+    async fn test_request_body_buffer() {
+        let body = crate::RequestBody::from_data(Bytes::from("{\"id\":1}"))
+            .await
+            .unwrap();
+        let mut request = http::Request::builder().body(body).unwrap();
+        assert!(request.body_buffer().is_none());
+
+        let data = request.body_mut().collect().await.unwrap();
+        request.set_body_buffer(BodyBuffer::from_bytes(data));
+
+        // The cached copy can be read again after the body was consumed
+        let buffer = request.body_buffer().unwrap();
+        assert_eq!(buffer.as_bytes(), b"{\"id\":1}");
+        assert_eq!(buffer.len(), 8);
+        assert_eq!(request.body_buffer().unwrap().as_bytes(), b"{\"id\":1}");
+        assert!(request.body_mut().collect().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_request_sniff_content_type() {
+        use tokio::io::AsyncReadExt;
+
+        let json = Bytes::from("  {\"name\": \"upload\"}");
+        let mut request = http::Request::builder()
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(RequestBody::from_data_streaming(json.clone()))
+            .unwrap();
+        assert_eq!(
+            request.sniff_content_type().await.unwrap(),
+            Some("application/json")
+        );
+
+        // The handler still sees the whole body
+        let mut data = Vec::new();
+        request.body_mut().read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, json);
+    }
+
+    #[test]
+    fn test_websocket_mode() {
+        let mut request = http::Request::builder().body(()).unwrap();
+        assert_eq!(request.websocket_mode(), WebSocketMode::None);
+        assert!(!request.websocket_mode().is_enabled());
+        assert_eq!(request.websocket_mode().role(), None);
+
+        request.set_websocket_mode(WebSocketMode::Client);
+        assert_eq!(request.websocket_mode(), WebSocketMode::Client);
+        assert_eq!(request.websocket_mode().role(), Some(WebSocketRole::Client));
+
+        let request = http::Request::builder()
+            .websocket_mode(WebSocketMode::Server)
+            .body(())
+            .unwrap();
+        assert!(request.websocket_mode().is_enabled());
+        assert_eq!(request.websocket_mode().role(), Some(WebSocketRole::Server));
+    }
+
+    #[test]
+    fn test_is_websocket_upgrade() {
+        let upgrade = |headers: &[(&str, &str)]| {
+            let mut builder = http::Request::builder().uri("/chat");
+            for (name, value) in headers {
+                builder = builder.header(*name, *value);
+            }
+            builder.body(()).unwrap().is_websocket_upgrade()
+        };
+        let key = ("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==");
+
+        assert!(upgrade(&[
+            ("upgrade", "websocket"),
+            ("connection", "Upgrade"),
+            key
+        ]));
+        assert!(upgrade(&[
+            ("upgrade", "WebSocket"),
+            ("connection", "keep-alive, upgrade"),
+            key
+        ]));
+
+        assert!(!upgrade(&[]));
+        assert!(!upgrade(&[
+            ("upgrade", "websocket"),
+            ("connection", "Upgrade")
+        ]));
+        assert!(!upgrade(&[
+            ("upgrade", "h2c"),
+            ("connection", "Upgrade"),
+            key
+        ]));
+        assert!(!upgrade(&[
+            ("upgrade", "websocket"),
+            ("connection", "close"),
+            key
+        ]));
+        assert!(!upgrade(&[("upgrade", "websocket"), key]));
+    }
+
+    #[test]
+    fn test_websocket_accept() {
+        let request = http::Request::builder()
+            .uri("/chat")
+            .header(header::UPGRADE, "websocket")
+            .header(header::CONNECTION, "Upgrade")
+            .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(())
+            .unwrap();
+        let response = http::Response::builder()
+            .websocket_accept(&request)
+            .unwrap()
+            .body(())
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(response.headers()[header::UPGRADE], "websocket");
+        assert_eq!(response.headers()[header::CONNECTION], "Upgrade");
+        assert_eq!(
+            response.headers()[header::SEC_WEBSOCKET_ACCEPT],
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(
+            response.extensions().get::<WebSocketMode>(),
+            Some(&WebSocketMode::Server)
+        );
+
+        let request = http::Request::builder().uri("/chat").body(()).unwrap();
+        assert!(
+            http::Response::builder()
+                .websocket_accept(&request)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_request_metadata() {
+        let mut request = http::Request::builder().body(()).unwrap();
+        assert!(request.metadata().is_none());
+        assert_eq!(request.meta_get("user_id"), None);
+
+        assert_eq!(request.meta_set("user_id", "42"), None);
+        assert_eq!(request.meta_set("user_id", "43"), Some("42".to_string()));
+        request.metadata_mut().insert("tenant", "acme");
+
+        assert_eq!(request.meta_get("user_id"), Some("43"));
+        assert_eq!(request.meta_get("tenant"), Some("acme"));
+        assert_eq!(request.metadata().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_socket_info_header_errors() {
+        assert_eq!(
+            SocketInfo::from_header_value("local"),
+            Err(SocketInfoParseError::InvalidPart("local".to_string()))
+        );
+        assert_eq!(
+            SocketInfo::from_header_value("peer=1.2.3.4:5"),
+            Err(SocketInfoParseError::UnknownKey("peer".to_string()))
+        );
+        assert_eq!(
+            SocketInfo::from_header_value("local=1.2.3.4:5;local=1.2.3.4:6"),
+            Err(SocketInfoParseError::DuplicateKey("local".to_string()))
+        );
+        assert_eq!(
+            SocketInfo::from_header_value("remote=::1:80"),
+            Err(SocketInfoParseError::InvalidAddress("::1:80".to_string()))
+        );
+    }
+
     #[test]
     fn test_response_log() {
         let mut log = ResponseLog::new();
@@ -517,6 +2414,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_response_log_entries() {
+        let mut log = ResponseLog::new();
+        log.push(LogLevel::Debug, "cache miss");
+        log.append("GET /");
+        log.push(LogLevel::Warn, "slow \"upstream\"");
+        log.push(LogLevel::Error, "upstream failed");
+        assert_eq!(
+            log.as_bytes(),
+            b"cache miss\nGET /\nslow \"upstream\"\nupstream failed\n"
+        );
+
+        let levels: Vec<_> = log.entries().iter().map(|entry| entry.level).collect();
+        assert_eq!(
+            levels,
+            [
+                LogLevel::Debug,
+                LogLevel::Info,
+                LogLevel::Warn,
+                LogLevel::Error
+            ]
+        );
+        assert_eq!(log.entries()[1].message, "GET /");
+
+        let severe: Vec<_> = log
+            .entries_at_least(LogLevel::Warn)
+            .map(|entry| entry.message.as_str())
+            .collect();
+        assert_eq!(severe, ["slow \"upstream\"", "upstream failed"]);
+        assert_eq!(log.entries_at_least(LogLevel::Trace).count(), 4);
+
+        let json = log.to_json_lines();
+        let lines: Vec<_> = json.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with(
+            "{\"level\":\"warn\",\"message\":\"slow \\\"upstream\\\"\",\"timestamp\":"
+        ));
+        assert!(lines[2].ends_with('}'));
+
+        // append_log adds info entries
+        let mut response = http::Response::new(());
+        response.append_log("handled");
+        assert_eq!(response.log().unwrap().entries()[0].level, LogLevel::Info);
+    }
+
     #[test]
     fn test_response_exception() {
         let exc = ResponseException::new("Error occurred");
@@ -527,6 +2469,69 @@ mod tests {
 
         let exc: ResponseException = "String error".into();
         assert_eq!(exc.message(), "String error");
+        assert_eq!(exc.kind(), None);
+    }
+
+    #[test]
+    fn test_push_exception_chain() {
+        let mut response = http::Response::builder().body(()).unwrap();
+        response.push_exception(ResponseException::new("proxy failed").with_kind("ProxyError"));
+        response.push_exception("connection reset");
+
+        let exception = response.exception().unwrap();
+        assert_eq!(exception.kind(), Some("ProxyError"));
+        let messages: Vec<_> = exception.chain().map(|e| e.message()).collect();
+        assert_eq!(messages, ["proxy failed", "connection reset"]);
+        assert_eq!(exception.root_cause().message(), "connection reset");
+        assert_eq!(exception.root_cause().kind(), None);
+
+        // A lone exception is its own root cause
+        let exception = ResponseException::new("alone");
+        assert_eq!(exception.chain().count(), 1);
+        assert_eq!(exception.root_cause(), &exception);
+    }
+
+    #[derive(Debug)]
+    struct ChainedError {
+        message: &'static str,
+        source: Option<Box<ChainedError>>,
+    }
+
+    impl fmt::Display for ChainedError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    impl std::error::Error for ChainedError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.source.as_deref().map(|e| e as _)
+        }
+    }
+
+    #[test]
+    fn test_set_exception_from_error_chain() {
+        let err = ChainedError {
+            message: "request failed",
+            source: Some(Box::new(ChainedError {
+                message: "upstream unavailable",
+                source: Some(Box::new(ChainedError {
+                    message: "connection refused",
+                    source: None,
+                })),
+            })),
+        };
+
+        let mut response = http::Response::builder().body(()).unwrap();
+        response.set_exception_from(&err);
+
+        let exception = response.exception().unwrap();
+        assert_eq!(exception.message(), "request failed");
+        let cause = exception.cause().unwrap();
+        assert_eq!(cause.message(), "upstream unavailable");
+        let root = cause.cause().unwrap();
+        assert_eq!(root.message(), "connection refused");
+        assert!(root.cause().is_none());
     }
 
     #[test]
@@ -581,4 +2586,387 @@ mod tests {
         response.log_mut().clear();
         assert!(response.log().unwrap().is_empty());
     }
+
+    #[tokio::test]
+    async fn test_request_builder_buffered_body() {
+        use tokio::io::AsyncReadExt;
+
+        let request = http::Request::builder()
+            .method("POST")
+            .uri("/upload")
+            .buffered_body("hello world")
+            .unwrap();
+        assert_eq!(request.method(), "POST");
+
+        let mut data = Vec::new();
+        request.into_body().read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn test_response_builder_buffered_body() {
+        use http_body_util::BodyExt;
+
+        // Larger than the default buffer size
+        let payload = Bytes::from(vec![b'x'; 64 * 1024]);
+        let response = http::Response::builder()
+            .status(201)
+            .buffered_body(payload.clone())
+            .unwrap();
+        assert_eq!(response.status(), 201);
+
+        let collected = response.into_body().collect().await.unwrap();
+        assert_eq!(collected.to_bytes(), payload);
+    }
+
+    #[test]
+    fn test_response_builder_buffered_body_content_length() {
+        let response = http::Response::builder()
+            .buffered_body("hello world")
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "11");
+        assert_eq!(
+            http_body::Body::size_hint(response.body()).exact(),
+            Some(11)
+        );
+
+        // Chunked responses keep their framing
+        let response = http::Response::builder()
+            .header(header::TRANSFER_ENCODING, "chunked")
+            .buffered_body("hello world")
+            .unwrap();
+        assert!(!response.headers().contains_key(header::CONTENT_LENGTH));
+    }
+
+    #[test]
+    fn test_remove_headers_matching() {
+        let mut request = http::Request::builder()
+            .header("X-Internal-Token", "secret")
+            .header("x-internal-trace", "abc")
+            .header("X-Request-Id", "123")
+            .body(())
+            .unwrap();
+
+        request.remove_headers_matching(|name| name.as_str().starts_with("x-internal-"));
+
+        assert!(request.headers().get("x-internal-token").is_none());
+        assert!(request.headers().get("x-internal-trace").is_none());
+        assert_eq!(request.headers()["x-request-id"], "123");
+    }
+
+    #[test]
+    fn test_remove_hop_by_hop() {
+        let mut request = http::Request::builder()
+            .header("Connection", "keep-alive, X-Custom-Hop")
+            .header("Connection", "Upgrade")
+            .header("X-Custom-Hop", "1")
+            .header("Keep-Alive", "timeout=5")
+            .header("Transfer-Encoding", "chunked")
+            .header("TE", "trailers")
+            .header("Upgrade", "websocket")
+            .header("Proxy-Authorization", "Basic abc")
+            .header("Content-Type", "text/plain")
+            .body(())
+            .unwrap();
+
+        request.remove_hop_by_hop();
+
+        let remaining: Vec<&str> = request.headers().keys().map(|name| name.as_str()).collect();
+        assert_eq!(remaining, ["content-type"]);
+    }
+
+    #[test]
+    fn test_downgrade_to_http11() {
+        let mut request = http::Request::builder()
+            .version(Version::HTTP_2)
+            .uri("https://example.com:8443/search?q=rust")
+            .header("Cookie", "a=1")
+            .header("Cookie", "b=2")
+            .body(())
+            .unwrap();
+
+        request.downgrade_to_http11();
+
+        assert_eq!(request.version(), Version::HTTP_11);
+        assert_eq!(request.headers()["host"], "example.com:8443");
+        assert_eq!(request.uri(), "/search?q=rust");
+        let cookies: Vec<_> = request.headers().get_all("cookie").iter().collect();
+        assert_eq!(cookies, ["a=1; b=2"]);
+
+        // An existing Host header is kept
+        let mut request = http::Request::builder()
+            .version(Version::HTTP_2)
+            .uri("https://internal.example.com/")
+            .header("Host", "example.com")
+            .body(())
+            .unwrap();
+        request.downgrade_to_http11();
+        assert_eq!(request.headers()["host"], "example.com");
+    }
+
+    #[test]
+    fn test_auth_basic() {
+        let request = |value: &str| {
+            http::Request::builder()
+                .header("Authorization", value)
+                .body(())
+                .unwrap()
+        };
+
+        // "Aladdin:open sesame"
+        let credentials = Some(("Aladdin".to_string(), "open sesame".to_string()));
+        assert_eq!(
+            request("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==").auth_basic(),
+            credentials
+        );
+        assert_eq!(
+            request("  basic   QWxhZGRpbjpvcGVuIHNlc2FtZQ==  ").auth_basic(),
+            credentials
+        );
+
+        // Not base64, no colon, another scheme, no credentials
+        for value in [
+            "Basic not*base64",
+            "Basic QWxhZGRpbg==",
+            "Bearer QWxhZGRpbjpvcGVuIHNlc2FtZQ==",
+            "Basic",
+            "Basic ",
+        ] {
+            assert_eq!(request(value).auth_basic(), None, "{}", value);
+        }
+        assert_eq!(http::Request::new(()).auth_basic(), None);
+    }
+
+    #[test]
+    fn test_auth_bearer() {
+        let request = |value: &str| {
+            http::Request::builder()
+                .header("Authorization", value)
+                .body(())
+                .unwrap()
+        };
+
+        assert_eq!(
+            request("Bearer abc.def-123").auth_bearer(),
+            Some("abc.def-123".to_string())
+        );
+        assert_eq!(
+            request("BEARER\tabc ").auth_bearer(),
+            Some("abc".to_string())
+        );
+        assert_eq!(request("Bearer a b").auth_bearer(), None);
+        assert_eq!(request("Bearer").auth_bearer(), None);
+        assert_eq!(request("Basic abc").auth_bearer(), None);
+    }
+
+    #[test]
+    fn test_prepare_for_version() {
+        let response = || {
+            http::Response::builder()
+                .header("Connection", "keep-alive, X-Hop")
+                .header("X-Hop", "1")
+                .header("Keep-Alive", "timeout=5")
+                .header("Transfer-Encoding", "chunked")
+                .header("Trailer", "X-Checksum")
+                .header("Content-Type", "text/plain")
+                .body(())
+                .unwrap()
+        };
+
+        let mut http10 = response();
+        http10.prepare_for_version(Version::HTTP_10);
+        assert_eq!(http10.version(), Version::HTTP_10);
+        assert!(!http10.headers().contains_key("transfer-encoding"));
+        assert!(!http10.headers().contains_key("trailer"));
+        assert!(http10.headers().contains_key("connection"));
+
+        let mut http2 = response();
+        http2.prepare_for_version(Version::HTTP_2);
+        let mut remaining: Vec<&str> = http2.headers().keys().map(|name| name.as_str()).collect();
+        remaining.sort();
+        assert_eq!(remaining, ["content-type", "trailer"]);
+
+        let mut http11 = response();
+        http11.prepare_for_version(Version::HTTP_11);
+        assert_eq!(http11.headers().len(), 6);
+    }
+
+    #[test]
+    fn test_copy_headers_to() {
+        let request = http::Request::builder()
+            .header("X-Request-Id", "123")
+            .header("Vary", "Accept")
+            .header("Vary", "Origin")
+            .header("Authorization", "secret")
+            .body(())
+            .unwrap();
+        let mut response = http::Response::builder()
+            .header("Vary", "Cookie")
+            .body(())
+            .unwrap();
+
+        request.copy_headers_to(
+            &mut response,
+            &[
+                HeaderName::from_static("x-request-id"),
+                header::VARY,
+                HeaderName::from_static("x-missing"),
+            ],
+        );
+
+        assert_eq!(response.headers()["x-request-id"], "123");
+        let vary: Vec<_> = response.headers().get_all(header::VARY).iter().collect();
+        assert_eq!(vary, ["Accept", "Origin"]);
+        assert!(response.headers().get(header::AUTHORIZATION).is_none());
+        assert!(response.headers().get("x-missing").is_none());
+    }
+
+    #[test]
+    fn test_trace_context_parse() {
+        let request = http::Request::builder()
+            .header(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .header("tracestate", "congo=t61rcWkgMzE")
+            .header("tracestate", "rojo=00f067aa0ba902b7")
+            .body(())
+            .unwrap();
+
+        let context = request.trace_context().unwrap();
+        assert_eq!(context.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+        assert_eq!(context.span_id, 0x00f067aa0ba902b7);
+        assert!(context.is_sampled());
+        assert_eq!(
+            context.tracestate.as_deref(),
+            Some("congo=t61rcWkgMzE,rojo=00f067aa0ba902b7")
+        );
+
+        // Round trip onto a response
+        let mut response = http::Response::builder().body(()).unwrap();
+        response.inject_trace_context(&context);
+        assert_eq!(
+            response.headers()["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert_eq!(
+            response.headers()["tracestate"],
+            "congo=t61rcWkgMzE,rojo=00f067aa0ba902b7"
+        );
+    }
+
+    #[test]
+    fn test_trace_context_malformed() {
+        let malformed = [
+            // Uppercase hex
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            // All-zero trace id
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            // All-zero span id
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            // Invalid version
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            // Version 00 with extra fields
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            // Short trace id
+            "00-4bf92f3577b34da6-00f067aa0ba902b7-01",
+            "not a traceparent",
+        ];
+
+        for traceparent in malformed {
+            let request = http::Request::builder()
+                .header("traceparent", traceparent)
+                .header("tracestate", "congo=t61rcWkgMzE")
+                .body(())
+                .unwrap();
+            assert_eq!(request.trace_context(), None, "{traceparent}");
+        }
+
+        // Later versions may append fields
+        let context = TraceContext::parse(
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra",
+            None,
+        )
+        .unwrap();
+        assert!(!context.is_sampled());
+    }
+
+    #[test]
+    fn test_request_id() {
+        let request = http::Request::builder()
+            .request_id(RequestId::new("req-1"))
+            .body(())
+            .unwrap();
+        assert_eq!(request.request_id().unwrap().as_str(), "req-1");
+
+        let mut request = http::Request::builder().body(()).unwrap();
+        assert!(request.request_id().is_none());
+        let id = request.ensure_request_id().clone();
+        assert_eq!(request.ensure_request_id(), &id);
+        assert_eq!(request.request_id(), Some(&id));
+
+        // Formatted like a version 4 UUID
+        let parts: Vec<_> = id.as_str().split('-').map(str::len).collect();
+        assert_eq!(parts, [8, 4, 4, 4, 12]);
+        assert_eq!(&id.as_str()[14..15], "4");
+        assert!(matches!(&id.as_str()[19..20], "8" | "9" | "a" | "b"));
+        assert_ne!(RequestId::generate(), id);
+
+        request.set_request_id(RequestId::new("req-2"));
+        assert_eq!(request.ensure_request_id().to_string(), "req-2");
+    }
+
+    #[test]
+    fn test_method_classification() {
+        let cases = [
+            (Method::GET, true, true),
+            (Method::HEAD, true, true),
+            (Method::OPTIONS, true, true),
+            (Method::TRACE, true, true),
+            (Method::PUT, false, true),
+            (Method::DELETE, false, true),
+            (Method::POST, false, false),
+            (Method::PATCH, false, false),
+            (Method::CONNECT, false, false),
+            (Method::from_bytes(b"PURGE").unwrap(), false, false),
+        ];
+
+        for (method, safe, idempotent) in cases {
+            let request = http::Request::builder()
+                .method(method.clone())
+                .body(())
+                .unwrap();
+            assert_eq!(request.is_safe_method(), safe, "{} safe", method);
+            assert_eq!(
+                request.is_idempotent_method(),
+                idempotent,
+                "{} idempotent",
+                method
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_uri_length() {
+        let request = http::Request::builder()
+            .uri("https://example.com/search?q=rust")
+            .body(())
+            .unwrap();
+
+        // "https://example.com/search?q=rust" is 33 bytes
+        assert_eq!(request.validate_uri_length(33), Ok(()));
+        assert_eq!(
+            request.validate_uri_length(32),
+            Err(UriTooLongError {
+                length: 33,
+                max: 32
+            })
+        );
+
+        let long_path = format!("/{}", "a".repeat(2048));
+        let request = http::Request::builder().uri(long_path).body(()).unwrap();
+        let err = request.validate_uri_length(1024).unwrap_err();
+        assert_eq!(err.length, 2049);
+        assert_eq!(err.to_string(), "URI length 2049 exceeds maximum of 1024");
+    }
 }
@@ -0,0 +1,193 @@
+//! Access logging wrapper.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use http::{Method, StatusCode};
+
+use super::Handler;
+use crate::{Request, RequestExt, Response};
+
+type LogFn = Arc<dyn Fn(AccessLogRecord) + Send + Sync>;
+
+/// One access log entry, describing a request and its response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessLogRecord {
+    /// Request method, as received
+    pub method: Method,
+    /// Request path and query, as received
+    pub path: String,
+    /// Response status
+    pub status: StatusCode,
+    /// Time from receiving the request to the end of the response body
+    pub duration: Duration,
+    /// Number of response body bytes read
+    pub bytes: u64,
+    /// Whether the whole response body was read
+    ///
+    /// This is `false` when the body was dropped part way through, such as
+    /// when the client disconnected or the body failed.
+    pub completed: bool,
+}
+
+/// Handler wrapper that logs each request once its response is finished
+///
+/// The callback runs once per request, when the response body has been read
+/// to the end or dropped before that, so the duration and byte count cover
+/// the whole response. The method and path are those first received, even
+/// if the inner handler rewrote them (see `RequestExt::original_uri`).
+/// Requests the inner handler fails with an error are not logged.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::handler::{AccessLog, Fixed};
+/// use http_handler::{HeaderMap, StatusCode};
+///
+/// let inner = Fixed::new(StatusCode::OK, HeaderMap::new(), "ok");
+/// let handler = AccessLog::new(inner, |record| {
+///     println!(
+///         "{} {} {} {}B {:?}",
+///         record.method, record.path, record.status, record.bytes, record.duration
+///     );
+/// });
+/// ```
+#[derive(Clone)]
+pub struct AccessLog<H> {
+    inner: H,
+    log: LogFn,
+}
+
+impl<H> AccessLog<H> {
+    /// Wrap a handler, passing a record of each request to `log`
+    pub fn new<F>(inner: H, log: F) -> Self
+    where
+        F: Fn(AccessLogRecord) + Send + Sync + 'static,
+    {
+        Self {
+            inner,
+            log: Arc::new(log),
+        }
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+impl<H> fmt::Debug for AccessLog<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLog").finish_non_exhaustive()
+    }
+}
+
+impl<H: Handler> Handler for AccessLog<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, mut request: Request) -> Result<Response, Self::Error> {
+        let start = Instant::now();
+        request.snapshot_request_line();
+        let method = request.original_method().clone();
+        let path = request
+            .original_uri()
+            .path_and_query()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| "/".to_string());
+
+        let response = self.inner.handle(request).await?;
+
+        let status = response.status();
+        let log = Arc::clone(&self.log);
+        Ok(response.map(|body| {
+            body.on_finish(move |bytes, completed| {
+                log(AccessLogRecord {
+                    method,
+                    path,
+                    status,
+                    duration: start.elapsed(),
+                    bytes,
+                    completed,
+                })
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::Fixed;
+    use crate::{RequestBody, ResponseBody};
+    use bytes::Bytes;
+    use http::HeaderMap;
+    use http_body_util::BodyExt;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn recorder() -> (Arc<Mutex<Vec<AccessLogRecord>>>, impl Fn(AccessLogRecord)) {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&records);
+        (records, move |record| sink.lock().unwrap().push(record))
+    }
+
+    #[tokio::test]
+    async fn test_logs_once_after_body_is_read() {
+        let (records, log) = recorder();
+        let inner = Fixed::new(StatusCode::CREATED, HeaderMap::new(), "Hello, World!");
+        let handler = AccessLog::new(inner, log);
+
+        let request = http::Request::builder()
+            .method("POST")
+            .uri("/items?draft=1")
+            .body(RequestBody::new())
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+        assert!(records.lock().unwrap().is_empty());
+
+        let data = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(data, "Hello, World!");
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].method, Method::POST);
+        assert_eq!(records[0].path, "/items?draft=1");
+        assert_eq!(records[0].status, StatusCode::CREATED);
+        assert_eq!(records[0].bytes, 13);
+        assert!(records[0].completed);
+    }
+
+    #[tokio::test]
+    async fn test_logs_aborted_body() {
+        struct Streaming;
+
+        impl Handler for Streaming {
+            type Error = std::convert::Infallible;
+
+            async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+                let body = ResponseBody::new();
+                let mut writer = body.clone();
+                writer.write_all(b"partial").await.unwrap();
+                Ok(http::Response::new(body))
+            }
+        }
+
+        let (records, log) = recorder();
+        let handler = AccessLog::new(Streaming, log);
+        let request = http::Request::builder()
+            .uri("/stream")
+            .body(RequestBody::from_data(Bytes::new()).await.unwrap())
+            .unwrap();
+
+        let mut body = handler.handle(request).await.unwrap().into_body();
+        let mut chunk = [0u8; 7];
+        body.read_exact(&mut chunk).await.unwrap();
+        drop(body);
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].bytes, 7);
+        assert!(!records[0].completed);
+    }
+}
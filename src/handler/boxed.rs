@@ -0,0 +1,43 @@
+//! Type-erased handlers, for registries holding handlers of different types.
+
+use std::{future::Future, pin::Pin};
+
+use super::Handler;
+use crate::{BoxError, Request, Response};
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+trait ErasedHandler: Send + Sync {
+    fn handle_boxed(&self, request: Request) -> BoxFuture<'_, Result<Response, BoxError>>;
+}
+
+impl<H> ErasedHandler for H
+where
+    H: Handler + Send + Sync,
+    H::Error: std::error::Error + Send + Sync + 'static,
+{
+    fn handle_boxed(&self, request: Request) -> BoxFuture<'_, Result<Response, BoxError>> {
+        Box::pin(async move { self.handle(request).await.map_err(Into::into) })
+    }
+}
+
+/// A handler of any type, with its error boxed.
+pub(crate) struct BoxHandler(Box<dyn ErasedHandler>);
+
+impl BoxHandler {
+    pub(crate) fn new<H>(handler: H) -> Self
+    where
+        H: Handler + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        Self(Box::new(handler))
+    }
+}
+
+impl Handler for BoxHandler {
+    type Error = BoxError;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        self.0.handle_boxed(request).await
+    }
+}
@@ -0,0 +1,448 @@
+//! Request body buffering wrapper.
+
+use std::fmt;
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use http::StatusCode;
+use tempfile::TempPath;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncWriteExt, ReadBuf};
+use tokio::task::JoinHandle;
+
+use super::Handler;
+use crate::{Request, RequestBody, Response, ResponseBody, ResponseExt};
+
+/// Default number of bytes kept in memory before spilling to disk (1 MiB)
+const DEFAULT_MEMORY_LIMIT: usize = 1024 * 1024;
+
+/// Handler wrapper that reads the whole request body before the inner handler
+///
+/// Bodies up to the memory limit are kept in memory. Larger ones are spilled
+/// to a temporary file, which is removed once the last reference to it is
+/// dropped. Either way, the inner handler gets a request whose body replays
+/// the complete data, plus a `BufferedBody` extension it can use to read the
+/// body again as often as it likes. This suits handlers that need the whole
+/// body up front, like signature verification.
+///
+/// If the body can't be read, the inner handler is skipped and a
+/// `400 Bad Request` is returned. If the temporary file can't be written, a
+/// `500 Internal Server Error` is returned. Both record the failure as the
+/// response exception. Buffering doesn't bound the body size on disk, so
+/// combine it with `LimitBody` to cap it.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::handler::{BufferBody, Fixed};
+/// use http_handler::{HeaderMap, StatusCode};
+///
+/// let inner = Fixed::new(StatusCode::OK, HeaderMap::new(), "ok");
+/// // Keep bodies of up to 64 KiB in memory
+/// let handler = BufferBody::new(inner).memory_limit(64 * 1024);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BufferBody<H> {
+    inner: H,
+    memory_limit: usize,
+    spill_dir: Option<PathBuf>,
+}
+
+impl<H> BufferBody<H> {
+    /// Wrap a handler, buffering request bodies of up to 1 MiB in memory
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            spill_dir: None,
+        }
+    }
+
+    /// Set how many bytes are kept in memory before spilling to disk
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Set the directory spilled bodies are written to (defaults to the
+    /// system temporary directory)
+    pub fn spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = Some(dir.into());
+        self
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    /// Create a temporary file to spill a body to
+    fn spill_file(&self) -> io::Result<(File, TempPath)> {
+        let file = match &self.spill_dir {
+            Some(dir) => tempfile::NamedTempFile::new_in(dir)?,
+            None => tempfile::NamedTempFile::new()?,
+        };
+        let (file, path) = file.into_parts();
+        Ok((File::from_std(file), path))
+    }
+
+    /// Read a body to the end, spilling it to disk past the memory limit
+    async fn buffer(&self, mut body: RequestBody) -> Result<BufferedBody, BufferError> {
+        let mut memory = BytesMut::new();
+        let mut chunk = vec![0u8; 8192];
+        let mut spilled: Option<(File, TempPath)> = None;
+        let mut len = 0u64;
+
+        loop {
+            let n = body.read(&mut chunk).await.map_err(BufferError::Read)?;
+            if n == 0 {
+                break;
+            }
+            len += n as u64;
+
+            if spilled.is_none() && memory.len() + n > self.memory_limit {
+                let (mut file, path) = self.spill_file().map_err(BufferError::Spill)?;
+                file.write_all(&memory).await.map_err(BufferError::Spill)?;
+                memory = BytesMut::new();
+                spilled = Some((file, path));
+            }
+
+            match spilled.as_mut() {
+                Some((file, _)) => file
+                    .write_all(&chunk[..n])
+                    .await
+                    .map_err(BufferError::Spill)?,
+                None => memory.extend_from_slice(&chunk[..n]),
+            }
+        }
+
+        let stored = match spilled {
+            Some((mut file, path)) => {
+                file.flush().await.map_err(BufferError::Spill)?;
+                Stored::File { path, len }
+            }
+            None => Stored::Memory(memory.freeze()),
+        };
+        Ok(BufferedBody(Arc::new(stored)))
+    }
+}
+
+/// Why buffering a body failed
+enum BufferError {
+    /// Reading the request body failed
+    Read(io::Error),
+    /// Writing the temporary file failed
+    Spill(io::Error),
+}
+
+impl BufferError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            Self::Read(e) => (
+                StatusCode::BAD_REQUEST,
+                format!("failed to read body: {}", e),
+            ),
+            Self::Spill(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to spill body to disk: {}", e),
+            ),
+        };
+        let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+        *response.status_mut() = status;
+        response.set_exception(message);
+        response
+    }
+}
+
+impl<H: Handler> Handler for BufferBody<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let (mut parts, body) = request.into_parts();
+        let buffer_size = body.buffer_size();
+        let buffered = match self.buffer(body).await {
+            Ok(buffered) => buffered,
+            Err(e) => return Ok(e.into_response()),
+        };
+
+        let (body, _) = buffered.replay(buffer_size).await;
+        parts.extensions.insert(buffered);
+        self.inner.handle(Request::from_parts(parts, body)).await
+    }
+}
+
+/// Where a buffered body is stored
+enum Stored {
+    Memory(Bytes),
+    File { path: TempPath, len: u64 },
+}
+
+/// A fully buffered request body that can be read any number of times
+///
+/// `BufferBody` inserts this as a request extension. Clones share the same
+/// data, and a spilled body's temporary file is removed once the last clone
+/// and reader are dropped.
+#[derive(Clone)]
+pub struct BufferedBody(Arc<Stored>);
+
+impl BufferedBody {
+    /// Get the length of the body in bytes
+    pub fn len(&self) -> u64 {
+        match &*self.0 {
+            Stored::Memory(data) => data.len() as u64,
+            Stored::File { len, .. } => *len,
+        }
+    }
+
+    /// Check if the body is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check if the body was spilled to a temporary file
+    pub fn is_spilled(&self) -> bool {
+        matches!(&*self.0, Stored::File { .. })
+    }
+
+    /// Get the path of the temporary file, if the body was spilled
+    pub fn path(&self) -> Option<&Path> {
+        match &*self.0 {
+            Stored::Memory(_) => None,
+            Stored::File { path, .. } => Some(path),
+        }
+    }
+
+    /// Open a reader positioned at the start of the body
+    pub async fn reader(&self) -> io::Result<BufferedBodyReader> {
+        let source = match &*self.0 {
+            Stored::Memory(data) => ReaderSource::Memory(io::Cursor::new(data.clone())),
+            Stored::File { path, .. } => ReaderSource::File(File::open(path).await?),
+        };
+        Ok(BufferedBodyReader {
+            source,
+            body: self.clone(),
+        })
+    }
+
+    /// Read the whole body into memory
+    pub async fn to_bytes(&self) -> io::Result<Bytes> {
+        if let Stored::Memory(data) = &*self.0 {
+            return Ok(data.clone());
+        }
+        let mut data = Vec::with_capacity(self.len() as usize);
+        self.reader().await?.read_to_end(&mut data).await?;
+        Ok(Bytes::from(data))
+    }
+
+    /// Create a request body replaying the buffered data, along with the
+    /// task writing it if one was spawned
+    async fn replay(&self, buffer_size: usize) -> (RequestBody, Option<JoinHandle<()>>) {
+        if let Stored::Memory(data) = &*self.0 {
+            return (RequestBody::buffered(data.clone()), None);
+        }
+
+        // Write through the write side only, so the copy fails and the task
+        // ends, dropping the reader and its file, if the body is dropped
+        // before it's read to the end
+        let body = RequestBody::new_with_buffer_size(buffer_size);
+        let mut writer = body.writer();
+        let reader = self.reader().await;
+        let task = tokio::spawn(async move {
+            // The reader is dropped before the body ends, so the file is
+            // released by the time the body has been read
+            let result = match reader {
+                Ok(mut reader) => tokio::io::copy(&mut reader, &mut writer).await,
+                Err(e) => Err(e),
+            };
+            let _ = match result {
+                Ok(_) => writer.finish().await,
+                Err(e) => {
+                    writer
+                        .abort(format!("failed to replay buffered body: {}", e))
+                        .await
+                }
+            };
+        });
+        (body, Some(task))
+    }
+}
+
+impl fmt::Debug for BufferedBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferedBody")
+            .field("len", &self.len())
+            .field("path", &self.path())
+            .finish()
+    }
+}
+
+enum ReaderSource {
+    Memory(io::Cursor<Bytes>),
+    File(File),
+}
+
+/// A reader over a `BufferedBody`, from `BufferedBody::reader`
+///
+/// Supports seeking, and keeps the body's temporary file alive while open.
+pub struct BufferedBodyReader {
+    source: ReaderSource,
+    body: BufferedBody,
+}
+
+impl fmt::Debug for BufferedBodyReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferedBodyReader")
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncRead for BufferedBodyReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().source {
+            ReaderSource::Memory(cursor) => Pin::new(cursor).poll_read(cx, buf),
+            ReaderSource::File(file) => Pin::new(file).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncSeek for BufferedBodyReader {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        match &mut self.get_mut().source {
+            ReaderSource::Memory(cursor) => Pin::new(cursor).start_seek(position),
+            ReaderSource::File(file) => Pin::new(file).start_seek(position),
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        match &mut self.get_mut().source {
+            ReaderSource::Memory(cursor) => Pin::new(cursor).poll_complete(cx),
+            ReaderSource::File(file) => Pin::new(file).poll_complete(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBuilderExt;
+    use http_body_util::BodyExt;
+    use std::sync::Mutex;
+    use tokio::io::AsyncSeekExt;
+
+    /// Echoes the request body, checking it matches a second read of the
+    /// buffered body and recording where that was stored
+    #[derive(Default)]
+    struct Echo {
+        spilled_to: Arc<Mutex<Option<PathBuf>>>,
+    }
+
+    impl Handler for Echo {
+        type Error = io::Error;
+
+        async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+            let (parts, mut body) = request.into_parts();
+            let mut data = Vec::new();
+            body.read_to_end(&mut data).await?;
+
+            let buffered = parts.extensions.get::<BufferedBody>().unwrap();
+            assert_eq!(buffered.len(), data.len() as u64);
+            assert_eq!(buffered.to_bytes().await?, data);
+
+            let mut reader = buffered.reader().await?;
+            reader.seek(SeekFrom::Start(1)).await?;
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).await?;
+            assert_eq!(rest, data[1..]);
+
+            *self.spilled_to.lock().unwrap() = buffered.path().map(Path::to_path_buf);
+            Ok(http::Response::new(ResponseBody::buffered(Bytes::from(
+                data,
+            ))))
+        }
+    }
+
+    fn body(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn test_small_body_stays_in_memory() {
+        let echo = Echo::default();
+        let spilled_to = echo.spilled_to.clone();
+        let handler = BufferBody::new(echo).memory_limit(1024);
+
+        let data = body(1000);
+        let request = http::Request::builder()
+            .method("POST")
+            .buffered_body(data.clone())
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+
+        let echoed = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(echoed, data);
+        assert!(spilled_to.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_large_body_spills_to_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let echo = Echo::default();
+        let spilled_to = echo.spilled_to.clone();
+        let handler = BufferBody::new(echo)
+            .memory_limit(1024)
+            .spill_dir(dir.path());
+
+        let data = body(100_000);
+        let request = http::Request::builder()
+            .method("POST")
+            .buffered_body(data.clone())
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+
+        let echoed = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(echoed, data);
+
+        // The replay task let go of the file before ending the body
+        let path = spilled_to.lock().unwrap().take().unwrap();
+        assert!(path.starts_with(dir.path()));
+        assert!(!path.exists(), "temporary file should be removed");
+    }
+
+    #[tokio::test]
+    async fn test_ignored_spilled_body_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = BufferBody::new(()).memory_limit(1024).spill_dir(dir.path());
+
+        // Streamed, so the replay body gets the default buffer size, which
+        // is too small for the replay to finish without a reader
+        let request_body = RequestBody::new();
+        let buffer_size = request_body.buffer_size();
+        let mut writer = request_body.writer();
+        tokio::spawn(async move {
+            writer.write_all(&body(200_000)).await.unwrap();
+            writer.finish().await.unwrap();
+        });
+        let Ok(buffered) = handler.buffer(request_body).await else {
+            panic!("buffering failed");
+        };
+        assert!(buffered.is_spilled());
+
+        // Dropping the replayed body unread ends the replay task
+        let (replayed, task) = buffered.replay(buffer_size).await;
+        drop(replayed);
+        task.unwrap().await.unwrap();
+
+        drop(buffered);
+        let is_empty = std::fs::read_dir(dir.path()).unwrap().next().is_none();
+        assert!(is_empty, "temporary file should be removed");
+    }
+}
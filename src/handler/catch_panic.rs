@@ -0,0 +1,147 @@
+//! Panic-catching handler wrapper.
+
+use std::panic::AssertUnwindSafe;
+
+use bytes::Bytes;
+use futures_util::FutureExt;
+use http::StatusCode;
+
+use super::Handler;
+use crate::body::panic_message;
+use crate::{Request, Response, ResponseBody, ResponseExt};
+
+/// Default maximum length of the panic message recorded as the exception
+const DEFAULT_MAX_MESSAGE_LEN: usize = 1024;
+
+/// Handler wrapper that turns panics into `500 Internal Server Error` responses
+///
+/// A panic in the inner handler is caught and the panic message is recorded
+/// as the response exception, truncated to a maximum length. With
+/// `redact_in_release` enabled, release builds record a generic message
+/// instead so panic details aren't exposed.
+///
+/// Panics in tasks spawned by the handler to write the response body are not
+/// seen by this wrapper. Use `ResponseBody::spawn_writer` for those tasks so a
+/// panic is reported to the reader as a body error.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::{Handler, Request, Response};
+/// use http_handler::handler::CatchPanic;
+///
+/// struct Flaky;
+///
+/// impl Handler for Flaky {
+///     type Error = std::convert::Infallible;
+///
+///     async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+///         panic!("something went wrong");
+///     }
+/// }
+///
+/// let handler = CatchPanic::new(Flaky).redact_in_release(true);
+/// ```
+#[derive(Clone, Debug)]
+pub struct CatchPanic<H> {
+    inner: H,
+    max_message_len: usize,
+    redact_in_release: bool,
+}
+
+impl<H> CatchPanic<H> {
+    /// Wrap a handler, catching any panics it raises
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            max_message_len: DEFAULT_MAX_MESSAGE_LEN,
+            redact_in_release: false,
+        }
+    }
+
+    /// Set the maximum length in bytes of the recorded panic message
+    pub fn max_message_len(mut self, len: usize) -> Self {
+        self.max_message_len = len;
+        self
+    }
+
+    /// Set whether release builds hide the panic message
+    pub fn redact_in_release(mut self, enabled: bool) -> Self {
+        self.redact_in_release = enabled;
+        self
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    fn exception_message(&self, mut message: String) -> String {
+        if self.redact_in_release && !cfg!(debug_assertions) {
+            return "handler panicked".to_string();
+        }
+
+        if message.len() > self.max_message_len {
+            let mut end = self.max_message_len;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+            message.push('…');
+        }
+        format!("handler panicked: {}", message)
+    }
+}
+
+impl<H: Handler> Handler for CatchPanic<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        match AssertUnwindSafe(self.inner.handle(request))
+            .catch_unwind()
+            .await
+        {
+            Ok(result) => result,
+            Err(payload) => {
+                let message = self.exception_message(panic_message(&*payload));
+
+                let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                response.set_exception(message);
+                Ok(response)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBody;
+
+    struct Panics;
+
+    impl Handler for Panics {
+        type Error = std::convert::Infallible;
+
+        async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+            panic!("kaboom: {}", "x".repeat(100));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panic_becomes_500() {
+        let handler = CatchPanic::new(Panics).max_message_len(5);
+        let request = http::Request::builder()
+            .uri("/")
+            .body(RequestBody::new())
+            .unwrap();
+
+        let response = handler.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.exception().unwrap().message(),
+            "handler panicked: kaboo…"
+        );
+    }
+}
@@ -0,0 +1,497 @@
+//! Response compression wrapper.
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Bytes, BytesMut};
+use flate2::Compression;
+use futures_util::FutureExt;
+use http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use http_body::{Body, Frame};
+use http_body_util::BodyExt;
+
+use super::Handler;
+use crate::{
+    Request, Response, ResponseBody,
+    body::{CompressedBody, ContentEncoding, compressed_len, pump::pump},
+};
+
+/// Largest number of bytes sampled before deciding whether to compress
+const DEFAULT_SAMPLE_SIZE: usize = 16 * 1024;
+
+/// Default minimum compression ratio worth sending compressed
+const DEFAULT_MIN_RATIO: f64 = 1.1;
+
+/// Handler wrapper that gzip-compresses responses
+///
+/// Responses are compressed when the request's `Accept-Encoding` allows gzip
+/// and the response isn't already encoded, partial, bodiless, an event
+/// stream, or marked `Cache-Control: no-transform`.
+///
+/// Compressing data that is already compressed, such as images or gzipped
+/// files, wastes CPU and can make it larger. So before committing, the start
+/// of the body is sampled and compressed, and unless that achieves at least
+/// `min_ratio` (original size over compressed size), the response is sent
+/// uncompressed instead. The sample is the first data frame, plus the frames
+/// already available after it, up to `sample_size` bytes. It never waits for
+/// more data than the first frame, so slowly streamed responses aren't held
+/// back. The frames sampled are then sent on first, so the body, trailers
+/// included, arrives whole either way.
+///
+/// Every response that could have been compressed gets `Vary:
+/// Accept-Encoding`, whether or not it was, so caches don't serve one
+/// client's encoding to another.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::handler::{Compress, Fixed};
+/// use http::{HeaderMap, StatusCode};
+///
+/// let report = Fixed::new(StatusCode::OK, HeaderMap::new(), "{\"rows\":[]}");
+/// let handler = Compress::new(report).sample_size(8 * 1024).min_ratio(1.25);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Compress<H> {
+    inner: H,
+    level: u32,
+    sample_size: usize,
+    min_ratio: f64,
+}
+
+impl<H> Compress<H> {
+    /// Wrap a handler, compressing its responses
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            level: Compression::default().level(),
+            sample_size: DEFAULT_SAMPLE_SIZE,
+            min_ratio: DEFAULT_MIN_RATIO,
+        }
+    }
+
+    /// Set the compression level, from 0 (none) to 9 (best, slowest)
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Set the most bytes sampled to decide whether to compress
+    /// (16KB by default)
+    pub fn sample_size(mut self, bytes: usize) -> Self {
+        self.sample_size = bytes.max(1);
+        self
+    }
+
+    /// Set the compression ratio the sample must achieve for the response to
+    /// be sent compressed (1.1 by default)
+    pub fn min_ratio(mut self, ratio: f64) -> Self {
+        self.min_ratio = ratio;
+        self
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+/// Check whether `Accept-Encoding` allows gzip
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut params = coding.split(';');
+            let name = params.next().unwrap_or_default().trim();
+            let rejected = params.any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            });
+            (name.eq_ignore_ascii_case("gzip") || name.eq_ignore_ascii_case("x-gzip")) && !rejected
+        })
+}
+
+/// Check whether a response may be compressed
+fn compressible(response: &Response) -> bool {
+    let status = response.status();
+    let headers = response.headers();
+    let no_transform = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| directive.trim().eq_ignore_ascii_case("no-transform"));
+
+    let event_stream = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            let essence = value.split(';').next().unwrap_or_default().trim();
+            essence.eq_ignore_ascii_case("text/event-stream")
+        });
+
+    !status.is_informational()
+        && !event_stream
+        && status != StatusCode::NO_CONTENT
+        && status != StatusCode::NOT_MODIFIED
+        && !headers.contains_key(header::CONTENT_ENCODING)
+        && !headers.contains_key(header::CONTENT_RANGE)
+        && !no_transform
+}
+
+/// Add `Vary: Accept-Encoding`, unless it is already listed
+fn vary_accept_encoding(headers: &mut HeaderMap) {
+    let listed = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|name| name == "*" || name.eq_ignore_ascii_case("accept-encoding"));
+    if !listed {
+        headers.append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    }
+}
+
+/// A body with the frames read while sampling it put back in front
+struct Resumed {
+    sampled: VecDeque<Result<Frame<Bytes>, String>>,
+    rest: ResponseBody,
+}
+
+impl Body for Resumed {
+    type Data = Bytes;
+    type Error = String;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        if let Some(frame) = self.sampled.pop_front() {
+            return Poll::Ready(Some(frame));
+        }
+        Pin::new(&mut self.rest).poll_frame(cx)
+    }
+}
+
+impl<H: Handler> Handler for Compress<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let accepted = accepts_gzip(request.headers()) && request.method() != Method::HEAD;
+        let response = self.inner.handle(request).await?;
+        if !compressible(&response) {
+            return Ok(response);
+        }
+
+        let (mut parts, mut body) = response.into_parts();
+        vary_accept_encoding(&mut parts.headers);
+        if !accepted {
+            return Ok(http::Response::from_parts(parts, body));
+        }
+
+        // Sample the first frame, and whatever else is ready without waiting
+        let mut sampled = VecDeque::new();
+        let mut sample = BytesMut::new();
+        while sample.len() < self.sample_size {
+            let next = if sampled.is_empty() {
+                body.frame().await
+            } else {
+                match body.frame().now_or_never() {
+                    Some(next) => next,
+                    None => break,
+                }
+            };
+            let Some(frame) = next else {
+                break;
+            };
+            let done = match &frame {
+                Ok(frame) => {
+                    if let Some(data) = frame.data_ref() {
+                        sample.extend_from_slice(data);
+                    }
+                    frame.is_trailers()
+                }
+                Err(_) => true,
+            };
+            sampled.push_back(frame);
+            if done {
+                break;
+            }
+        }
+
+        let output = ResponseBody::new_with_buffer_size(body.buffer_size());
+        let writer = output.writer();
+        let resumed = Resumed {
+            sampled,
+            rest: body,
+        };
+
+        let encoding = ContentEncoding::Gzip;
+        let compressed = compressed_len(encoding, self.level, &sample).unwrap_or(usize::MAX);
+        let ratio = sample.len() as f64 / compressed as f64;
+        if sample.is_empty() || ratio < self.min_ratio {
+            tokio::spawn(async move {
+                pump(resumed, writer, &mut ()).await;
+            });
+            return Ok(http::Response::from_parts(parts, output));
+        }
+
+        let headers = &mut parts.headers;
+        headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.as_str()),
+        );
+        headers.remove(header::CONTENT_LENGTH);
+
+        let compressed = CompressedBody::with_level(resumed, encoding, self.level);
+        tokio::spawn(async move {
+            pump(compressed, writer, &mut ()).await;
+        });
+        Ok(http::Response::from_parts(parts, output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBody;
+    use crate::handler::Fixed;
+    use bytes::Bytes;
+    use flate2::read::GzDecoder;
+    use http_body_util::BodyExt;
+    use std::io::Read;
+    use std::time::Duration;
+
+    async fn call(body: Bytes, accept_encoding: &str) -> (HeaderMap, Bytes) {
+        let handler = Compress::new(Fixed::new(StatusCode::OK, HeaderMap::new(), body));
+        let request = http::Request::builder()
+            .header(header::ACCEPT_ENCODING, accept_encoding)
+            .body(RequestBody::new())
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        (parts.headers, body.collect().await.unwrap().to_bytes())
+    }
+
+    /// Deterministic pseudo-random bytes, which don't compress
+    fn random_bytes(len: usize) -> Bytes {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_compressible_body() {
+        let original = Bytes::from("{\"message\":\"hello\"}".repeat(2000));
+        let (headers, body) = call(original.clone(), "br, gzip;q=0.8").await;
+
+        assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(headers[header::VARY], "Accept-Encoding");
+        assert!(body.len() < original.len() / 10);
+
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&body[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, original);
+    }
+
+    #[tokio::test]
+    async fn test_incompressible_body_sent_uncompressed() {
+        let original = random_bytes(64 * 1024);
+        let (headers, body) = call(original.clone(), "gzip").await;
+
+        assert!(!headers.contains_key(header::CONTENT_ENCODING));
+        assert_eq!(headers[header::VARY], "Accept-Encoding");
+        assert_eq!(body, original);
+    }
+
+    #[tokio::test]
+    async fn test_gzip_not_accepted() {
+        let original = Bytes::from("a".repeat(4096));
+
+        let (headers, body) = call(original.clone(), "gzip;q=0, br").await;
+        assert!(!headers.contains_key(header::CONTENT_ENCODING));
+        assert_eq!(headers[header::VARY], "Accept-Encoding");
+        assert_eq!(body, original);
+    }
+
+    /// Responds with the given data and trailers
+    struct Trailing(Bytes);
+
+    impl Handler for Trailing {
+        type Error = std::io::Error;
+
+        async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+            let body = ResponseBody::buffered(self.0.clone());
+            let mut trailers = HeaderMap::new();
+            trailers.insert("x-checksum", HeaderValue::from_static("abc"));
+            body.set_trailers(trailers);
+            Ok(http::Response::new(body))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uncompressed_body_keeps_trailers() {
+        let request = http::Request::builder()
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(RequestBody::new())
+            .unwrap();
+        let handler = Compress::new(Trailing(random_bytes(32 * 1024)));
+        let response = handler.handle(request).await.unwrap();
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+
+        let collected = response.into_body().collect().await.unwrap();
+        assert_eq!(collected.trailers().unwrap()["x-checksum"], "abc");
+        assert_eq!(collected.to_bytes(), random_bytes(32 * 1024));
+    }
+
+    #[tokio::test]
+    async fn test_compressed_body_keeps_trailers() {
+        let original = Bytes::from("a".repeat(32 * 1024));
+        let request = http::Request::builder()
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(RequestBody::new())
+            .unwrap();
+        let handler = Compress::new(Trailing(original.clone()));
+        let response = handler.handle(request).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+
+        let collected = response.into_body().collect().await.unwrap();
+        assert_eq!(collected.trailers().unwrap()["x-checksum"], "abc");
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&collected.to_bytes()[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, original);
+    }
+
+    /// Streams the given data, then holds the body open until released
+    struct Paused {
+        data: Bytes,
+        release: std::sync::Mutex<Option<tokio::sync::oneshot::Receiver<()>>>,
+    }
+
+    impl Handler for Paused {
+        type Error = std::io::Error;
+
+        async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+            use tokio::io::AsyncWriteExt;
+
+            let body = ResponseBody::new();
+            let mut writer = body.writer();
+            let data = self.data.clone();
+            let release = self.release.lock().unwrap().take();
+            tokio::spawn(async move {
+                writer.write_all(&data).await.unwrap();
+                writer.flush().await.unwrap();
+                if let Some(release) = release {
+                    let _ = release.await;
+                }
+                writer.finish().await.unwrap();
+            });
+            Ok(http::Response::new(body))
+        }
+    }
+
+    /// Check the first data of a paused stream arrives before the release
+    async fn first_data_before_release(data: Bytes) -> (HeaderMap, Bytes) {
+        let (release, paused) = tokio::sync::oneshot::channel();
+        let handler = Compress::new(Paused {
+            data,
+            release: std::sync::Mutex::new(Some(paused)),
+        });
+        let request = http::Request::builder()
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(RequestBody::new())
+            .unwrap();
+
+        let response = tokio::time::timeout(Duration::from_secs(5), handler.handle(request))
+            .await
+            .expect("response isn't held back")
+            .unwrap();
+        let (parts, mut body) = response.into_parts();
+        let frame = tokio::time::timeout(Duration::from_secs(5), body.frame())
+            .await
+            .expect("first data isn't held back")
+            .unwrap()
+            .unwrap();
+        release.send(()).unwrap();
+        (parts.headers, frame.into_data().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_paused_stream_not_held_back() {
+        let original = Bytes::from("data: tick\n\n".repeat(8));
+        let (headers, first) = first_data_before_release(original.clone()).await;
+        assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+
+        // The flushed output decompresses to everything sent so far
+        let mut decoder = flate2::write::GzDecoder::new(Vec::new());
+        std::io::Write::write_all(&mut decoder, &first).unwrap();
+        std::io::Write::flush(&mut decoder).unwrap();
+        assert_eq!(decoder.get_ref()[..], original[..]);
+
+        let original = random_bytes(64);
+        let (headers, first) = first_data_before_release(original.clone()).await;
+        assert!(!headers.contains_key(header::CONTENT_ENCODING));
+        assert_eq!(first, original);
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_not_compressed() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream; charset=utf-8"),
+        );
+        let original = Bytes::from("data: tick\n\n".repeat(1000));
+        let handler = Compress::new(Fixed::new(StatusCode::OK, headers, original.clone()));
+        let request = http::Request::builder()
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(RequestBody::new())
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+        assert!(!response.headers().contains_key(header::VARY));
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, original);
+    }
+
+    #[tokio::test]
+    async fn test_vary_not_duplicated() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::VARY,
+            HeaderValue::from_static("Origin, accept-encoding"),
+        );
+        let original = Bytes::from("a".repeat(4096));
+        let handler = Compress::new(Fixed::new(StatusCode::OK, headers, original));
+        let request = http::Request::builder()
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(RequestBody::new())
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let vary: Vec<_> = response.headers().get_all(header::VARY).iter().collect();
+        assert_eq!(vary, ["Origin, accept-encoding"]);
+    }
+}
@@ -0,0 +1,377 @@
+//! Cross-origin resource sharing (CORS) wrapper.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header};
+
+use super::Handler;
+use crate::{Request, Response, ResponseBody};
+
+/// Origins allowed to make cross-origin requests
+#[derive(Clone, Debug)]
+enum AllowOrigin {
+    Any,
+    List(Vec<String>),
+}
+
+/// Handler wrapper implementing CORS
+///
+/// Preflight requests, `OPTIONS` requests with an `Origin` and an
+/// `Access-Control-Request-Method` header, are answered directly: with a
+/// `204 No Content` listing the allowed methods and headers if the origin,
+/// method and headers are all allowed, or a `403 Forbidden` otherwise. Other
+/// requests are passed to the inner handler, and responses to allowed origins
+/// get `Access-Control-Allow-Origin` and, if enabled,
+/// `Access-Control-Allow-Credentials`. Requests without an `Origin` header
+/// are passed through untouched.
+///
+/// Origins are matched exactly, ignoring ASCII case. With any origin allowed,
+/// `Access-Control-Allow-Origin: *` is sent and credentials are never
+/// allowed: reflecting the request origin instead would let any site make
+/// credentialed requests. With a list of origins, every response to a
+/// request with an `Origin` gets `Vary: Origin`, whether the origin was
+/// allowed or not, so caches keep responses for different origins apart.
+///
+/// By default no origins are allowed, the allowed methods are `GET`, `HEAD`
+/// and `POST`, and no request headers are allowed.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::{Handler, Request, Response};
+/// use http_handler::handler::Cors;
+/// use http::{Method, header};
+/// use std::time::Duration;
+///
+/// struct Api;
+///
+/// impl Handler for Api {
+///     type Error = std::convert::Infallible;
+///
+///     async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+///         let response_body = request.body().create_response();
+///         Ok(http::Response::builder().body(response_body).unwrap())
+///     }
+/// }
+///
+/// let handler = Cors::new(Api)
+///     .allow_origin("https://app.example.com")
+///     .allow_methods([Method::GET, Method::PUT])
+///     .allow_headers([header::CONTENT_TYPE])
+///     .allow_credentials(true)
+///     .max_age(Duration::from_secs(600));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Cors<H> {
+    inner: H,
+    origins: AllowOrigin,
+    methods: Vec<Method>,
+    headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl<H> Cors<H> {
+    /// Wrap a handler, allowing no origins until configured
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            origins: AllowOrigin::List(Vec::new()),
+            methods: vec![Method::GET, Method::HEAD, Method::POST],
+            headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Allow an origin, such as `https://example.com`
+    ///
+    /// Passing `*` allows any origin.
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        if origin == "*" {
+            self.origins = AllowOrigin::Any;
+        } else if let AllowOrigin::List(origins) = &mut self.origins {
+            origins.push(origin.to_string());
+        }
+        self
+    }
+
+    /// Allow any origin
+    pub fn allow_any_origin(mut self) -> Self {
+        self.origins = AllowOrigin::Any;
+        self
+    }
+
+    /// Set the methods allowed in cross-origin requests
+    pub fn allow_methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    /// Set the request headers allowed in cross-origin requests
+    pub fn allow_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.headers = headers.into_iter().collect();
+        self
+    }
+
+    /// Set whether cross-origin requests may include credentials
+    ///
+    /// Has no effect when any origin is allowed.
+    pub fn allow_credentials(mut self, enabled: bool) -> Self {
+        self.credentials = enabled;
+        self
+    }
+
+    /// Set how long browsers may cache preflight results
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    /// Check an origin against the allowed origins
+    fn origin_allowed(&self, origin: &HeaderValue) -> bool {
+        match &self.origins {
+            AllowOrigin::Any => true,
+            AllowOrigin::List(origins) => origins
+                .iter()
+                .any(|allowed| allowed.as_bytes().eq_ignore_ascii_case(origin.as_bytes())),
+        }
+    }
+
+    /// Check the method and headers a preflight asks to use
+    fn preflight_allowed(&self, headers: &HeaderMap) -> bool {
+        let method_allowed = headers
+            .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|value| Method::from_bytes(value.as_bytes()).ok())
+            .is_some_and(|method| self.methods.contains(&method));
+
+        let headers_allowed = headers
+            .get_all(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            .iter()
+            .all(|value| {
+                value.to_str().is_ok_and(|list| {
+                    list.split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .all(|name| {
+                            self.headers
+                                .iter()
+                                .any(|allowed| allowed.as_str().eq_ignore_ascii_case(name))
+                        })
+                })
+            });
+
+        method_allowed && headers_allowed
+    }
+
+    /// Add `Vary: Origin` when responses depend on the request origin
+    fn vary_origin(&self, headers: &mut HeaderMap) {
+        if let AllowOrigin::List(_) = self.origins {
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+    }
+
+    /// Add the headers sent on every response to an allowed origin
+    fn insert_origin_headers(&self, headers: &mut HeaderMap, origin: HeaderValue) {
+        match self.origins {
+            AllowOrigin::Any => {
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                    HeaderValue::from_static("*"),
+                );
+            }
+            AllowOrigin::List(_) => {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                if self.credentials {
+                    headers.insert(
+                        header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                        HeaderValue::from_static("true"),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Build the response to a preflight request
+    fn preflight(&self, request: &Request, origin: HeaderValue) -> Response {
+        let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+        self.vary_origin(response.headers_mut());
+        if !self.origin_allowed(&origin) || !self.preflight_allowed(request.headers()) {
+            *response.status_mut() = StatusCode::FORBIDDEN;
+            return response;
+        }
+
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let headers = response.headers_mut();
+        self.insert_origin_headers(headers, origin);
+
+        let methods: Vec<&str> = self.methods.iter().map(Method::as_str).collect();
+        if let Ok(value) = HeaderValue::from_str(&methods.join(", ")) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, value);
+        }
+        if !self.headers.is_empty() {
+            let names: Vec<&str> = self.headers.iter().map(HeaderName::as_str).collect();
+            if let Ok(value) = HeaderValue::from_str(&names.join(", ")) {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+            }
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+        }
+        response
+    }
+}
+
+impl<H: Handler> Handler for Cors<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let Some(origin) = request.headers().get(header::ORIGIN).cloned() else {
+            return self.inner.handle(request).await;
+        };
+
+        if request.method() == Method::OPTIONS
+            && request
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            return Ok(self.preflight(&request, origin));
+        }
+
+        let allowed = self.origin_allowed(&origin);
+        let mut response = self.inner.handle(request).await?;
+        self.vary_origin(response.headers_mut());
+        if allowed {
+            self.insert_origin_headers(response.headers_mut(), origin);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBody;
+    use crate::handler::Fixed;
+
+    fn handler() -> Cors<Fixed> {
+        Cors::new(Fixed::new(StatusCode::OK, HeaderMap::new(), "ok"))
+            .allow_origin("https://app.example.com")
+            .allow_methods([Method::GET, Method::PUT])
+            .allow_headers([header::CONTENT_TYPE, HeaderName::from_static("x-api-key")])
+            .allow_credentials(true)
+            .max_age(Duration::from_secs(600))
+    }
+
+    fn preflight(origin: &str, method: &str, headers: &str) -> Request {
+        http::Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/items/1")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, headers)
+            .body(RequestBody::new())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_preflight() {
+        let request = preflight("https://app.example.com", "PUT", "Content-Type, X-Api-Key");
+        let response = handler().handle(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "GET, PUT");
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "content-type, x-api-key"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+        assert_eq!(headers[header::VARY], "Origin");
+    }
+
+    #[tokio::test]
+    async fn test_preflight_rejects_method_and_headers() {
+        let request = preflight("https://app.example.com", "DELETE", "");
+        let response = handler().handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()[header::VARY], "Origin");
+
+        let request = preflight("https://app.example.com", "PUT", "x-other");
+        let response = handler().handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_allowed_actual_request() {
+        let request = http::Request::builder()
+            .uri("/items/1")
+            .header(header::ORIGIN, "https://app.example.com")
+            .body(RequestBody::new())
+            .unwrap();
+        let response = handler().handle(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[header::VARY], "Origin");
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_origin() {
+        let request = preflight("https://evil.example.org", "GET", "");
+        let response = handler().handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()[header::VARY], "Origin");
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+
+        let request = http::Request::builder()
+            .uri("/items/1")
+            .header(header::ORIGIN, "https://evil.example.org")
+            .body(RequestBody::new())
+            .unwrap();
+        let response = handler().handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::VARY], "Origin");
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_any_origin_never_allows_credentials() {
+        let handler = handler().allow_any_origin();
+        let request = http::Request::builder()
+            .uri("/items/1")
+            .header(header::ORIGIN, "https://anywhere.example.net")
+            .body(RequestBody::new())
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+
+        let headers = response.headers();
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+    }
+}
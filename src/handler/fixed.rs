@@ -0,0 +1,102 @@
+//! Handler that always returns the same response.
+
+use std::{convert::Infallible, sync::Arc};
+
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode};
+
+use super::Handler;
+use crate::{Request, Response, ResponseBody};
+
+/// Handler that serves a fixed response
+///
+/// Every call returns a new response with the configured status, headers and
+/// body. The request body is never read. Cloning is cheap, as the headers and
+/// body are shared.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::{HeaderMap, StatusCode};
+/// use http_handler::handler::Fixed;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert("Content-Type", "text/plain".parse().unwrap());
+///
+/// let health = Fixed::new(StatusCode::OK, headers, "ok");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Fixed {
+    status: StatusCode,
+    headers: Arc<HeaderMap>,
+    body: Bytes,
+}
+
+impl Fixed {
+    /// Create a handler serving the given status, headers and body
+    pub fn new(status: StatusCode, headers: HeaderMap, body: impl Into<Bytes>) -> Self {
+        Self {
+            status,
+            headers: Arc::new(headers),
+            body: body.into(),
+        }
+    }
+
+    /// Get the status served by this handler
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the headers served by this handler
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the body served by this handler
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+}
+
+impl Handler for Fixed {
+    type Error = Infallible;
+
+    async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+        let mut response = http::Response::new(ResponseBody::buffered(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = HeaderMap::clone(&self.headers);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBody;
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn test_fixed_response() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "text/html".parse().unwrap());
+        let handler = Fixed::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            headers,
+            "<h1>Down for maintenance</h1>",
+        );
+
+        for _ in 0..2 {
+            let request = http::Request::builder()
+                .uri("/")
+                .body(RequestBody::new())
+                .unwrap();
+
+            let response = handler.clone().handle(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(response.headers()["content-type"], "text/html");
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, "<h1>Down for maintenance</h1>");
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Request body size limiting wrapper.
+
+use http::{StatusCode, header::CONTENT_LENGTH};
+use tokio::io::AsyncWriteExt;
+
+use super::Handler;
+use crate::{Request, Response};
+
+/// Handler wrapper that limits the size of request bodies
+///
+/// The request body is limited before the inner handler sees it, so any read
+/// past the limit fails with `StreamError::BodyTooLarge`. If the request
+/// declares a `Content-Length` larger than the limit, the inner handler is
+/// skipped and a `413 Payload Too Large` response is returned instead.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::{Handler, Request, Response};
+/// use http_handler::handler::LimitBody;
+///
+/// struct Upload;
+///
+/// impl Handler for Upload {
+///     type Error = std::convert::Infallible;
+///
+///     async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+///         let response_body = request.body().create_response();
+///         Ok(http::Response::builder().body(response_body).unwrap())
+///     }
+/// }
+///
+/// // Allow uploads of up to 1 MiB
+/// let handler = LimitBody::new(Upload, 1024 * 1024);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LimitBody<H> {
+    inner: H,
+    limit: usize,
+    check_content_length: bool,
+}
+
+impl<H> LimitBody<H> {
+    /// Wrap a handler, limiting request bodies to `limit` bytes
+    pub fn new(inner: H, limit: usize) -> Self {
+        Self {
+            inner,
+            limit,
+            check_content_length: true,
+        }
+    }
+
+    /// Set whether a declared `Content-Length` over the limit is rejected
+    /// with a 413 before calling the inner handler (enabled by default)
+    pub fn check_content_length(mut self, enabled: bool) -> Self {
+        self.check_content_length = enabled;
+        self
+    }
+
+    /// Get the configured size limit
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    fn declared_too_large(&self, request: &Request) -> bool {
+        request
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .is_some_and(|length| length > self.limit as u64)
+    }
+}
+
+impl<H: Handler> Handler for LimitBody<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        if self.check_content_length && self.declared_too_large(&request) {
+            let mut body = request.body().create_response();
+            let _ = body.shutdown().await;
+
+            let mut response = http::Response::new(body);
+            *response.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+            return Ok(response);
+        }
+
+        let limit = self.limit;
+        let request = request.map(|body| body.with_limit(limit));
+        self.inner.handle(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::test_util::ReadAll;
+    use crate::{RequestBody, StreamError};
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn test_declared_length_too_large() {
+        let handler = LimitBody::new(ReadAll, 10);
+        let request = http::Request::builder()
+            .method("POST")
+            .uri("/upload")
+            .header("Content-Length", "1000")
+            .body(RequestBody::new())
+            .unwrap();
+
+        // The inner handler would block reading a body that is never written
+        let response = handler.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_body_too_large() {
+        let handler = LimitBody::new(ReadAll, 10);
+        let body = RequestBody::new();
+        let request = http::Request::builder()
+            .method("POST")
+            .uri("/upload")
+            .header("Transfer-Encoding", "chunked")
+            .body(body.clone())
+            .unwrap();
+
+        let mut writer = body;
+        tokio::spawn(async move {
+            for _ in 0..4 {
+                let _ = writer.write_all(b"chunk").await;
+            }
+            let _ = writer.shutdown().await;
+        });
+
+        let result = handler.handle(request).await;
+        assert!(matches!(result, Err(StreamError::BodyTooLarge(10))));
+    }
+
+    #[tokio::test]
+    async fn test_body_within_limit() {
+        let handler = LimitBody::new(ReadAll, 10);
+        let body = RequestBody::from_data(Bytes::from("small")).await.unwrap();
+        let request = http::Request::builder()
+            .method("POST")
+            .uri("/upload")
+            .header("Content-Length", "5")
+            .body(body)
+            .unwrap();
+
+        let response = handler.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,224 @@
+//! Handler wrappers that transform the inner handler's result.
+
+use std::{fmt, future::Future};
+
+use super::Handler;
+use crate::{Request, Response};
+
+/// Handler wrapper that applies a function to successful responses
+///
+/// Created with `Handler::map_response`. Errors from the inner handler are
+/// passed through unchanged.
+#[derive(Clone)]
+pub struct MapResponse<H, F> {
+    inner: H,
+    f: F,
+}
+
+impl<H, F> MapResponse<H, F> {
+    /// Wrap a handler, applying `f` to each response it returns
+    pub fn new(inner: H, f: F) -> Self {
+        Self { inner, f }
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+impl<H: fmt::Debug, F> fmt::Debug for MapResponse<H, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapResponse")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H, F> Handler for MapResponse<H, F>
+where
+    H: Handler,
+    F: Fn(Response) -> Response,
+{
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        self.inner.handle(request).await.map(&self.f)
+    }
+}
+
+/// Handler wrapper that applies a function to errors
+///
+/// Created with `Handler::map_err`. Successful responses are passed through
+/// unchanged.
+#[derive(Clone)]
+pub struct MapErr<H, F> {
+    inner: H,
+    f: F,
+}
+
+impl<H, F> MapErr<H, F> {
+    /// Wrap a handler, applying `f` to each error it returns
+    pub fn new(inner: H, f: F) -> Self {
+        Self { inner, f }
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+impl<H: fmt::Debug, F> fmt::Debug for MapErr<H, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapErr")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H, F, E> Handler for MapErr<H, F>
+where
+    H: Handler,
+    F: Fn(H::Error) -> E,
+{
+    type Error = E;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        self.inner.handle(request).await.map_err(&self.f)
+    }
+}
+
+/// Handler wrapper that post-processes successful responses asynchronously
+///
+/// Created with `Handler::and_then`. The function runs after the inner
+/// handler returns and may fail with the handler's error type. Errors from
+/// the inner handler are returned without calling it.
+#[derive(Clone)]
+pub struct AndThen<H, F> {
+    inner: H,
+    f: F,
+}
+
+impl<H, F> AndThen<H, F> {
+    /// Wrap a handler, passing each response it returns through `f`
+    pub fn new(inner: H, f: F) -> Self {
+        Self { inner, f }
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+impl<H: fmt::Debug, F> fmt::Debug for AndThen<H, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AndThen")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H, F, Fut> Handler for AndThen<H, F>
+where
+    H: Handler,
+    F: Fn(Response) -> Fut,
+    Fut: Future<Output = Result<Response, H::Error>>,
+{
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let response = self.inner.handle(request).await?;
+        (self.f)(response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::Fixed;
+    use crate::{RequestBody, ResponseExt};
+    use http::{HeaderMap, HeaderValue, StatusCode};
+    use std::time::Duration;
+
+    struct Fails;
+
+    impl Handler for Fails {
+        type Error = &'static str;
+
+        async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+            Err("backend unavailable")
+        }
+    }
+
+    fn request() -> Request {
+        http::Request::builder()
+            .uri("/")
+            .body(RequestBody::new())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_map_response() {
+        let handler =
+            Fixed::new(StatusCode::OK, HeaderMap::new(), "ok").map_response(|mut response| {
+                response
+                    .headers_mut()
+                    .insert("x-api-version", HeaderValue::from_static("1.0"));
+                response
+            });
+        let response = handler.handle(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-api-version"], "1.0");
+
+        // Errors skip the function
+        let handler = Fails.map_response(|_| unreachable!());
+        assert_eq!(
+            handler.handle(request()).await.unwrap_err(),
+            "backend unavailable"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_map_err() {
+        let handler = Fails.map_err(|e| format!("upstream: {}", e));
+        assert_eq!(
+            handler.handle(request()).await.unwrap_err(),
+            "upstream: backend unavailable"
+        );
+
+        // Responses pass through untouched
+        let handler = Fixed::new(StatusCode::ACCEPTED, HeaderMap::new(), "")
+            .map_err(|_: std::convert::Infallible| "never");
+        let response = handler.handle(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_and_then() {
+        let handler = Fixed::new(StatusCode::OK, HeaderMap::new(), "ok").and_then(
+            |mut response| async move {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                response.append_log("audit: served /");
+                Ok(response)
+            },
+        );
+        let response = handler.handle(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.log().unwrap().as_bytes(), b"audit: served /\n");
+
+        // The post-processor can fail the request
+        let handler = Fixed::new(StatusCode::OK, HeaderMap::new(), "ok")
+            .map_err(|_| "never")
+            .and_then(|_| async { Err("audit failed") });
+        assert_eq!(handler.handle(request()).await.unwrap_err(), "audit failed");
+
+        // Errors from the inner handler skip it
+        let handler = Fails.and_then(|_| async { unreachable!() });
+        assert_eq!(
+            handler.handle(request()).await.unwrap_err(),
+            "backend unavailable"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Request and response body mirroring.
+
+use std::{fmt, future::Future};
+
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use http_body::Frame;
+use http_body_util::StreamBody;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+use super::Handler;
+use crate::{
+    Request, RequestBody, Response, ResponseBody,
+    body::pump::{PumpEnd, Tap, pump},
+};
+
+/// A sink that mirrored body data is copied to
+pub type MirrorSink = Box<dyn AsyncWrite + Send + Unpin>;
+
+type RequestSinkFn = Box<dyn Fn(&Request) -> Option<MirrorSink> + Send + Sync>;
+type ResponseSinkFn = Box<dyn Fn(&Response) -> Option<MirrorSink> + Send + Sync>;
+
+/// What to do when writing to a mirror sink fails
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MirrorErrorPolicy {
+    /// Stop mirroring and carry on passing the body through
+    #[default]
+    Drop,
+    /// Fail the body being mirrored, so its reader gets an error
+    Fail,
+}
+
+/// Handler wrapper that copies request and response bodies to sinks
+///
+/// For each request, the sink factories decide where (and whether) to mirror
+/// the bodies. The bodies pass through unchanged. Each chunk is written to the
+/// sink before it is passed on, so a slow sink slows the body down rather than
+/// being outrun.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::handler::{Fixed, Mirror, MirrorErrorPolicy, MirrorSink};
+/// use http_handler::{HeaderMap, StatusCode};
+///
+/// let inner = Fixed::new(StatusCode::OK, HeaderMap::new(), "ok");
+/// let handler = Mirror::new(inner)
+///     .request_sink(|_request| Some(Box::new(tokio::io::sink()) as MirrorSink))
+///     .on_sink_error(MirrorErrorPolicy::Drop);
+/// ```
+pub struct Mirror<H> {
+    inner: H,
+    request_sink: Option<RequestSinkFn>,
+    response_sink: Option<ResponseSinkFn>,
+    on_sink_error: MirrorErrorPolicy,
+}
+
+impl<H> Mirror<H> {
+    /// Wrap a handler, mirroring nothing until sinks are configured
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            request_sink: None,
+            response_sink: None,
+            on_sink_error: MirrorErrorPolicy::default(),
+        }
+    }
+
+    /// Set the factory providing a sink for each request body
+    pub fn request_sink<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Request) -> Option<MirrorSink> + Send + Sync + 'static,
+    {
+        self.request_sink = Some(Box::new(f));
+        self
+    }
+
+    /// Set the factory providing a sink for each response body
+    pub fn response_sink<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Response) -> Option<MirrorSink> + Send + Sync + 'static,
+    {
+        self.response_sink = Some(Box::new(f));
+        self
+    }
+
+    /// Set what happens when writing to a sink fails (defaults to `Drop`)
+    pub fn on_sink_error(mut self, policy: MirrorErrorPolicy) -> Self {
+        self.on_sink_error = policy;
+        self
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+impl<H> fmt::Debug for Mirror<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mirror")
+            .field("request_sink", &self.request_sink.is_some())
+            .field("response_sink", &self.response_sink.is_some())
+            .field("on_sink_error", &self.on_sink_error)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Writes each chunk to the sink before the pump passes it on
+struct MirrorTap {
+    sink: Option<MirrorSink>,
+    policy: MirrorErrorPolicy,
+}
+
+impl MirrorTap {
+    /// Shut the sink down once the whole body has been mirrored
+    async fn finish(self, end: PumpEnd) {
+        if end == PumpEnd::Complete
+            && let Some(mut sink) = self.sink
+        {
+            let _ = sink.shutdown().await;
+        }
+    }
+}
+
+impl Tap for MirrorTap {
+    fn data(&mut self, data: &Bytes) -> impl Future<Output = Result<(), String>> + Send {
+        let data = data.clone();
+        async move {
+            if let Some(mirror) = self.sink.as_mut()
+                && let Err(e) = mirror.write_all(&data).await
+            {
+                match self.policy {
+                    MirrorErrorPolicy::Drop => self.sink = None,
+                    MirrorErrorPolicy::Fail => return Err(format!("mirror sink failed: {}", e)),
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+impl<H: Handler> Handler for Mirror<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let request = match self.request_sink.as_ref().and_then(|f| f(&request)) {
+            Some(sink) => {
+                let policy = self.on_sink_error;
+                request.map(|original| {
+                    let body = RequestBody::new_with_buffer_size(original.buffer_size());
+                    let writer = body.writer();
+                    let source = StreamBody::new(ReaderStream::new(original).map_ok(Frame::data));
+                    tokio::spawn(async move {
+                        let mut tap = MirrorTap {
+                            sink: Some(sink),
+                            policy,
+                        };
+                        let end = pump(source, writer, &mut tap).await;
+                        tap.finish(end).await;
+                    });
+                    body
+                })
+            }
+            None => request,
+        };
+
+        let response = self.inner.handle(request).await?;
+
+        let Some(sink) = self.response_sink.as_ref().and_then(|f| f(&response)) else {
+            return Ok(response);
+        };
+        let policy = self.on_sink_error;
+        Ok(response.map(|original| {
+            let body = ResponseBody::new_with_buffer_size(original.buffer_size());
+            let writer = body.writer();
+            tokio::spawn(async move {
+                let mut tap = MirrorTap {
+                    sink: Some(sink),
+                    policy,
+                };
+                let end = pump(original, writer, &mut tap).await;
+                tap.finish(end).await;
+            });
+            body
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+
+    /// Echoes the request body back
+    struct Echo;
+
+    impl Handler for Echo {
+        type Error = std::io::Error;
+
+        async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+            let mut body = request.into_body();
+            let mut data = Vec::new();
+            body.read_to_end(&mut data).await?;
+            Ok(http::Response::new(ResponseBody::buffered(Bytes::from(
+                data,
+            ))))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirror_request_and_response() {
+        let (request_sink, mut request_copy) = tokio::io::duplex(64 * 1024);
+        let (response_sink, mut response_copy) = tokio::io::duplex(64 * 1024);
+        let request_sink = std::sync::Mutex::new(Some(request_sink));
+        let response_sink = std::sync::Mutex::new(Some(response_sink));
+
+        let handler = Mirror::new(Echo)
+            .request_sink(move |_| {
+                let sink = request_sink.lock().unwrap().take()?;
+                Some(Box::new(sink) as MirrorSink)
+            })
+            .response_sink(move |_| {
+                let sink = response_sink.lock().unwrap().take()?;
+                Some(Box::new(sink) as MirrorSink)
+            });
+
+        let payload: Bytes = (0..20_000).map(|i| (i % 251) as u8).collect();
+        let request = http::Request::builder()
+            .method("POST")
+            .body(RequestBody::from_data_streaming(payload.clone()))
+            .unwrap();
+
+        let response = handler.handle(request).await.unwrap();
+        let echoed = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(echoed, payload);
+
+        let mut mirrored = Vec::new();
+        request_copy.read_to_end(&mut mirrored).await.unwrap();
+        assert_eq!(mirrored, payload);
+
+        let mut mirrored = Vec::new();
+        response_copy.read_to_end(&mut mirrored).await.unwrap();
+        assert_eq!(mirrored, payload);
+    }
+
+    #[tokio::test]
+    async fn test_failing_sink() {
+        for (policy, should_fail) in [
+            (MirrorErrorPolicy::Drop, false),
+            (MirrorErrorPolicy::Fail, true),
+        ] {
+            let handler = Mirror::new(Echo)
+                .request_sink(|_| {
+                    // The read side is dropped, so writes fail
+                    let (sink, _) = tokio::io::duplex(16);
+                    Some(Box::new(sink) as MirrorSink)
+                })
+                .on_sink_error(policy);
+
+            let request = http::Request::builder()
+                .body(RequestBody::from_data_sync(Bytes::from("hello")).unwrap())
+                .unwrap();
+            let result = handler.handle(request).await;
+
+            if should_fail {
+                let err = result.unwrap_err();
+                assert!(err.to_string().starts_with("mirror sink failed"), "{err}");
+            } else {
+                let body = result.unwrap().into_body().collect().await.unwrap();
+                assert_eq!(body.to_bytes(), "hello");
+            }
+        }
+    }
+
+    /// Responds with a small body carrying trailers
+    struct Trailing;
+
+    impl Handler for Trailing {
+        type Error = std::io::Error;
+
+        async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+            let body = ResponseBody::buffered(Bytes::from("hello"));
+            body.set_trailers(trailers());
+            Ok(http::Response::new(body))
+        }
+    }
+
+    fn trailers() -> http::HeaderMap {
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("x-checksum", "abc".parse().unwrap());
+        trailers
+    }
+
+    /// Streams far more than the buffers hold, reporting whether its body
+    /// was read to the end
+    struct Endless(std::sync::Mutex<Option<tokio::sync::oneshot::Sender<bool>>>);
+
+    impl Handler for Endless {
+        type Error = std::io::Error;
+
+        async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+            let finished = self.0.lock().unwrap().take();
+            let body = ResponseBody::new().on_finish(move |_, completed| {
+                if let Some(tx) = finished {
+                    let _ = tx.send(completed);
+                }
+            });
+            let mut writer = body.writer();
+            tokio::spawn(async move {
+                for _ in 0..1024 {
+                    if writer.write_all(&[b'x'; 1024]).await.is_err() {
+                        return;
+                    }
+                }
+                let _ = writer.finish().await;
+            });
+            Ok(http::Response::new(body))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_response_trailers_pass_through() {
+        let handler = Mirror::new(Trailing)
+            .response_sink(|_| Some(Box::new(tokio::io::sink()) as MirrorSink));
+
+        let request = http::Request::new(RequestBody::new());
+        let response = handler.handle(request).await.unwrap();
+        let collected = response.into_body().collect().await.unwrap();
+        assert_eq!(collected.trailers(), Some(&trailers()));
+        assert_eq!(collected.to_bytes(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_dropped_response_stops_mirroring() {
+        let (finished_tx, finished_rx) = tokio::sync::oneshot::channel();
+        let handler = Mirror::new(Endless(std::sync::Mutex::new(Some(finished_tx))))
+            .response_sink(|_| Some(Box::new(tokio::io::sink()) as MirrorSink));
+
+        let request = http::Request::new(RequestBody::new());
+        let mut body = handler.handle(request).await.unwrap().into_body();
+        body.frame().await.unwrap().unwrap();
+        drop(body);
+
+        // The mirroring task stops and drops the inner body unfinished
+        let completed = tokio::time::timeout(Duration::from_secs(5), finished_rx)
+            .await
+            .expect("mirror task should end")
+            .unwrap();
+        assert!(!completed);
+    }
+}
@@ -97,8 +97,51 @@
 //!     header_name: "X-API-Version",
 //!     header_value: "1.0",
 //! };
+//!
+//! // Or, without a wrapper struct
+//! let handler = ApiHandler.map_response(|mut response| {
+//!     response.headers_mut().insert("X-API-Version", "1.0".parse().unwrap());
+//!     response
+//! });
 //! ```
 
+mod access_log;
+mod boxed;
+mod buffer_body;
+mod catch_panic;
+mod compress;
+mod cors;
+mod fixed;
+mod limit_body;
+mod map;
+mod mirror;
+mod proxy;
+mod request_deadline;
+mod require_headers;
+mod router;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "json-schema")]
+mod validate_json;
+mod virtual_host;
+
+pub use access_log::{AccessLog, AccessLogRecord};
+pub use buffer_body::{BufferBody, BufferedBody, BufferedBodyReader};
+pub use catch_panic::CatchPanic;
+pub use compress::Compress;
+pub use cors::Cors;
+pub use fixed::Fixed;
+pub use limit_body::LimitBody;
+pub use map::{AndThen, MapErr, MapResponse};
+pub use mirror::{Mirror, MirrorErrorPolicy, MirrorSink};
+pub use proxy::Proxy;
+pub use request_deadline::RequestDeadline;
+pub use require_headers::RequireHeaders;
+pub use router::{RouteError, Router};
+#[cfg(feature = "json-schema")]
+pub use validate_json::ValidateJson;
+pub use virtual_host::VirtualHost;
+
 /// Trait for types that can handle HTTP requests and produce responses
 ///
 /// The handler trait works with duplex stream-based request and response bodies,
@@ -146,6 +189,51 @@ pub trait Handler {
         &self,
         request: http::Request<crate::RequestBody>,
     ) -> Result<http::Response<crate::ResponseBody>, Self::Error>;
+
+    /// Wrap this handler, applying `f` to each successful response
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use http_handler::{Handler, HeaderMap, HeaderValue, StatusCode};
+    /// use http_handler::handler::Fixed;
+    ///
+    /// let handler = Fixed::new(StatusCode::OK, HeaderMap::new(), "ok").map_response(|mut response| {
+    ///     response.headers_mut().insert("X-API-Version", HeaderValue::from_static("1.0"));
+    ///     response
+    /// });
+    /// ```
+    fn map_response<F>(self, f: F) -> MapResponse<Self, F>
+    where
+        Self: Sized,
+        F: Fn(http::Response<crate::ResponseBody>) -> http::Response<crate::ResponseBody>,
+    {
+        MapResponse::new(self, f)
+    }
+
+    /// Wrap this handler, applying `f` to each error
+    fn map_err<F, E>(self, f: F) -> MapErr<Self, F>
+    where
+        Self: Sized,
+        F: Fn(Self::Error) -> E,
+    {
+        MapErr::new(self, f)
+    }
+
+    /// Wrap this handler, passing each successful response through the
+    /// async function `f`
+    ///
+    /// This lets middleware do async work, such as writing an audit record,
+    /// after the inner handler returns. `f` may fail with the handler's error
+    /// type; errors from this handler are returned without calling it.
+    fn and_then<F, Fut>(self, f: F) -> AndThen<Self, F>
+    where
+        Self: Sized,
+        F: Fn(http::Response<crate::ResponseBody>) -> Fut,
+        Fut: std::future::Future<Output = Result<http::Response<crate::ResponseBody>, Self::Error>>,
+    {
+        AndThen::new(self, f)
+    }
 }
 
 #[cfg(test)]
@@ -214,6 +302,80 @@ mod tests {
         assert_eq!(&collected[..], b"Hello, world!");
     }
 
+    #[tokio::test]
+    async fn test_echo_handler_with_buffered_request() {
+        let body = crate::RequestBody::from_data(Bytes::from("Hello, world!"))
+            .await
+            .unwrap();
+        let request = http::Request::builder().uri("/echo").body(body).unwrap();
+
+        let (request, captured) = crate::test_util::buffer_request(request).await;
+        assert_eq!(&captured[..], b"Hello, world!");
+        assert_eq!(request.uri(), "/echo");
+
+        let response = EchoHandler.handle(request).await.unwrap();
+        let (response, echoed) = crate::test_util::buffer_response(response).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(echoed, captured);
+    }
+
+    /// Wrapper that records the authenticated user as request metadata
+    struct Authenticate<H>(H);
+
+    impl<H: Handler> Handler for Authenticate<H> {
+        type Error = H::Error;
+
+        async fn handle(
+            &self,
+            mut request: crate::Request,
+        ) -> Result<crate::Response, Self::Error> {
+            let user = request
+                .headers()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(str::to_string);
+            if let Some(user) = user {
+                request.meta_set("user_id", user);
+            }
+            self.0.handle(request).await
+        }
+    }
+
+    /// Handler that greets the user recorded by `Authenticate`
+    struct GreetUser;
+
+    impl Handler for GreetUser {
+        type Error = std::convert::Infallible;
+
+        async fn handle(&self, request: crate::Request) -> Result<crate::Response, Self::Error> {
+            let user = request.meta_get("user_id").unwrap_or("anonymous");
+            let body = crate::ResponseBody::from_data_sync(Bytes::from(format!("hello {}", user)))
+                .unwrap();
+            Ok(http::Response::new(body))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metadata_passed_to_inner_handler() {
+        let handler = Authenticate(GreetUser);
+
+        let request = http::Request::builder()
+            .header("authorization", "Bearer user-42")
+            .body(crate::RequestBody::new())
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello user-42");
+
+        let request = http::Request::builder()
+            .body(crate::RequestBody::new())
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello anonymous");
+    }
+
     /// Test handler that adds logging
     struct LoggingHandler;
 
@@ -0,0 +1,384 @@
+//! Reverse proxy handler.
+
+use std::fmt;
+use std::net::IpAddr;
+
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode, Uri, header};
+
+use super::Handler;
+use crate::extensions::remove_hop_by_hop;
+use crate::types::response;
+use crate::{Request, RequestExt, Response, ResponseExt};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Handler that forwards requests to an upstream server
+///
+/// The client does the actual network I/O: it is any handler sending the
+/// request it is given to the absolute URI it carries, such as an adapter
+/// around an HTTP client library. With the `hyper` feature, `HyperClient`
+/// is such an adapter. Each request is rewritten for the upstream
+/// before it is sent:
+///
+/// - The URI is resolved against the upstream base URI, appending the
+///   request path and query to the base path.
+/// - `Host` is set to the upstream authority, unless `preserve_host` is on.
+/// - Hop-by-hop headers are removed.
+/// - The client's address from `SocketInfo` is appended to
+///   `X-Forwarded-For` and `Forwarded`, the latter with the original host
+///   and protocol.
+///
+/// The original method and URI are snapshotted first, see
+/// `RequestExt::original_uri`. Request and response bodies are passed
+/// through as streams, never buffered. Hop-by-hop headers are also removed
+/// from the upstream response. If the client fails, the response is
+/// `502 Bad Gateway` with the error recorded as the exception.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::handler::{Fixed, Proxy};
+/// use http_handler::{HeaderMap, StatusCode};
+///
+/// // Stands in for a real HTTP client
+/// let client = Fixed::new(StatusCode::OK, HeaderMap::new(), "from upstream");
+/// let proxy = Proxy::new("http://backend:8080/api".parse().unwrap(), client);
+/// ```
+#[derive(Clone)]
+pub struct Proxy<C> {
+    upstream: Uri,
+    client: C,
+    preserve_host: bool,
+}
+
+impl<C> Proxy<C> {
+    /// Proxy to an absolute upstream base URI through `client`
+    pub fn new(upstream: Uri, client: C) -> Self {
+        Self {
+            upstream,
+            client,
+            preserve_host: false,
+        }
+    }
+
+    /// Set whether the request's `Host` header is sent upstream unchanged
+    pub fn preserve_host(mut self, enabled: bool) -> Self {
+        self.preserve_host = enabled;
+        self
+    }
+
+    /// Get the upstream base URI
+    pub fn upstream(&self) -> &Uri {
+        &self.upstream
+    }
+
+    /// Get a reference to the client
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    /// Resolve a request URI against the upstream base URI
+    fn target_uri(&self, uri: &Uri) -> Result<Uri, http::Error> {
+        let base = self.upstream.path().trim_end_matches('/');
+        let path_and_query = uri
+            .path_and_query()
+            .map(|path| path.as_str())
+            .filter(|path| path.starts_with('/'))
+            .unwrap_or("/");
+
+        let mut builder = Uri::builder();
+        if let Some(scheme) = self.upstream.scheme() {
+            builder = builder.scheme(scheme.clone());
+        }
+        if let Some(authority) = self.upstream.authority() {
+            builder = builder.authority(authority.clone());
+        }
+        builder
+            .path_and_query(format!("{}{}", base, path_and_query))
+            .build()
+    }
+}
+
+impl<C> fmt::Debug for Proxy<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("upstream", &self.upstream)
+            .field("preserve_host", &self.preserve_host)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Append a value to a comma-separated list header
+///
+/// All existing lines of the header are joined into one, so the value ends
+/// up last in the single list whichever line a reader looks at.
+fn append_list(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    let mut joined = Vec::new();
+    for existing in headers.get_all(&name) {
+        if !existing.is_empty() {
+            joined.extend_from_slice(existing.as_bytes());
+            joined.extend_from_slice(b", ");
+        }
+    }
+    joined.extend_from_slice(value.as_bytes());
+    if let Ok(joined) = HeaderValue::from_bytes(&joined) {
+        headers.insert(name, joined);
+    }
+}
+
+/// Format a value as an RFC 7230 quoted-string, escaping `"` and `\`
+fn quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Add the client address to `X-Forwarded-For` and `Forwarded` (RFC 7239)
+///
+/// The protocol comes from the URI scheme when the request has one, as for
+/// absolute-form requests. Otherwise it is `https` if the connection carried
+/// `TlsInfo`, and `http` if not.
+fn add_forwarded(request: &mut Request, client: IpAddr) {
+    let mut forwarded = match client {
+        IpAddr::V4(ip) => format!("for={}", ip),
+        IpAddr::V6(ip) => format!("for=\"[{}]\"", ip),
+    };
+    if let Some(host) = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+    {
+        forwarded.push_str(&format!(";host={}", quoted(host)));
+    }
+    let proto = match request.uri().scheme_str() {
+        Some(scheme) => scheme,
+        None if request.tls_info().is_some() => "https",
+        None => "http",
+    };
+    forwarded.push_str(&format!(";proto={}", proto));
+
+    let headers = request.headers_mut();
+    append_list(headers, X_FORWARDED_FOR, &client.to_string());
+    append_list(headers, header::FORWARDED, &forwarded);
+}
+
+impl<C> Handler for Proxy<C>
+where
+    C: Handler,
+    C::Error: fmt::Display,
+{
+    type Error = std::convert::Infallible;
+
+    async fn handle(&self, mut request: Request) -> Result<Response, Self::Error> {
+        let target = match self.target_uri(request.uri()) {
+            Ok(target) => target,
+            Err(e) => {
+                let mut response = response::status(StatusCode::BAD_GATEWAY);
+                response.set_exception(format!("invalid upstream URI: {}", e));
+                return Ok(response);
+            }
+        };
+
+        request.snapshot_request_line();
+        remove_hop_by_hop(request.headers_mut());
+        if let Some(remote) = request.socket_info().and_then(|info| info.remote) {
+            add_forwarded(&mut request, remote.ip());
+        }
+        if !self.preserve_host
+            && let Some(authority) = target.authority()
+            && let Ok(host) = HeaderValue::from_str(authority.as_str())
+        {
+            request.headers_mut().insert(header::HOST, host);
+        }
+        *request.uri_mut() = target;
+
+        match self.client.handle(request).await {
+            Ok(mut response) => {
+                remove_hop_by_hop(response.headers_mut());
+                Ok(response)
+            }
+            Err(e) => {
+                let mut response = response::status(StatusCode::BAD_GATEWAY);
+                response.set_exception(format!("upstream request failed: {}", e));
+                Ok(response)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RequestBody, ResponseBody, SocketInfo, TlsInfo};
+    use bytes::Bytes;
+    use http_body_util::BodyExt;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::oneshot;
+
+    /// Request parts and body as seen by the upstream
+    type Received = Arc<Mutex<Option<(http::request::Parts, Vec<u8>)>>>;
+
+    /// Upstream server streaming its response in two parts, the second only
+    /// once released
+    struct Upstream {
+        received: Received,
+        release: Mutex<Option<oneshot::Receiver<()>>>,
+    }
+
+    impl Handler for Upstream {
+        type Error = std::io::Error;
+
+        async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+            let (parts, mut body) = request.into_parts();
+            let mut data = Vec::new();
+            body.read_to_end(&mut data).await?;
+            *self.received.lock().unwrap() = Some((parts, data));
+
+            let release = self.release.lock().unwrap().take().unwrap();
+            let response_body = ResponseBody::new();
+            response_body.spawn_writer(|mut writer| async move {
+                writer.write_all(b"first,").await.unwrap();
+                writer.flush().await.unwrap();
+                let _ = release.await;
+                writer.write_all(b"second").await.unwrap();
+                writer.shutdown().await.unwrap();
+            });
+
+            Ok(http::Response::builder()
+                .status(StatusCode::CREATED)
+                .header("X-Upstream", "yes")
+                .header("Connection", "keep-alive")
+                .header("Keep-Alive", "timeout=5")
+                .body(response_body)
+                .unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proxy_streams_through() {
+        let (release_tx, release_rx) = oneshot::channel();
+        let received = Arc::new(Mutex::new(None));
+        let upstream = Upstream {
+            received: Arc::clone(&received),
+            release: Mutex::new(Some(release_rx)),
+        };
+        let proxy = Proxy::new("http://backend:8080/api/".parse().unwrap(), upstream);
+
+        let mut request = http::Request::builder()
+            .method("POST")
+            .uri("/users?page=2")
+            .header("Host", "example.com")
+            .header("X-Forwarded-For", "203.0.113.7")
+            .header("Connection", "close")
+            .body(
+                RequestBody::from_data(Bytes::from("payload"))
+                    .await
+                    .unwrap(),
+            )
+            .unwrap();
+        request.set_socket_info(SocketInfo::with_remote("192.0.2.1:5000".parse().unwrap()));
+
+        let response = proxy.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["x-upstream"], "yes");
+        assert!(!response.headers().contains_key("connection"));
+        assert!(!response.headers().contains_key("keep-alive"));
+
+        {
+            let received = received.lock().unwrap();
+            let (parts, data) = received.as_ref().unwrap();
+            assert_eq!(parts.uri, "http://backend:8080/api/users?page=2");
+            assert_eq!(parts.headers["host"], "backend:8080");
+            assert_eq!(parts.headers["x-forwarded-for"], "203.0.113.7, 192.0.2.1");
+            assert_eq!(
+                parts.headers["forwarded"],
+                "for=192.0.2.1;host=\"example.com\";proto=http"
+            );
+            assert!(!parts.headers.contains_key("connection"));
+            assert_eq!(data, b"payload");
+        }
+
+        // The first part arrives while upstream is still holding the rest
+        let mut body = response.into_body();
+        let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(first, "first,");
+        release_tx.send(()).unwrap();
+        let rest = body.collect().await.unwrap().to_bytes();
+        assert_eq!(rest, "second");
+    }
+
+    #[test]
+    fn test_forwarded_headers() {
+        let mut request = http::Request::builder()
+            .uri("https://example.com/")
+            .header("Host", "evil\";for=\"10.0.0.1")
+            .header("X-Forwarded-For", "203.0.113.7")
+            .header("X-Forwarded-For", "198.51.100.2, 198.51.100.3")
+            .body(RequestBody::new())
+            .unwrap();
+        add_forwarded(&mut request, "192.0.2.1".parse().unwrap());
+
+        let headers = request.headers();
+        assert_eq!(headers.get_all("x-forwarded-for").iter().count(), 1);
+        assert_eq!(
+            headers["x-forwarded-for"],
+            "203.0.113.7, 198.51.100.2, 198.51.100.3, 192.0.2.1"
+        );
+        assert_eq!(
+            headers["forwarded"],
+            "for=192.0.2.1;host=\"evil\\\";for=\\\"10.0.0.1\";proto=https"
+        );
+    }
+
+    #[test]
+    fn test_forwarded_proto_from_tls() {
+        let mut request = http::Request::builder()
+            .uri("/")
+            .header("Host", "example.com")
+            .body(RequestBody::new())
+            .unwrap();
+        request.set_tls_info(TlsInfo::default());
+        add_forwarded(&mut request, "2001:db8::1".parse().unwrap());
+        assert_eq!(
+            request.headers()["forwarded"],
+            "for=\"[2001:db8::1]\";host=\"example.com\";proto=https"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upstream_failure_is_bad_gateway() {
+        struct Unreachable;
+
+        impl Handler for Unreachable {
+            type Error = std::io::Error;
+
+            async fn handle(&self, _request: Request) -> Result<Response, Self::Error> {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionRefused,
+                    "connection refused",
+                ))
+            }
+        }
+
+        let proxy = Proxy::new("http://backend:8080".parse().unwrap(), Unreachable);
+        let request = http::Request::builder()
+            .uri("/")
+            .body(RequestBody::new())
+            .unwrap();
+
+        let response = proxy.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            response.exception().unwrap().message(),
+            "upstream request failed: connection refused"
+        );
+    }
+}
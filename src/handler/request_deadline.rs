@@ -0,0 +1,128 @@
+//! Request body deadline wrapper.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::StatusCode;
+use tokio::time::Instant;
+
+use super::Handler;
+use crate::{Request, Response, ResponseBody};
+
+/// Handler wrapper that limits how long reading the request body may take
+///
+/// The deadline starts when handling starts. The request body is given the
+/// deadline with `RequestBody::with_deadline`, so once it passes, the inner
+/// handler's reads fail with `StreamError::DeadlineExceeded`. If a read failed
+/// this way by the time the inner handler returns, its result is replaced with
+/// an empty `408 Request Timeout` response. This guards against clients
+/// trickling a body to hold connections open.
+///
+/// Reads in tasks spawned by the inner handler still fail past the deadline,
+/// but a response already returned can't be replaced.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::{Handler, Request, Response};
+/// use http_handler::handler::RequestDeadline;
+/// use std::time::Duration;
+///
+/// struct Upload;
+///
+/// impl Handler for Upload {
+///     type Error = std::convert::Infallible;
+///
+///     async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+///         let response_body = request.body().create_response();
+///         Ok(http::Response::builder().body(response_body).unwrap())
+///     }
+/// }
+///
+/// let handler = RequestDeadline::new(Upload, Duration::from_secs(30));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RequestDeadline<H> {
+    inner: H,
+    timeout: Duration,
+}
+
+impl<H> RequestDeadline<H> {
+    /// Wrap a handler, requiring request bodies to be read within `timeout`
+    pub fn new(inner: H, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    /// Get the configured timeout
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+impl<H: Handler> Handler for RequestDeadline<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let deadline = Instant::now() + self.timeout;
+        let request = request.map(|body| body.with_deadline(deadline));
+        let body = request.body().clone();
+
+        let result = self.inner.handle(request).await;
+        if body.deadline_exceeded() {
+            let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+            *response.status_mut() = StatusCode::REQUEST_TIMEOUT;
+            return Ok(response);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBody;
+    use crate::handler::test_util::ReadAll;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drip_fed_body_times_out() {
+        let handler = RequestDeadline::new(ReadAll, Duration::from_secs(5));
+        let body = RequestBody::new();
+        let request = http::Request::builder()
+            .method("POST")
+            .body(body.clone())
+            .unwrap();
+
+        // One byte a second, for longer than the deadline
+        let mut writer = body;
+        tokio::spawn(async move {
+            for _ in 0..10 {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                if writer.write_all(b"x").await.is_err() {
+                    return;
+                }
+            }
+            let _ = writer.shutdown().await;
+        });
+
+        let start = Instant::now();
+        let response = handler.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fast_body_passes() {
+        let handler = RequestDeadline::new(ReadAll, Duration::from_secs(5));
+        let body = RequestBody::from_data(Bytes::from("quick")).await.unwrap();
+        let request = http::Request::builder().method("POST").body(body).unwrap();
+
+        let response = handler.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
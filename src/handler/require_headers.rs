@@ -0,0 +1,195 @@
+//! Required request header wrapper.
+
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header};
+
+use super::Handler;
+use crate::{Request, Response, ResponseBody, ResponseExt};
+
+/// What a required header's value must look like
+#[derive(Clone, Debug)]
+enum Expect {
+    Any,
+    Exact(HeaderValue),
+    Prefix(Vec<u8>),
+}
+
+impl Expect {
+    fn matches(&self, value: &HeaderValue) -> bool {
+        match self {
+            Expect::Any => true,
+            Expect::Exact(expected) => value == expected,
+            Expect::Prefix(prefix) => value.as_bytes().starts_with(prefix),
+        }
+    }
+}
+
+/// Handler wrapper that rejects requests missing required headers
+///
+/// Each required header must be present, and if a value is expected, at least
+/// one of its values must match it exactly or by prefix. Values are compared
+/// byte for byte. Requests failing any requirement skip the inner handler and
+/// get a `text/plain` response listing the failing header names, which is
+/// also recorded as the response exception. The status is
+/// `401 Unauthorized` if `Authorization` is among them, and
+/// `400 Bad Request` otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::handler::{Fixed, RequireHeaders};
+/// use http_handler::{HeaderMap, HeaderName, StatusCode, header};
+///
+/// let inner = Fixed::new(StatusCode::OK, HeaderMap::new(), "ok");
+/// let handler = RequireHeaders::new(inner)
+///     .require(HeaderName::from_static("x-api-key"))
+///     .require_prefix(header::CONTENT_TYPE, "application/json");
+/// ```
+#[derive(Clone, Debug)]
+pub struct RequireHeaders<H> {
+    inner: H,
+    required: Vec<(HeaderName, Expect)>,
+}
+
+impl<H> RequireHeaders<H> {
+    /// Wrap a handler, requiring no headers until configured
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            required: Vec::new(),
+        }
+    }
+
+    /// Require a header to be present, with any value
+    pub fn require(mut self, name: HeaderName) -> Self {
+        self.required.push((name, Expect::Any));
+        self
+    }
+
+    /// Require a header to have exactly the given value
+    pub fn require_exact(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.required.push((name, Expect::Exact(value)));
+        self
+    }
+
+    /// Require a header to have a value starting with `prefix`
+    pub fn require_prefix(mut self, name: HeaderName, prefix: impl AsRef<[u8]>) -> Self {
+        self.required
+            .push((name, Expect::Prefix(prefix.as_ref().to_vec())));
+        self
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    /// Names of the required headers the request fails to satisfy
+    fn failing(&self, headers: &HeaderMap) -> Vec<&HeaderName> {
+        let mut failing: Vec<&HeaderName> = Vec::new();
+        for (name, expect) in &self.required {
+            let satisfied = headers.get_all(name).iter().any(|v| expect.matches(v));
+            if !satisfied && !failing.contains(&name) {
+                failing.push(name);
+            }
+        }
+        failing
+    }
+}
+
+/// Build the rejection listing the failing headers
+fn rejection(failing: &[&HeaderName]) -> Response {
+    let status = if failing.contains(&&header::AUTHORIZATION) {
+        StatusCode::UNAUTHORIZED
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    let names: Vec<&str> = failing.iter().map(|name| name.as_str()).collect();
+    let message = format!("missing or invalid headers: {}", names.join(", "));
+
+    let mut response = http::Response::new(ResponseBody::buffered(Bytes::from(message.clone())));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response.set_exception(message);
+    response
+}
+
+impl<H: Handler> Handler for RequireHeaders<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let failing = self.failing(request.headers());
+        if !failing.is_empty() {
+            return Ok(rejection(&failing));
+        }
+        self.inner.handle(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBuilderExt;
+    use crate::handler::Fixed;
+    use http_body_util::BodyExt;
+
+    fn handler() -> RequireHeaders<Fixed> {
+        let inner = Fixed::new(StatusCode::OK, HeaderMap::new(), "ok");
+        RequireHeaders::new(inner)
+            .require(HeaderName::from_static("x-api-key"))
+            .require_exact(
+                HeaderName::from_static("x-version"),
+                HeaderValue::from_static("2"),
+            )
+            .require_prefix(header::CONTENT_TYPE, "application/json")
+    }
+
+    #[tokio::test]
+    async fn test_all_present_passes_through() {
+        let request = http::Request::builder()
+            .header("X-Api-Key", "secret")
+            .header("X-Version", "2")
+            .header("Content-Type", "application/json; charset=utf-8")
+            .buffered_body("{}")
+            .unwrap();
+
+        let response = handler().handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "ok");
+    }
+
+    #[tokio::test]
+    async fn test_missing_headers_rejected() {
+        let request = http::Request::builder()
+            .header("X-Version", "3")
+            .header("Content-Type", "application/json")
+            .buffered_body("{}")
+            .unwrap();
+
+        let response = handler().handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.exception().unwrap().message(),
+            "missing or invalid headers: x-api-key, x-version"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "missing or invalid headers: x-api-key, x-version");
+    }
+
+    #[tokio::test]
+    async fn test_missing_authorization_is_unauthorized() {
+        let inner = Fixed::new(StatusCode::OK, HeaderMap::new(), "ok");
+        let handler = RequireHeaders::new(inner).require_prefix(header::AUTHORIZATION, "Bearer ");
+
+        let request = http::Request::builder()
+            .header("Authorization", "Basic Zm9vOmJhcg==")
+            .buffered_body("")
+            .unwrap();
+        let response = handler.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,570 @@
+//! Path-based request routing.
+
+use std::fmt;
+
+use bytes::Bytes;
+use http::{Method, StatusCode, header::ALLOW};
+use percent_encoding::percent_decode_str;
+
+use super::Handler;
+use super::boxed::BoxHandler;
+use crate::types::response;
+use crate::{BoxError, PathParams, Request, RequestExt, Response, ResponseBody};
+
+/// A single segment of a route pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Static(String),
+    Param(String),
+}
+
+/// Parsed route pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Pattern {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    fn parse(source: &str) -> Result<Self, RouteError> {
+        if !source.starts_with('/') {
+            return Err(RouteError::Invalid(source.to_string()));
+        }
+
+        let mut segments = Vec::new();
+        for part in source.split('/').filter(|part| !part.is_empty()) {
+            let segment = match part.strip_prefix(':') {
+                Some("") => return Err(RouteError::Invalid(source.to_string())),
+                Some(name) => {
+                    if segments.contains(&Segment::Param(name.to_string())) {
+                        return Err(RouteError::Invalid(source.to_string()));
+                    }
+                    Segment::Param(name.to_string())
+                }
+                None => Segment::Static(part.to_string()),
+            };
+            segments.push(segment);
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            segments,
+        })
+    }
+
+    /// Two patterns conflict if they have the same shape, so neither is more
+    /// specific than the other for any path both match.
+    fn conflicts_with(&self, other: &Pattern) -> bool {
+        self.segments.len() == other.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(&other.segments)
+                .all(|pair| match pair {
+                    (Segment::Static(a), Segment::Static(b)) => a == b,
+                    (Segment::Param(_), Segment::Param(_)) => true,
+                    _ => false,
+                })
+    }
+
+    /// Match a path, capturing percent-decoded parameters.
+    fn matches(&self, path: &[&str]) -> Option<PathParams> {
+        if path.len() != self.segments.len() {
+            return None;
+        }
+
+        let mut params = PathParams::new();
+        for (segment, part) in self.segments.iter().zip(path) {
+            match segment {
+                Segment::Static(expected) => {
+                    if expected != part {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    let value = percent_decode_str(part).decode_utf8().ok()?;
+                    params.push(name.clone(), value);
+                }
+            }
+        }
+        Some(params)
+    }
+
+    /// Ordering key preferring static segments, from left to right.
+    fn specificity(&self) -> Vec<bool> {
+        self.segments
+            .iter()
+            .map(|segment| matches!(segment, Segment::Param(_)))
+            .collect()
+    }
+}
+
+/// Error returned when a route can't be registered
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RouteError {
+    /// The pattern is malformed: it must start with `/`, and parameters must
+    /// be named and unique
+    Invalid(String),
+    /// The pattern matches exactly the same paths as an existing route that
+    /// handles the same method, or any method
+    Conflict {
+        /// The pattern being added
+        pattern: String,
+        /// The existing pattern it conflicts with
+        existing: String,
+    },
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::Invalid(pattern) => write!(f, "Invalid route pattern: {}", pattern),
+            RouteError::Conflict { pattern, existing } => write!(
+                f,
+                "Route {} conflicts with existing route {}",
+                pattern, existing
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+/// Handlers for a route, either one for every method or one per method
+enum RouteHandlers {
+    Any(BoxHandler),
+    Methods(Vec<(Method, BoxHandler)>),
+}
+
+struct Route {
+    pattern: Pattern,
+    handlers: RouteHandlers,
+}
+
+impl Route {
+    /// Methods the route handles, in registration order, plus `HEAD` after
+    /// `GET` and `OPTIONS`, which the router answers itself
+    fn allowed_methods(&self) -> Vec<Method> {
+        let RouteHandlers::Methods(methods) = &self.handlers else {
+            return Vec::new();
+        };
+        let mut allowed: Vec<Method> = methods.iter().map(|(method, _)| method.clone()).collect();
+        if let Some(get) = allowed.iter().position(|method| method == Method::GET)
+            && !allowed.contains(&Method::HEAD)
+        {
+            allowed.insert(get + 1, Method::HEAD);
+        }
+        if !allowed.contains(&Method::OPTIONS) {
+            allowed.push(Method::OPTIONS);
+        }
+        allowed
+    }
+}
+
+/// Handler that dispatches requests to other handlers by path
+///
+/// Route patterns are made of `/`-separated segments. Segments starting with
+/// `:` capture the matching part of the path as a named parameter, which the
+/// routed handler can read with `RequestExt::path_params`. Captured values are
+/// percent-decoded.
+///
+/// When several routes match a path, the one with static segments furthest to
+/// the left wins, so `/users/me` takes precedence over `/users/:id`. Requests
+/// that match no route go to the fallback handler, or get an empty 404.
+///
+/// Routes added with `route` handle every method. Routes added with
+/// `route_method` handle only their method, and several methods can share a
+/// pattern. For those, a `HEAD` request the route doesn't handle itself goes
+/// to its `GET` handler, with the response body dropped. An `OPTIONS` request
+/// the route doesn't handle itself gets an empty 204, and any other unhandled
+/// method an empty 405, both with an `Allow` header listing the route's
+/// methods.
+///
+/// The most specific matching path is chosen before the method is looked at,
+/// so a request whose method that route doesn't handle gets the 405 even if a
+/// less specific route would handle it.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::{Handler, Request, RequestExt, Response};
+/// use http_handler::handler::Router;
+///
+/// struct ShowPost;
+///
+/// impl Handler for ShowPost {
+///     type Error = std::convert::Infallible;
+///
+///     async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+///         let params = request.path_params().unwrap();
+///         let _user = params.get("id");
+///         let _post = params.get("post_id");
+///         let response_body = request.body().create_response();
+///         Ok(http::Response::builder().body(response_body).unwrap())
+///     }
+/// }
+///
+/// let router = Router::new()
+///     .route("/users/:id/posts/:post_id", ShowPost)
+///     .route_method(http::Method::GET, "/posts/:post_id", ShowPost);
+/// ```
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Option<BoxHandler>,
+}
+
+impl Router {
+    /// Create a router with no routes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a route
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is invalid or conflicts with an existing route.
+    /// Use `try_route` to handle these errors instead.
+    pub fn route<H>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Handler + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        match self.try_route(pattern, handler) {
+            Ok(router) => router,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Add a route, failing if the pattern is invalid or conflicts with an
+    /// existing route
+    pub fn try_route<H>(mut self, pattern: &str, handler: H) -> Result<Self, RouteError>
+    where
+        H: Handler + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let pattern = Pattern::parse(pattern)?;
+        if let Some(existing) = self
+            .routes
+            .iter()
+            .find(|route| route.pattern.conflicts_with(&pattern))
+        {
+            return Err(RouteError::Conflict {
+                pattern: pattern.source,
+                existing: existing.pattern.source.clone(),
+            });
+        }
+
+        self.routes.push(Route {
+            pattern,
+            handlers: RouteHandlers::Any(BoxHandler::new(handler)),
+        });
+        Ok(self)
+    }
+
+    /// Add a route handling a single method
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is invalid or conflicts with an existing route.
+    /// Use `try_route_method` to handle these errors instead.
+    pub fn route_method<H>(self, method: Method, pattern: &str, handler: H) -> Self
+    where
+        H: Handler + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        match self.try_route_method(method, pattern, handler) {
+            Ok(router) => router,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Add a route handling a single method, failing if the pattern is invalid
+    /// or conflicts with an existing route
+    ///
+    /// Routes for other methods may share the pattern, as long as they name
+    /// their parameters the same way.
+    pub fn try_route_method<H>(
+        mut self,
+        method: Method,
+        pattern: &str,
+        handler: H,
+    ) -> Result<Self, RouteError>
+    where
+        H: Handler + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let pattern = Pattern::parse(pattern)?;
+        let Some(existing) = self
+            .routes
+            .iter_mut()
+            .find(|route| route.pattern.conflicts_with(&pattern))
+        else {
+            self.routes.push(Route {
+                pattern,
+                handlers: RouteHandlers::Methods(vec![(method, BoxHandler::new(handler))]),
+            });
+            return Ok(self);
+        };
+
+        match &mut existing.handlers {
+            RouteHandlers::Methods(methods)
+                if existing.pattern.segments == pattern.segments
+                    && !methods.iter().any(|(m, _)| *m == method) =>
+            {
+                methods.push((method, BoxHandler::new(handler)));
+                Ok(self)
+            }
+            _ => Err(RouteError::Conflict {
+                pattern: pattern.source,
+                existing: existing.pattern.source.clone(),
+            }),
+        }
+    }
+
+    /// Set the handler for requests that match no route
+    pub fn fallback<H>(mut self, handler: H) -> Self
+    where
+        H: Handler + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.fallback = Some(BoxHandler::new(handler));
+        self
+    }
+
+    /// Find the most specific route matching a path
+    fn find(&self, path: &str) -> Option<(&Route, PathParams)> {
+        let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+        self.routes
+            .iter()
+            .filter_map(|route| route.pattern.matches(&parts).map(|params| (route, params)))
+            .min_by_key(|(route, _)| route.pattern.specificity())
+    }
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let patterns: Vec<&str> = self
+            .routes
+            .iter()
+            .map(|route| route.pattern.source.as_str())
+            .collect();
+        f.debug_struct("Router")
+            .field("routes", &patterns)
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+impl Handler for Router {
+    type Error = BoxError;
+
+    async fn handle(&self, mut request: Request) -> Result<Response, Self::Error> {
+        if let Some((route, params)) = self.find(request.uri().path()) {
+            let methods = match &route.handlers {
+                RouteHandlers::Any(handler) => {
+                    request.set_path_params(params);
+                    return handler.handle(request).await;
+                }
+                RouteHandlers::Methods(methods) => methods,
+            };
+
+            if let Some((_, handler)) = methods.iter().find(|(m, _)| m == request.method()) {
+                request.set_path_params(params);
+                return handler.handle(request).await;
+            }
+
+            if request.method() == Method::HEAD
+                && let Some((_, handler)) = methods.iter().find(|(m, _)| m == Method::GET)
+            {
+                request.set_path_params(params);
+                let response = handler.handle(request).await?;
+                let (parts, _) = response.into_parts();
+                return Ok(http::Response::from_parts(
+                    parts,
+                    ResponseBody::buffered(Bytes::new()),
+                ));
+            }
+
+            let allowed = route.allowed_methods();
+            if request.method() == Method::OPTIONS {
+                let mut response = response::no_content();
+                response
+                    .headers_mut()
+                    .insert(ALLOW, response::allow_header(&allowed));
+                return Ok(response);
+            }
+            return Ok(response::method_not_allowed(&allowed));
+        }
+
+        if let Some(fallback) = &self.fallback {
+            return fallback.handle(request).await;
+        }
+
+        let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBody;
+    use http_body_util::BodyExt;
+    use std::convert::Infallible;
+
+    /// Responds with the route name and captured parameters
+    struct Named(&'static str);
+
+    impl Handler for Named {
+        type Error = Infallible;
+
+        async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+            let mut text = self.0.to_string();
+            for (name, value) in request.path_params().into_iter().flat_map(|p| p.iter()) {
+                text.push_str(&format!(" {}={}", name, value));
+            }
+            Ok(http::Response::new(ResponseBody::buffered(Bytes::from(
+                text,
+            ))))
+        }
+    }
+
+    async fn call(router: &Router, uri: &str) -> (StatusCode, Bytes) {
+        let request = http::Request::builder()
+            .uri(uri)
+            .body(RequestBody::new())
+            .unwrap();
+        let response = router.handle(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_two_parameter_route() {
+        let router = Router::new()
+            .route("/users/:id", Named("user"))
+            .route("/users/:id/posts/:post_id", Named("post"));
+
+        let (status, body) = call(&router, "/users/42/posts/7?draft=1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "post id=42 post_id=7");
+
+        let (_, body) = call(&router, "/users/42").await;
+        assert_eq!(body, "user id=42");
+    }
+
+    #[tokio::test]
+    async fn test_non_matching_path() {
+        let router = Router::new().route("/users/:id", Named("user"));
+
+        let (status, body) = call(&router, "/users/42/extra").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.is_empty());
+
+        let router = router.fallback(Named("fallback"));
+        let (status, body) = call(&router, "/nope").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "fallback");
+    }
+
+    #[tokio::test]
+    async fn test_percent_decoded_captures() {
+        let router = Router::new().route("/files/:name", Named("file"));
+
+        let (_, body) = call(&router, "/files/hello%20w%C3%B6rld.txt").await;
+        assert_eq!(body, "file name=hello wörld.txt");
+    }
+
+    #[tokio::test]
+    async fn test_static_segments_take_precedence() {
+        let router = Router::new()
+            .route("/users/:id", Named("user"))
+            .route("/users/me", Named("me"));
+
+        let (_, body) = call(&router, "/users/me").await;
+        assert_eq!(body, "me");
+    }
+
+    #[tokio::test]
+    async fn test_method_routes() {
+        let router = Router::new()
+            .route_method(Method::GET, "/users/:id", Named("show"))
+            .route_method(Method::DELETE, "/users/:id", Named("delete"));
+
+        let request = |method: Method| {
+            http::Request::builder()
+                .method(method)
+                .uri("/users/42")
+                .body(RequestBody::new())
+                .unwrap()
+        };
+
+        let response = router.handle(request(Method::DELETE)).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "delete id=42");
+
+        let response = router.handle(request(Method::OPTIONS)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[ALLOW], "GET, HEAD, DELETE, OPTIONS");
+
+        let response = router.handle(request(Method::POST)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "GET, HEAD, DELETE, OPTIONS");
+    }
+
+    #[tokio::test]
+    async fn test_head_served_by_get_route() {
+        let router = Router::new().route_method(Method::GET, "/users/:id", Named("show"));
+        let request = http::Request::builder()
+            .method(Method::HEAD)
+            .uri("/users/42")
+            .body(RequestBody::new())
+            .unwrap();
+
+        let response = router.handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_conflicting_method_routes() {
+        let router = Router::new().route_method(Method::GET, "/users/:id", Named("a"));
+        let router = router
+            .try_route_method(Method::POST, "/users/:id", Named("b"))
+            .unwrap();
+
+        for (method, pattern) in [(Method::GET, "/users/:id"), (Method::PUT, "/users/:name")] {
+            let result = Router::new()
+                .route_method(Method::GET, "/users/:id", Named("a"))
+                .try_route_method(method, pattern, Named("b"));
+            assert!(matches!(result, Err(RouteError::Conflict { .. })));
+        }
+
+        assert!(matches!(
+            router.try_route("/users/:id", Named("c")),
+            Err(RouteError::Conflict { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_ambiguous_routes() {
+        let result = Router::new()
+            .route("/users/:id", Named("a"))
+            .try_route("/users/:name", Named("b"));
+        assert!(matches!(result, Err(RouteError::Conflict { .. })));
+
+        assert!(matches!(
+            Router::new().try_route("/users/:id/:id", Named("a")),
+            Err(RouteError::Invalid(_))
+        ));
+        assert!(matches!(
+            Router::new().try_route("users", Named("a")),
+            Err(RouteError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,23 @@
+//! Handlers shared by the handler tests.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::Handler;
+use crate::{Request, Response, StreamError};
+
+/// Handler that reads the whole body and reports how that went
+pub(crate) struct ReadAll;
+
+impl Handler for ReadAll {
+    type Error = StreamError;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let (_, mut body) = request.into_parts();
+        let mut data = Vec::new();
+        body.read_to_end(&mut data).await?;
+
+        let mut response_body = body.create_response();
+        response_body.shutdown().await?;
+        Ok(http::Response::new(response_body))
+    }
+}
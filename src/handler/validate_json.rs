@@ -0,0 +1,270 @@
+//! JSON Schema request validation wrapper.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, StatusCode, header};
+use jsonschema::{ValidationError, Validator};
+use serde_json::{Value, json};
+
+use super::Handler;
+use crate::{Request, RequestBody, Response, ResponseBody, ResponseExt, StreamError};
+
+/// Default largest body buffered for validation
+const DEFAULT_MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// Handler wrapper that validates JSON request bodies against a JSON Schema
+///
+/// Requests with a JSON content type (`application/json` or any `+json` type)
+/// have their body buffered and validated before reaching the inner handler.
+/// Valid requests are forwarded with the buffered body. Invalid ones, and
+/// bodies that can't be read or parsed, get a `400 Bad Request` with a JSON
+/// body of the form `{"errors": ["<path>: <message>", ...]}`, and the same
+/// errors recorded as the response exception. Bodies larger than
+/// `max_body_size` (4MB by default) get a `413 Payload Too Large` instead.
+/// Requests with other content types are passed through untouched.
+///
+/// Requires the `json-schema` feature.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::handler::{Fixed, ValidateJson};
+/// use http::{HeaderMap, StatusCode};
+/// use serde_json::json;
+///
+/// let create_user = Fixed::new(StatusCode::CREATED, HeaderMap::new(), "");
+/// let schema = json!({
+///     "type": "object",
+///     "properties": { "name": { "type": "string" } },
+///     "required": ["name"]
+/// });
+/// let handler = ValidateJson::new(create_user, &schema).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ValidateJson<H> {
+    inner: H,
+    validator: Arc<Validator>,
+    max_body_size: usize,
+}
+
+impl<H> ValidateJson<H> {
+    /// Wrap a handler, compiling the schema its requests are validated against
+    ///
+    /// Fails if the schema itself is invalid.
+    pub fn new(inner: H, schema: &Value) -> Result<Self, Box<ValidationError<'static>>> {
+        Ok(Self {
+            inner,
+            validator: Arc::new(jsonschema::validator_for(schema).map_err(Box::new)?),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        })
+    }
+
+    /// Set the largest body, in bytes, buffered for validation
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// Get a reference to the inner handler
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    /// Validate a body, returning the errors found
+    fn errors(&self, body: &[u8]) -> Vec<String> {
+        let value: Value = match serde_json::from_slice(body) {
+            Ok(value) => value,
+            Err(e) => return vec![format!("invalid JSON: {}", e)],
+        };
+        self.validator
+            .iter_errors(&value)
+            .map(|error| {
+                let path = error.instance_path.as_str();
+                let path = if path.is_empty() { "/" } else { path };
+                format!("{}: {}", path, error)
+            })
+            .collect()
+    }
+}
+
+/// Check whether a content type is JSON
+fn is_json(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+/// Build a 413 response for a body over `max` bytes
+fn payload_too_large(max: usize) -> Response {
+    let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+    *response.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+    response.set_exception(format!("request body exceeds {} bytes", max));
+    response
+}
+
+/// Build a 400 response listing the errors
+fn bad_request(errors: Vec<String>) -> Response {
+    let message = format!(
+        "request body failed schema validation: {}",
+        errors.join("; ")
+    );
+    let body = json!({ "errors": errors }).to_string();
+    let mut response = http::Response::new(ResponseBody::buffered(Bytes::from(body)));
+    *response.status_mut() = StatusCode::BAD_REQUEST;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response.set_exception(message);
+    response
+}
+
+impl<H: Handler> Handler for ValidateJson<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        if !is_json(request.headers()) {
+            return self.inner.handle(request).await;
+        }
+
+        let (parts, body) = request.into_parts();
+        let data = match body.collect_with_limit(self.max_body_size).await {
+            Ok(data) => data,
+            Err(StreamError::BodyTooLarge(max)) => return Ok(payload_too_large(max)),
+            Err(e) => return Ok(bad_request(vec![format!("failed to read body: {}", e)])),
+        };
+
+        let errors = self.errors(&data);
+        if !errors.is_empty() {
+            return Ok(bad_request(errors));
+        }
+
+        let body = RequestBody::buffered(data);
+        self.inner.handle(Request::from_parts(parts, body)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBuilderExt;
+    use http_body_util::BodyExt;
+    use tokio::io::AsyncReadExt;
+
+    /// Echoes the request body back
+    struct Echo;
+
+    impl Handler for Echo {
+        type Error = std::io::Error;
+
+        async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+            let (_, mut body) = request.into_parts();
+            let mut data = Vec::new();
+            body.read_to_end(&mut data).await?;
+            Ok(http::Response::new(ResponseBody::buffered(Bytes::from(
+                data,
+            ))))
+        }
+    }
+
+    fn handler() -> ValidateJson<Echo> {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "age": { "type": "integer", "minimum": 0 }
+            },
+            "required": ["name"]
+        });
+        ValidateJson::new(Echo, &schema).unwrap()
+    }
+
+    fn request(body: &str) -> Request {
+        http::Request::builder()
+            .method("POST")
+            .header(header::CONTENT_TYPE, "application/json; charset=utf-8")
+            .buffered_body(body.to_string())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_valid_body_passes_through() {
+        let body = r#"{"name":"Ada","age":36}"#;
+        let response = handler().handle(request(body)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let echoed = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(echoed, body);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_body_rejected() {
+        let response = handler().handle(request(r#"{"age":-1}"#)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let exception = response.exception().unwrap();
+        assert!(
+            exception
+                .message()
+                .starts_with("request body failed schema validation: ")
+        );
+        assert!(exception.message().contains("/age: "));
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let errors: Vec<&str> = body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error.as_str().unwrap())
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert!(
+            errors
+                .iter()
+                .any(|error| error.starts_with("/: ") && error.contains("name"))
+        );
+        assert!(errors.iter().any(|error| error.starts_with("/age: ")));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json_rejected() {
+        let response = handler().handle(request("{not json")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_rejected() {
+        let body = format!(r#"{{"name":"{}"}}"#, "a".repeat(64));
+        let handler = handler().max_body_size(32);
+
+        let response = handler.handle(request(&body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            response.exception().unwrap().message(),
+            "request body exceeds 32 bytes"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_other_content_types_pass_through() {
+        let request = http::Request::builder()
+            .method("POST")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .buffered_body("{not json")
+            .unwrap();
+        let response = handler().handle(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,287 @@
+//! Host-based request routing.
+
+use std::fmt;
+
+use bytes::Bytes;
+use http::{StatusCode, header};
+
+use super::Handler;
+use super::boxed::BoxHandler;
+use crate::{BoxError, Request, Response, ResponseBody};
+
+/// Host name a handler is registered for
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum HostPattern {
+    Exact(String),
+    /// Any subdomain of the stored suffix, which includes the leading dot
+    Wildcard(String),
+}
+
+impl HostPattern {
+    fn parse(source: &str) -> Self {
+        let host = normalize(source);
+        match host.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') => HostPattern::Wildcard(suffix.to_string()),
+            _ => HostPattern::Exact(host),
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            HostPattern::Exact(expected) => expected == host,
+            HostPattern::Wildcard(suffix) => host.len() > suffix.len() && host.ends_with(suffix),
+        }
+    }
+}
+
+/// Lowercase a host name and drop any trailing dot
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Strip the port from a `Host` value, returning `None` if it's malformed
+fn strip_port(authority: &str) -> Option<&str> {
+    // IPv6 literals are bracketed, and contain colons of their own
+    let (host, rest) = if authority.starts_with('[') {
+        let end = authority.find(']')? + 1;
+        authority.split_at(end)
+    } else {
+        match authority.find(':') {
+            Some(colon) => authority.split_at(colon),
+            None => (authority, ""),
+        }
+    };
+
+    let port_ok = match rest.strip_prefix(':') {
+        Some(port) => port.bytes().all(|b| b.is_ascii_digit()),
+        None => rest.is_empty(),
+    };
+    let host_ok = !host.is_empty()
+        && host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._[]:".contains(&b));
+
+    (port_ok && host_ok).then_some(host)
+}
+
+/// Get the normalized host a request is for
+///
+/// Uses the `Host` header, falling back to the URI authority, which is where
+/// HTTP/2 requests carry `:authority`.
+fn request_host(request: &Request) -> Option<String> {
+    let authority = match request.headers().get(header::HOST) {
+        Some(value) => value.to_str().ok()?,
+        None => request.uri().authority()?.as_str(),
+    };
+    strip_port(authority).map(normalize)
+}
+
+struct Host {
+    pattern: HostPattern,
+    handler: BoxHandler,
+}
+
+/// Handler that dispatches requests to other handlers by host name
+///
+/// Hosts are matched against the request's `Host` header, or the URI
+/// authority if there is no `Host` header. Matching ignores case, the port,
+/// and any trailing dot. A pattern starting with `*.` matches any subdomain,
+/// so `*.example.com` matches `api.example.com` but not `example.com` itself.
+/// Exact hosts take precedence over wildcards, and longer wildcards over
+/// shorter ones.
+///
+/// Requests for an unknown host go to the fallback handler, or get an empty
+/// 404. Requests with a missing or malformed host also go to the fallback
+/// handler, or get an empty 400.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::handler::{Fixed, VirtualHost};
+/// use http::{HeaderMap, StatusCode};
+///
+/// let ok = |body: &'static str| Fixed::new(StatusCode::OK, HeaderMap::new(), body);
+///
+/// let hosts = VirtualHost::new()
+///     .host("example.com", ok("main site"))
+///     .host("*.example.com", ok("tenant"))
+///     .fallback(ok("unknown host"));
+/// ```
+#[derive(Default)]
+pub struct VirtualHost {
+    hosts: Vec<Host>,
+    fallback: Option<BoxHandler>,
+}
+
+impl VirtualHost {
+    /// Create a registry with no hosts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a handler for a host name or `*.` wildcard
+    ///
+    /// Registering the same host again replaces the earlier handler.
+    pub fn host<H>(mut self, host: &str, handler: H) -> Self
+    where
+        H: Handler + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let pattern = HostPattern::parse(host);
+        let handler = BoxHandler::new(handler);
+        match self.hosts.iter_mut().find(|host| host.pattern == pattern) {
+            Some(existing) => existing.handler = handler,
+            None => self.hosts.push(Host { pattern, handler }),
+        }
+        self
+    }
+
+    /// Set the default handler, for requests that match no host
+    pub fn fallback<H>(mut self, handler: H) -> Self
+    where
+        H: Handler + Send + Sync + 'static,
+        H::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.fallback = Some(BoxHandler::new(handler));
+        self
+    }
+
+    /// Find the most specific handler for a normalized host
+    fn find(&self, host: &str) -> Option<&BoxHandler> {
+        self.hosts
+            .iter()
+            .filter(|entry| entry.pattern.matches(host))
+            .max_by_key(|entry| match &entry.pattern {
+                HostPattern::Exact(_) => usize::MAX,
+                HostPattern::Wildcard(suffix) => suffix.len(),
+            })
+            .map(|entry| &entry.handler)
+    }
+}
+
+impl fmt::Debug for VirtualHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hosts: Vec<&HostPattern> = self.hosts.iter().map(|host| &host.pattern).collect();
+        f.debug_struct("VirtualHost")
+            .field("hosts", &hosts)
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+impl Handler for VirtualHost {
+    type Error = BoxError;
+
+    async fn handle(&self, request: Request) -> Result<Response, Self::Error> {
+        let host = request_host(&request);
+        if let Some(handler) = host.as_deref().and_then(|host| self.find(host)) {
+            return handler.handle(request).await;
+        }
+
+        if let Some(fallback) = &self.fallback {
+            return fallback.handle(request).await;
+        }
+
+        let status = match host {
+            Some(_) => StatusCode::NOT_FOUND,
+            None => StatusCode::BAD_REQUEST,
+        };
+        let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+        *response.status_mut() = status;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBody;
+    use crate::handler::Fixed;
+    use http::HeaderMap;
+    use http_body_util::BodyExt;
+
+    fn named(name: &'static str) -> Fixed {
+        Fixed::new(StatusCode::OK, HeaderMap::new(), name)
+    }
+
+    async fn call(hosts: &VirtualHost, host: Option<&str>) -> (StatusCode, Bytes) {
+        let mut builder = http::Request::builder().uri("/");
+        if let Some(host) = host {
+            builder = builder.header(header::HOST, host);
+        }
+        let request = builder.body(RequestBody::new()).unwrap();
+        let response = hosts.handle(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_exact_host() {
+        let hosts = VirtualHost::new()
+            .host("example.com", named("main"))
+            .host("other.org", named("other"));
+
+        assert_eq!(call(&hosts, Some("example.com")).await.1, "main");
+        assert_eq!(call(&hosts, Some("Example.COM:8080")).await.1, "main");
+        assert_eq!(call(&hosts, Some("other.org.")).await.1, "other");
+
+        let (status, _) = call(&hosts, Some("unknown.net")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_subdomain() {
+        let hosts = VirtualHost::new()
+            .host("*.example.com", named("tenant"))
+            .host("*.eu.example.com", named("eu tenant"))
+            .host("admin.example.com", named("admin"));
+
+        assert_eq!(call(&hosts, Some("acme.example.com")).await.1, "tenant");
+        assert_eq!(call(&hosts, Some("a.b.example.com")).await.1, "tenant");
+        assert_eq!(
+            call(&hosts, Some("acme.eu.example.com")).await.1,
+            "eu tenant"
+        );
+        assert_eq!(call(&hosts, Some("ADMIN.example.com")).await.1, "admin");
+
+        // The wildcard doesn't cover the bare domain
+        let (status, _) = call(&hosts, Some("example.com")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_default_fallback() {
+        let hosts = VirtualHost::new()
+            .host("example.com", named("main"))
+            .fallback(named("default"));
+
+        assert_eq!(call(&hosts, Some("elsewhere.com")).await.1, "default");
+        assert_eq!(call(&hosts, None).await.1, "default");
+        assert_eq!(call(&hosts, Some("bad host")).await.1, "default");
+    }
+
+    #[tokio::test]
+    async fn test_missing_host_without_default() {
+        let hosts = VirtualHost::new().host("example.com", named("main"));
+
+        let (status, _) = call(&hosts, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = call(&hosts, Some("example.com:port")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_authority_without_host_header() {
+        let hosts = VirtualHost::new().host("[::1]", named("loopback"));
+
+        let request = http::Request::builder()
+            .uri("https://[::1]:8443/")
+            .body(RequestBody::new())
+            .unwrap();
+        let response = hosts.handle(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "loopback");
+    }
+}
@@ -9,6 +9,7 @@ pub use http::*;
 
 /// Body types for HTTP requests and responses with streaming support
 pub mod body;
+pub mod cookie;
 pub mod extensions;
 pub mod handler;
 pub mod types;
@@ -16,14 +17,32 @@ pub mod types;
 /// WebSocket frame codec for RFC 6455 compliant framing
 pub mod websocket;
 
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+
+/// HTTP client adapter for proxying requests upstream with hyper
+#[cfg(feature = "hyper")]
+pub mod client;
+
+/// Adapter for serving handlers as `tower::Service`s
+#[cfg(feature = "tower")]
+pub mod service;
+
 /// Provides N-API bindings to expose the `http` crate types to Node.js.
 #[cfg(feature = "napi-support")]
 pub mod napi;
 
-pub use body::{RequestBody, ResponseBody, StreamError};
+pub use body::{RequestBody, ResponseBody, StreamChunk, StreamError};
+#[cfg(feature = "hyper")]
+pub use client::{ClientBody, HyperClient};
+pub use cookie::{Cookie, SameSite};
 pub use extensions::{
-    BodyBuffer, RequestBuilderExt, RequestExt, ResponseBuilderExt, ResponseException, ResponseExt,
-    ResponseLog, SocketInfo, WebSocketMode,
+    BodyBuffer, ClientCertificate, LogEntry, LogLevel, OriginalRequestLine, PathParams,
+    RequestBuilderExt, RequestExt, RequestId, RequestMetadata, RequestTargetForm,
+    ResponseBuilderExt, ResponseException, ResponseExt, ResponseLog, SocketInfo,
+    SocketInfoParseError, TlsInfo, TraceContext, UriTooLongError, WebSocketMode,
 };
 pub use handler::Handler;
-pub use types::{Request, Response};
+#[cfg(feature = "tower")]
+pub use service::HandlerService;
+pub use types::{BoxError, Request, Response};
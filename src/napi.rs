@@ -16,8 +16,8 @@ use napi::{Either, Error, Result, Status, bindgen_prelude::*};
 use napi_derive::napi;
 
 use crate::{
-    RequestBody, RequestBuilderExt, RequestExt, ResponseBody, ResponseBuilderExt, ResponseExt,
-    SocketInfo as InnerSocketInfo, WebSocketMode,
+    RequestBody, RequestBuilderExt, RequestExt, RequestTargetForm, ResponseBody,
+    ResponseBuilderExt, ResponseExt, SocketInfo as InnerSocketInfo, WebSocketMode,
 };
 
 //
@@ -71,46 +71,45 @@ impl TryFrom<HeaderMap> for HttpHeaderMap {
 //
 
 /// Input options for creating a SocketInfo.
+///
+/// Either half may be omitted, e.g. for a Unix socket or when only the remote
+/// peer is known. A half is considered present when its address is set.
 #[napi(object)]
 #[derive(Default)]
 pub struct SocketInfo {
     /// The string representation of the local IP address the remote client is connecting on.
-    pub local_address: String,
+    pub local_address: Option<String>,
     /// The numeric representation of the local port. For example, 80 or 21.
-    pub local_port: u16,
+    pub local_port: Option<u16>,
     /// The string representation of the local IP family, e.g., "IPv4" or "IPv6".
-    pub local_family: String,
+    pub local_family: Option<String>,
     /// The string representation of the remote IP address.
-    pub remote_address: String,
+    pub remote_address: Option<String>,
     /// The numeric representation of the remote port. For example, 80 or 21.
-    pub remote_port: u16,
+    pub remote_port: Option<u16>,
     /// The string representation of the remote IP family, e.g., "IPv4" or "IPv6".
-    pub remote_family: String,
+    pub remote_family: Option<String>,
 }
 
 impl TryFrom<InnerSocketInfo> for SocketInfo {
     type Error = Error;
 
     fn try_from(socket: InnerSocketInfo) -> Result<Self> {
-        let local = socket.local.ok_or(Error::new(
-            Status::InvalidArg,
-            "Local socket address is required",
-        ))?;
-        let remote = socket.remote.ok_or(Error::new(
-            Status::InvalidArg,
-            "Remote socket address is required",
-        ))?;
-
-        fn socket_info_tuple(socket: &SocketAddr) -> (String, u16, String) {
-            (
-                socket.ip().to_string(),
-                socket.port(),
-                if socket.is_ipv4() { "IPv4" } else { "IPv6" }.to_string(),
-            )
+        type SocketInfoTuple = (Option<String>, Option<u16>, Option<String>);
+
+        fn socket_info_tuple(socket: Option<SocketAddr>) -> SocketInfoTuple {
+            match socket {
+                Some(socket) => (
+                    Some(socket.ip().to_string()),
+                    Some(socket.port()),
+                    Some(if socket.is_ipv4() { "IPv4" } else { "IPv6" }.to_string()),
+                ),
+                None => (None, None, None),
+            }
         }
 
-        let (local_address, local_port, local_family) = socket_info_tuple(&local);
-        let (remote_address, remote_port, remote_family) = socket_info_tuple(&remote);
+        let (local_address, local_port, local_family) = socket_info_tuple(socket.local);
+        let (remote_address, remote_port, remote_family) = socket_info_tuple(socket.remote);
 
         Ok(SocketInfo {
             local_address,
@@ -127,30 +126,40 @@ impl TryFrom<SocketInfo> for InnerSocketInfo {
     type Error = Error;
 
     fn try_from(socket: SocketInfo) -> std::result::Result<Self, Self::Error> {
-        fn sock_addr(family: &str, address: &str, port: u16) -> Result<SocketAddr> {
-            if family == "IPv6" {
+        fn sock_addr(
+            family: Option<&str>,
+            address: Option<&str>,
+            port: Option<u16>,
+        ) -> Result<Option<SocketAddr>> {
+            let Some(address) = address else {
+                return Ok(None);
+            };
+            let port = port.unwrap_or(0);
+            let is_ipv6 = match family {
+                Some(family) => family == "IPv6",
+                None => address.contains(':'),
+            };
+            if is_ipv6 {
                 format!("[{}]:{}", address, port)
             } else {
                 format!("{}:{}", address, port)
             }
             .parse()
+            .map(Some)
             .map_err(|e| Error::new(Status::InvalidArg, format!("Invalid socket address: {}", e)))
         }
 
         let local = sock_addr(
-            &socket.local_family,
-            &socket.local_address,
+            socket.local_family.as_deref(),
+            socket.local_address.as_deref(),
             socket.local_port,
         )?;
         let remote = sock_addr(
-            &socket.remote_family,
-            &socket.remote_address,
+            socket.remote_family.as_deref(),
+            socket.remote_address.as_deref(),
             socket.remote_port,
         )?;
-        Ok(Self {
-            local: Some(local),
-            remote: Some(remote),
-        })
+        Ok(Self { local, remote })
     }
 }
 
@@ -600,37 +609,40 @@ impl Request {
             .parse()
             .map_err(|_| Error::new(Status::InvalidArg, "Invalid URL"))?;
 
+        let method = options.method.unwrap_or_else(|| "GET".to_string());
+        let target_form = http::Method::from_bytes(method.as_bytes())
+            .map(|method| RequestTargetForm::detect(&method, &initial_uri))
+            .unwrap_or(RequestTargetForm::Origin);
+
         let mut final_uri = initial_uri.clone();
 
-        // If we only have a path (no scheme/authority), try to reconstruct from Host header
-        if initial_uri.scheme().is_none() && initial_uri.authority().is_none() {
-            if let Some(ref headers) = options.headers {
-                if let Some(host_value) = headers.get("host".to_string()) {
-                    // Reconstruct the full URI using the Host header
-                    let scheme = "https"; // Default to HTTPS
-                    let full_url = format!(
-                        "{}://{}{}",
-                        scheme,
-                        host_value,
-                        initial_uri
-                            .path_and_query()
-                            .map(|pq| pq.as_str())
-                            .unwrap_or("/")
-                    );
-
-                    final_uri = full_url.parse().map_err(|_| {
-                        Error::new(
-                            Status::InvalidArg,
-                            "Invalid reconstructed URL from Host header",
-                        )
-                    })?;
-                }
-            }
+        // If we only have a path, try to reconstruct from Host header. Other
+        // forms, such as `*` for OPTIONS, can't be made absolute.
+        if target_form == RequestTargetForm::Origin
+            && let Some(ref headers) = options.headers
+            && let Some(host_value) = headers.get("host".to_string())
+        {
+            // Reconstruct the full URI using the Host header
+            let scheme = "https"; // Default to HTTPS
+            let full_url = format!(
+                "{}://{}{}",
+                scheme,
+                host_value,
+                initial_uri
+                    .path_and_query()
+                    .map(|pq| pq.as_str())
+                    .unwrap_or("/")
+            );
+
+            final_uri = full_url.parse().map_err(|_| {
+                Error::new(
+                    Status::InvalidArg,
+                    "Invalid reconstructed URL from Host header",
+                )
+            })?;
         }
 
-        let mut request = RequestBuilder::new()
-            .method(options.method.unwrap_or_else(|| "GET".to_string()).as_str())
-            .uri(final_uri);
+        let mut request = RequestBuilder::new().method(method.as_str()).uri(final_uri);
 
         if let Some(headers) = options.headers {
             for (key, value) in headers.iter() {
@@ -653,6 +665,7 @@ impl Request {
         let body = RequestBody::new();
 
         let mut request = request.body(body).expect("Failed to build request");
+        request.set_request_target_form(target_form);
 
         // Store body data in BodyBuffer extension if provided (to be sent later in Task::compute)
         if let Some(body_buf) = options.body {
@@ -664,7 +677,7 @@ impl Request {
 
         // Set WebSocket mode extension after building
         if websocket {
-            request.extensions_mut().insert(WebSocketMode);
+            request.extensions_mut().insert(WebSocketMode::Server);
         }
 
         Ok(Request(request))
@@ -838,6 +851,33 @@ impl Request {
         *self.0.document_root_mut() = docroot.into();
     }
 
+    /// Get the socket information for the request.
+    ///
+    /// Either half may be absent, in which case its fields are undefined.
+    ///
+    /// # Examples
+    ///
+    /// ```js
+    /// const request = new Request({
+    ///   url: "/index.php",
+    ///   socket: {
+    ///     remoteAddress: "10.0.0.1",
+    ///     remotePort: 54321
+    ///   }
+    /// });
+    ///
+    /// console.log(request.socket.remoteAddress); // 10.0.0.1
+    /// console.log(request.socket.localAddress); // undefined
+    /// ```
+    #[napi(getter, enumerable = true)]
+    pub fn socket(&self) -> Result<Option<SocketInfo>> {
+        self.0
+            .socket_info()
+            .cloned()
+            .map(SocketInfo::try_from)
+            .transpose()
+    }
+
     /// Get the body of the request as a Buffer.
     ///
     /// Returns buffered data if the request was created with a body in the constructor.
@@ -927,7 +967,11 @@ impl Request {
         }
 
         // Auto-detect WebSocket mode and encode frames transparently
-        let is_websocket = self.0.extensions().get::<crate::WebSocketMode>().is_some();
+        let is_websocket = self
+            .0
+            .extensions()
+            .get::<crate::WebSocketMode>()
+            .is_some_and(|mode| mode.is_enabled());
 
         if is_websocket {
             // WebSocket mode: encode as frames
@@ -1028,8 +1072,8 @@ impl Clone for Request {
         }
 
         // Copy the WebSocketMode extension if it exists
-        if self.0.extensions().get::<crate::WebSocketMode>().is_some() {
-            req.extensions_mut().insert(crate::WebSocketMode);
+        if let Some(mode) = self.0.extensions().get::<crate::WebSocketMode>() {
+            req.extensions_mut().insert(*mode);
         }
 
         Request(req)
@@ -1320,7 +1364,7 @@ impl Response {
     /// ```
     #[napi(getter, enumerable = true)]
     pub fn exception(&self) -> Option<String> {
-        self.0.exception().map(|e| e.0.clone())
+        self.0.exception().map(|e| e.message().to_string())
     }
 
     /// Convert the response to a JSON object representation.
@@ -1360,7 +1404,7 @@ impl Response {
 
         // Include exception if present
         if let Some(exception) = self.0.exception() {
-            obj.set("exception", exception.0.clone())?;
+            obj.set("exception", exception.message().to_string())?;
         }
 
         Ok(obj)
@@ -1510,7 +1554,11 @@ impl Response {
         use tokio_util::codec::Decoder;
 
         // Auto-detect WebSocket mode and decode frames transparently
-        let is_websocket = self.0.extensions().get::<crate::WebSocketMode>().is_some();
+        let is_websocket = self
+            .0
+            .extensions()
+            .get::<crate::WebSocketMode>()
+            .is_some_and(|mode| mode.is_enabled());
 
         if is_websocket {
             // WebSocket mode: read HTTP body frames and decode as WebSocket frames
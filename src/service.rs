@@ -0,0 +1,119 @@
+//! Bridge from `Handler` to `tower::Service`, for serving handlers from
+//! tower and hyper server stacks.
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tower_service::Service;
+
+use crate::{Handler, Request, Response};
+
+/// Future returned by `HandlerService`
+///
+/// `Handler::handle` futures aren't required to be `Send`, so neither is
+/// this one. Run connections served by a `HandlerService` on a local task,
+/// e.g. with `tokio::task::spawn_local`.
+pub type HandlerFuture<E> = Pin<Box<dyn Future<Output = Result<Response, E>>>>;
+
+/// `tower::Service` that serves requests with a `Handler`
+///
+/// Each call hands the request to `Handler::handle`, and the handler's error
+/// type is the service's error type. The service is always ready. Cloning is
+/// cheap, as clones share the handler.
+///
+/// With hyper, wrap it in `hyper_util::service::TowerToHyperService` to pass
+/// it to a connection builder.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::{HandlerService, HeaderMap, StatusCode};
+/// use http_handler::handler::Fixed;
+///
+/// let service = HandlerService::new(Fixed::new(StatusCode::OK, HeaderMap::new(), "ok"));
+/// ```
+pub struct HandlerService<H> {
+    handler: Arc<H>,
+}
+
+impl<H> HandlerService<H> {
+    /// Create a service serving requests with `handler`
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler: Arc::new(handler),
+        }
+    }
+
+    /// Get a reference to the handler
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+}
+
+impl<H> Clone for HandlerService<H> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: Arc::clone(&self.handler),
+        }
+    }
+}
+
+impl<H: fmt::Debug> fmt::Debug for HandlerService<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandlerService")
+            .field("handler", &self.handler)
+            .finish()
+    }
+}
+
+impl<H> Service<Request> for HandlerService<H>
+where
+    H: Handler + 'static,
+{
+    type Response = Response;
+    type Error = H::Error;
+    type Future = HandlerFuture<H::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let handler = Arc::clone(&self.handler);
+        Box::pin(async move { handler.handle(request).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBody;
+    use crate::handler::Fixed;
+    use futures_util::future::poll_fn;
+    use http::{HeaderMap, HeaderValue, StatusCode};
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn test_handler_service() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("text/plain"));
+        let mut service = HandlerService::new(Fixed::new(StatusCode::OK, headers, "hello"));
+
+        poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        let request = http::Request::builder()
+            .uri("/")
+            .body(RequestBody::new())
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello");
+    }
+}
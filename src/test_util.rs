@@ -0,0 +1,47 @@
+//! Helpers for testing handlers.
+//!
+//! Enabled with the `test-util` feature.
+
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use tokio::io::AsyncReadExt;
+
+use crate::{Request, RequestBody, Response, ResponseBody};
+
+/// Drain a request body, returning an equivalent request and the body bytes
+///
+/// The returned request has the same head and extensions, with a buffered
+/// body holding the same bytes. This lets a test pass the request on to a
+/// handler while still asserting on what the body contained.
+///
+/// # Panics
+///
+/// Panics if reading the request body fails.
+pub async fn buffer_request(request: Request) -> (Request, Bytes) {
+    let (parts, mut body) = request.into_parts();
+    let mut data = Vec::new();
+    body.read_to_end(&mut data)
+        .await
+        .expect("failed to read request body");
+
+    let data = Bytes::from(data);
+    let request = Request::from_parts(parts, RequestBody::buffered(data.clone()));
+    (request, data)
+}
+
+/// Drain a response body, returning an equivalent response and the body bytes
+///
+/// # Panics
+///
+/// Panics if reading the response body fails.
+pub async fn buffer_response(response: Response) -> (Response, Bytes) {
+    let (parts, body) = response.into_parts();
+    let data = body
+        .collect()
+        .await
+        .expect("failed to read response body")
+        .to_bytes();
+
+    let response = Response::from_parts(parts, ResponseBody::buffered(data.clone()));
+    (response, data)
+}
@@ -9,6 +9,9 @@ pub type Request = http::Request<RequestBody>;
 /// Type alias for HTTP Response with streaming body
 pub type Response = http::Response<ResponseBody>;
 
+/// Type alias for a boxed error, used where errors of different types meet
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 /// Helper functions for building requests with extensions
 pub mod request {
     use super::*;
@@ -29,6 +32,11 @@ pub mod request {
 pub mod response {
     use super::*;
     use bytes::Bytes;
+    use http::{
+        HeaderValue, Method, StatusCode,
+        header::{ALLOW, ETAG, LOCATION},
+    };
+    use http_body_util::BodyExt;
 
     /// Build a response with log data
     pub fn with_log(mut response: Response, log: impl Into<Bytes>) -> Response {
@@ -41,6 +49,174 @@ pub mod response {
         response.set_exception(exception);
         response
     }
+
+    /// Build an empty response with the given status, recording the error
+    /// and its `source()` chain as the exception
+    pub fn from_error<E: std::error::Error + ?Sized>(
+        status: http::StatusCode,
+        err: &E,
+    ) -> Response {
+        let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+        *response.status_mut() = status;
+        response.set_exception_from(err);
+        response
+    }
+
+    /// Build an empty response with the given status
+    pub fn status(status: StatusCode) -> Response {
+        let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+        *response.status_mut() = status;
+        response
+    }
+
+    /// Build an empty response with the given status, recording the message
+    /// as the exception
+    fn error(status: StatusCode, message: impl Into<String>) -> Response {
+        let mut response = self::status(status);
+        response.set_exception(message);
+        response
+    }
+
+    /// Build an empty `204 No Content` response
+    pub fn no_content() -> Response {
+        status(StatusCode::NO_CONTENT)
+    }
+
+    /// Build an empty `400 Bad Request` response with the message as the
+    /// exception
+    pub fn bad_request(message: impl Into<String>) -> Response {
+        error(StatusCode::BAD_REQUEST, message)
+    }
+
+    /// Build an empty `401 Unauthorized` response
+    pub fn unauthorized() -> Response {
+        status(StatusCode::UNAUTHORIZED)
+    }
+
+    /// Build an empty `403 Forbidden` response
+    pub fn forbidden() -> Response {
+        status(StatusCode::FORBIDDEN)
+    }
+
+    /// Build an empty `404 Not Found` response
+    pub fn not_found() -> Response {
+        status(StatusCode::NOT_FOUND)
+    }
+
+    /// Build an `Allow` header value listing the given methods
+    pub fn allow_header(allowed: &[Method]) -> HeaderValue {
+        let methods: Vec<&str> = allowed.iter().map(Method::as_str).collect();
+        HeaderValue::from_str(&methods.join(", ")).expect("method names are valid header values")
+    }
+
+    /// Build an empty `405 Method Not Allowed` response with an `Allow` header
+    /// listing the allowed methods
+    pub fn method_not_allowed(allowed: &[Method]) -> Response {
+        let mut response = status(StatusCode::METHOD_NOT_ALLOWED);
+        response.headers_mut().insert(ALLOW, allow_header(allowed));
+        response
+    }
+
+    /// Build an empty `500 Internal Server Error` response with the message as
+    /// the exception
+    pub fn internal_error(message: impl Into<String>) -> Response {
+        error(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+
+    /// Error returned when building a redirect fails
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum RedirectError {
+        /// The status is not a 3xx redirection status
+        NotRedirect(StatusCode),
+        /// The location contains characters not allowed in a header value
+        InvalidLocation,
+    }
+
+    impl std::fmt::Display for RedirectError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                RedirectError::NotRedirect(status) => {
+                    write!(f, "Status {} is not a redirect", status)
+                }
+                RedirectError::InvalidLocation => write!(f, "Invalid redirect location"),
+            }
+        }
+    }
+
+    impl std::error::Error for RedirectError {}
+
+    /// Build an empty redirect response with the given 3xx status
+    ///
+    /// The location is rejected if it contains control characters, so it
+    /// can't be used to inject headers.
+    pub fn redirect(status: StatusCode, location: &str) -> Result<Response, RedirectError> {
+        if !status.is_redirection() {
+            return Err(RedirectError::NotRedirect(status));
+        }
+        if location.chars().any(char::is_control) {
+            return Err(RedirectError::InvalidLocation);
+        }
+        let location =
+            HeaderValue::from_str(location).map_err(|_| RedirectError::InvalidLocation)?;
+
+        let mut response = http::Response::new(ResponseBody::buffered(Bytes::new()));
+        *response.status_mut() = status;
+        response.headers_mut().insert(LOCATION, location);
+        Ok(response)
+    }
+
+    /// Build a `301 Moved Permanently` redirect
+    pub fn moved_permanently(location: &str) -> Result<Response, RedirectError> {
+        redirect(StatusCode::MOVED_PERMANENTLY, location)
+    }
+
+    /// Build a `302 Found` redirect
+    pub fn found(location: &str) -> Result<Response, RedirectError> {
+        redirect(StatusCode::FOUND, location)
+    }
+
+    /// Build a `303 See Other` redirect
+    pub fn see_other(location: &str) -> Result<Response, RedirectError> {
+        redirect(StatusCode::SEE_OTHER, location)
+    }
+
+    /// Build a `307 Temporary Redirect` redirect
+    pub fn temporary_redirect(location: &str) -> Result<Response, RedirectError> {
+        redirect(StatusCode::TEMPORARY_REDIRECT, location)
+    }
+
+    /// Build a `308 Permanent Redirect` redirect
+    pub fn permanent_redirect(location: &str) -> Result<Response, RedirectError> {
+        redirect(StatusCode::PERMANENT_REDIRECT, location)
+    }
+
+    /// Buffer a response body and set an `ETag` header computed from it
+    ///
+    /// The tag is the 128-bit XXH3 hash of the body in hex, so identical
+    /// bodies always get the same tag, across processes too. A weak tag is
+    /// prefixed with `W/`, for bodies whose representations may differ in
+    /// ways that don't matter, like compression. The whole body is read into
+    /// memory and the response is returned with a buffered body holding it,
+    /// so this is only suitable for bodies that fit. Fails with the body's
+    /// error message if reading it fails.
+    pub async fn with_etag(response: Response, strong: bool) -> Result<Response, String> {
+        let (mut parts, body) = response.into_parts();
+        let data = body.collect().await?.to_bytes();
+
+        let hash = xxhash_rust::xxh3::xxh3_128(&data);
+        let tag = if strong {
+            format!("\"{:032x}\"", hash)
+        } else {
+            format!("W/\"{:032x}\"", hash)
+        };
+        let tag = HeaderValue::from_str(&tag).expect("hex digits are valid header values");
+        parts.headers.insert(ETAG, tag);
+
+        Ok(http::Response::from_parts(
+            parts,
+            ResponseBody::buffered(data),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -48,6 +224,7 @@ mod tests {
     use super::*;
     use bytes::Bytes;
     use http::{Method, StatusCode};
+    use http_body_util::BodyExt;
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
     #[tokio::test]
@@ -127,6 +304,120 @@ mod tests {
         assert_eq!(exception.message(), "Something went wrong");
     }
 
+    #[test]
+    fn test_response_from_error() {
+        let err = std::io::Error::other("disk on fire");
+        let response = response::from_error(StatusCode::SERVICE_UNAVAILABLE, &err);
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.exception().unwrap().message(), "disk on fire");
+    }
+
+    #[test]
+    fn test_status_helpers() {
+        let cases = [
+            (response::status(StatusCode::ACCEPTED), StatusCode::ACCEPTED),
+            (response::no_content(), StatusCode::NO_CONTENT),
+            (response::unauthorized(), StatusCode::UNAUTHORIZED),
+            (response::forbidden(), StatusCode::FORBIDDEN),
+            (response::not_found(), StatusCode::NOT_FOUND),
+        ];
+
+        for (response, status) in cases {
+            assert_eq!(response.status(), status);
+            assert!(response.exception().is_none());
+        }
+
+        let response = response::bad_request("missing field `name`");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.exception().unwrap().message(),
+            "missing field `name`"
+        );
+
+        let response = response::internal_error("database unavailable");
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.exception().unwrap().message(),
+            "database unavailable"
+        );
+    }
+
+    #[test]
+    fn test_method_not_allowed() {
+        let response = response::method_not_allowed(&[Method::GET, Method::HEAD]);
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()["allow"], "GET, HEAD");
+
+        let response = response::method_not_allowed(&[]);
+        assert_eq!(response.headers()["allow"], "");
+    }
+
+    #[test]
+    fn test_redirect_helpers() {
+        let location = "/login?next=%2F";
+        let cases = [
+            (
+                response::moved_permanently(location),
+                StatusCode::MOVED_PERMANENTLY,
+            ),
+            (response::found(location), StatusCode::FOUND),
+            (response::see_other(location), StatusCode::SEE_OTHER),
+            (
+                response::temporary_redirect(location),
+                StatusCode::TEMPORARY_REDIRECT,
+            ),
+            (
+                response::permanent_redirect(location),
+                StatusCode::PERMANENT_REDIRECT,
+            ),
+        ];
+
+        for (response, status) in cases {
+            let response = response.unwrap();
+            assert_eq!(response.status(), status);
+            assert_eq!(response.headers()["location"], location);
+        }
+
+        assert_eq!(
+            response::redirect(StatusCode::OK, "/").unwrap_err(),
+            response::RedirectError::NotRedirect(StatusCode::OK)
+        );
+    }
+
+    #[test]
+    fn test_redirect_rejects_control_characters() {
+        for location in ["/a\r\nSet-Cookie: x=1", "/a\nb", "/a\tb"] {
+            assert_eq!(
+                response::see_other(location).unwrap_err(),
+                response::RedirectError::InvalidLocation
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_with_etag() {
+        let etag = |body: &'static str, strong: bool| async move {
+            let response = http::Response::new(ResponseBody::buffered(Bytes::from(body)));
+            let response = response::with_etag(response, strong).await.unwrap();
+            let tag = response.headers()[http::header::ETAG].clone();
+            let data = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(data, body);
+            tag
+        };
+
+        let first = etag("hello", true).await;
+        assert!(first.to_str().unwrap().starts_with('"'));
+        assert_eq!(first, etag("hello", true).await);
+        assert_ne!(first, etag("hello!", true).await);
+
+        let weak = etag("hello", false).await;
+        assert_eq!(
+            weak.to_str().unwrap(),
+            format!("W/{}", first.to_str().unwrap())
+        );
+    }
+
     #[test]
     fn test_combined_extensions() {
         // Test that we can use multiple extensions together
@@ -0,0 +1,224 @@
+//! Fan-out of WebSocket frames to many connections.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::future::join_all;
+use tokio::io::AsyncWriteExt;
+
+use super::{WebSocketEncoder, WebSocketFrame};
+
+/// Default time a subscriber gets to accept a broadcast frame
+const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A set of WebSocket encoders that frames can be broadcast to.
+///
+/// Encoders are keyed by an id chosen by the caller, such as a connection or
+/// user id. Clones share the same set, so a broadcaster can be handed to every
+/// task that subscribes connections or publishes messages.
+pub struct Broadcaster<K, W> {
+    encoders: Arc<Mutex<HashMap<K, Arc<WebSocketEncoder<W>>>>>,
+    send_timeout: Duration,
+}
+
+impl<K, W> Broadcaster<K, W>
+where
+    K: Hash + Eq + Clone,
+    W: AsyncWriteExt + Unpin + Send,
+{
+    /// Create a broadcaster with no subscribers.
+    pub fn new() -> Self {
+        Self {
+            encoders: Arc::new(Mutex::new(HashMap::new())),
+            send_timeout: DEFAULT_SEND_TIMEOUT,
+        }
+    }
+
+    /// Set how long each subscriber may take to accept a broadcast frame.
+    ///
+    /// Defaults to 5 seconds. See `broadcast`.
+    pub fn with_send_timeout(mut self, timeout: Duration) -> Self {
+        self.send_timeout = timeout;
+        self
+    }
+
+    /// Add an encoder, returning the one it replaced under the same id.
+    pub fn subscribe(
+        &self,
+        id: K,
+        encoder: Arc<WebSocketEncoder<W>>,
+    ) -> Option<Arc<WebSocketEncoder<W>>> {
+        self.encoders.lock().unwrap().insert(id, encoder)
+    }
+
+    /// Remove an encoder, returning it if it was subscribed.
+    pub fn unsubscribe(&self, id: &K) -> Option<Arc<WebSocketEncoder<W>>> {
+        self.encoders.lock().unwrap().remove(id)
+    }
+
+    /// Get the number of subscribed encoders.
+    pub fn len(&self) -> usize {
+        self.encoders.lock().unwrap().len()
+    }
+
+    /// Check if there are no subscribed encoders.
+    pub fn is_empty(&self) -> bool {
+        self.encoders.lock().unwrap().is_empty()
+    }
+
+    /// Send a frame to every subscribed encoder.
+    ///
+    /// Frames are written concurrently, and the broadcast waits for all of
+    /// them, but for at most the send timeout. Encoders that fail to write,
+    /// usually because the peer disconnected, or that don't accept the frame
+    /// in time, because the peer stopped reading, are unsubscribed. A timed
+    /// out connection may be left with part of the frame written, so it
+    /// should be closed. Returns how many encoders the frame was written to.
+    pub async fn broadcast(&self, frame: WebSocketFrame) -> usize {
+        // Snapshot the set so the lock isn't held across writes
+        let encoders: Vec<(K, Arc<WebSocketEncoder<W>>)> = self
+            .encoders
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, encoder)| (id.clone(), Arc::clone(encoder)))
+            .collect();
+
+        let results = join_all(encoders.iter().map(|(_, encoder)| {
+            let frame = frame.clone();
+            async move {
+                tokio::time::timeout(self.send_timeout, encoder.write_frame(frame))
+                    .await
+                    .is_ok_and(|result| result.is_ok())
+            }
+        }))
+        .await;
+
+        let mut delivered = 0;
+        let mut subscribed = self.encoders.lock().unwrap();
+        for ((id, encoder), written) in encoders.into_iter().zip(results) {
+            if written {
+                delivered += 1;
+                continue;
+            }
+            // Leave it alone if it was replaced while writing
+            if subscribed
+                .get(&id)
+                .is_some_and(|current| Arc::ptr_eq(current, &encoder))
+            {
+                subscribed.remove(&id);
+            }
+        }
+        delivered
+    }
+}
+
+impl<K, W> Default for Broadcaster<K, W>
+where
+    K: Hash + Eq + Clone,
+    W: AsyncWriteExt + Unpin + Send,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, W> Clone for Broadcaster<K, W> {
+    fn clone(&self) -> Self {
+        Self {
+            encoders: Arc::clone(&self.encoders),
+            send_timeout: self.send_timeout,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::WebSocketDecoder;
+    use tokio::io::{DuplexStream, duplex};
+
+    fn connection() -> (Arc<WebSocketEncoder<DuplexStream>>, DuplexStream) {
+        let (server, client) = duplex(1024);
+        (Arc::new(WebSocketEncoder::new(server)), client)
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_to_all() {
+        let broadcaster = Broadcaster::new();
+        let (alice, alice_client) = connection();
+        let (bob, bob_client) = connection();
+        broadcaster.subscribe("alice", alice);
+        broadcaster.subscribe("bob", bob);
+
+        // Broadcasting from another task
+        let publisher = broadcaster.clone();
+        let delivered = tokio::spawn(async move {
+            publisher
+                .broadcast(WebSocketFrame::new_text("hello".to_string(), true))
+                .await
+        })
+        .await
+        .unwrap();
+        assert_eq!(delivered, 2);
+
+        for client in [alice_client, bob_client] {
+            let mut decoder = WebSocketDecoder::new(client);
+            let frame = decoder.read_message().await.unwrap().unwrap();
+            assert_eq!(frame.payload_as_text().unwrap(), "hello");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dead_connection_pruned() {
+        let broadcaster = Broadcaster::new();
+        let (alive, alive_client) = connection();
+        let (dead, dead_client) = connection();
+        broadcaster.subscribe(1, alive);
+        broadcaster.subscribe(2, dead);
+        drop(dead_client);
+
+        let delivered = broadcaster
+            .broadcast(WebSocketFrame::new_binary(vec![1, 2, 3], true))
+            .await;
+        assert_eq!(delivered, 1);
+        assert_eq!(broadcaster.len(), 1);
+        assert!(broadcaster.unsubscribe(&2).is_none());
+
+        let mut decoder = WebSocketDecoder::new(alive_client);
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.payload, [1, 2, 3]);
+
+        assert!(broadcaster.unsubscribe(&1).is_some());
+        assert!(broadcaster.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stalled_subscriber_evicted() {
+        let broadcaster = Broadcaster::new().with_send_timeout(Duration::from_secs(2));
+        let (alive, alive_client) = connection();
+        // Never read, with room for less than one frame
+        let (stalled, _stalled_client) = connection();
+        broadcaster.subscribe("alive", alive);
+        broadcaster.subscribe("stalled", stalled);
+
+        let reader = tokio::spawn(async move {
+            let mut decoder = WebSocketDecoder::new(alive_client);
+            decoder.read_message().await.unwrap().unwrap().payload.len()
+        });
+
+        let start = tokio::time::Instant::now();
+        let delivered = broadcaster
+            .broadcast(WebSocketFrame::new_binary(vec![0; 4096], true))
+            .await;
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert_eq!(delivered, 1);
+        assert_eq!(reader.await.unwrap(), 4096);
+        assert!(broadcaster.unsubscribe(&"stalled").is_none());
+        assert_eq!(broadcaster.len(), 1);
+    }
+}
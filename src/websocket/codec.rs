@@ -3,10 +3,85 @@
 //! This codec provides a clean abstraction over DuplexStream, turning raw bytes
 //! into a Stream of WebSocket frames.
 
-use super::frame::{WebSocketError, WebSocketFrame, WebSocketOpcode};
+use super::deflate::{DeflateConfig, Deflater, Inflater};
+use super::frame::{WebSocketCloseCode, WebSocketError, WebSocketFrame, WebSocketOpcode};
 use bytes::{Buf, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 
+/// Counters describing the traffic a `WebSocketCodec` has decoded.
+///
+/// Returned by `WebSocketCodec::stats`. Payload sizes are measured after
+/// unmasking, so they exclude frame headers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CodecStats {
+    /// Number of text frames decoded
+    pub text_frames: u64,
+    /// Number of binary frames decoded
+    pub binary_frames: u64,
+    /// Number of continuation frames decoded
+    pub continuation_frames: u64,
+    /// Number of close frames decoded
+    pub close_frames: u64,
+    /// Number of ping frames decoded
+    pub ping_frames: u64,
+    /// Number of pong frames decoded
+    pub pong_frames: u64,
+    /// Total payload bytes across all decoded frames
+    pub payload_bytes: u64,
+    /// Largest data message seen, in bytes
+    ///
+    /// In passthrough mode messages are not reassembled, so this is the
+    /// largest single data frame instead.
+    pub max_message_size: usize,
+    /// Number of messages reassembled from more than one fragment
+    pub fragmented_messages: u64,
+}
+
+impl CodecStats {
+    /// Total number of frames decoded, across all opcodes.
+    pub fn frames(&self) -> u64 {
+        self.text_frames
+            + self.binary_frames
+            + self.continuation_frames
+            + self.close_frames
+            + self.ping_frames
+            + self.pong_frames
+    }
+
+    fn record_frame(&mut self, frame: &WebSocketFrame) {
+        let counter = match frame.opcode {
+            WebSocketOpcode::Continuation => &mut self.continuation_frames,
+            WebSocketOpcode::Text => &mut self.text_frames,
+            WebSocketOpcode::Binary => &mut self.binary_frames,
+            WebSocketOpcode::Close => &mut self.close_frames,
+            WebSocketOpcode::Ping => &mut self.ping_frames,
+            WebSocketOpcode::Pong => &mut self.pong_frames,
+        };
+        *counter += 1;
+        self.payload_bytes += frame.payload.len() as u64;
+    }
+
+    fn record_message(&mut self, size: usize, fragments: usize) {
+        self.max_message_size = self.max_message_size.max(size);
+        if fragments > 1 {
+            self.fragmented_messages += 1;
+        }
+    }
+}
+
+/// Which end of a connection a `WebSocketCodec` is on.
+///
+/// RFC 6455 Section 5.1 requires clients to mask every frame they send and
+/// servers to never mask theirs, so the role decides both how frames are
+/// encoded and which incoming frames are accepted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebSocketRole {
+    /// Server side: incoming frames must be masked
+    Server,
+    /// Client side: outgoing frames are masked, incoming ones must not be
+    Client,
+}
+
 /// WebSocket codec that implements tokio_util's Decoder and Encoder traits.
 ///
 /// This codec handles:
@@ -16,11 +91,30 @@ use tokio_util::codec::{Decoder, Encoder};
 ///
 /// Use with `tokio_util::codec::Framed` to turn a DuplexStream into a
 /// `Stream<Item = WebSocketFrame>` and `Sink<WebSocketFrame>`.
+/// `WebSocketStream` does this for you, exchanging `WebSocketMessage`s.
 pub struct WebSocketCodec {
     /// Fragments being assembled into a complete message
     fragments: Vec<Vec<u8>>,
     /// Opcode of the first fragment (determines final message type)
     message_opcode: Option<WebSocketOpcode>,
+    /// Maximum number of fragments a single message may be split into
+    max_fragments: Option<usize>,
+    /// Return data frames as received instead of reassembling messages
+    passthrough: bool,
+    /// Counters for decoded traffic
+    stats: CodecStats,
+    /// Record fragment lengths of reassembled messages
+    record_boundaries: bool,
+    /// Fragment lengths of the most recently reassembled message
+    boundaries: Option<Vec<usize>>,
+    /// permessage-deflate state for outgoing messages, when negotiated
+    deflater: Option<Deflater>,
+    /// permessage-deflate state for incoming messages, when negotiated
+    inflater: Option<Inflater>,
+    /// Whether the message being assembled arrived with RSV1 set
+    message_compressed: bool,
+    /// End of the connection, when masking of incoming frames is enforced
+    role: Option<WebSocketRole>,
 }
 
 impl WebSocketCodec {
@@ -29,7 +123,146 @@ impl WebSocketCodec {
         Self {
             fragments: Vec::new(),
             message_opcode: None,
+            max_fragments: None,
+            passthrough: false,
+            stats: CodecStats::default(),
+            record_boundaries: false,
+            boundaries: None,
+            deflater: None,
+            inflater: None,
+            message_compressed: false,
+            role: None,
+        }
+    }
+
+    /// Create a WebSocket codec that does not reassemble fragmented messages.
+    ///
+    /// `decode` returns every frame as it was received, including non-final
+    /// fragments and continuation frames, so a proxy can forward them without
+    /// changing the framing. Frames are still validated when parsed.
+    pub fn passthrough() -> Self {
+        Self {
+            passthrough: true,
+            ..Self::new()
+        }
+    }
+
+    /// Check if this codec returns frames without reassembling messages.
+    pub fn is_passthrough(&self) -> bool {
+        self.passthrough
+    }
+
+    /// Limit the number of fragments a single message may be split into.
+    ///
+    /// Once a message exceeds this many fragments, decoding fails with
+    /// `WebSocketError::TooManyFragments`. This guards against peers sending
+    /// huge numbers of tiny fragments. By default there is no limit.
+    pub fn with_max_fragments(mut self, max_fragments: usize) -> Self {
+        self.max_fragments = Some(max_fragments);
+        self
+    }
+
+    /// Enforce the masking rules for one end of a connection.
+    ///
+    /// A server codec fails decoding with `WebSocketError::UnmaskedClientFrame`
+    /// on an unmasked frame. A client codec fails with
+    /// `WebSocketError::MaskedServerFrame` on a masked frame, and masks every
+    /// frame encoded through `Encoder` with a random key, as clients must
+    /// (RFC 6455 Section 5.3). Without a role, the default, frames are
+    /// accepted either way and encoded unmasked.
+    pub fn with_role(mut self, role: WebSocketRole) -> Self {
+        self.role = Some(role);
+        self
+    }
+
+    /// Get the end of the connection this codec enforces masking for, if set.
+    pub fn role(&self) -> Option<WebSocketRole> {
+        self.role
+    }
+
+    /// Use permessage-deflate (RFC 7692) in both directions.
+    ///
+    /// Only use this once the extension has been negotiated. Unfragmented
+    /// text and binary frames of at least the configured threshold are
+    /// compressed and sent with RSV1 set; everything else is sent as is.
+    ///
+    /// Incoming messages with RSV1 set on their first frame are inflated once
+    /// reassembled, and text messages are checked for UTF-8 after inflating.
+    /// Messages inflating past `DeflateConfig::max_inflated_size` fail with
+    /// `WebSocketError::FrameTooLarge`.
+    /// In passthrough mode frames are returned still compressed, with RSV1
+    /// set, for the caller to forward or inflate.
+    pub fn with_deflate(mut self, config: DeflateConfig) -> Self {
+        self.deflater = Some(Deflater::new(config));
+        self.inflater = Some(Inflater::new(config));
+        self
+    }
+
+    /// Record the fragment boundaries of each reassembled message.
+    ///
+    /// After `decode` returns a data message, `fragment_boundaries` gives the
+    /// payload lengths of the fragments it was assembled from, in order, so a
+    /// proxy can re-fragment it identically. Has no effect in passthrough
+    /// mode, where messages are not reassembled.
+    pub fn with_fragment_boundaries(mut self) -> Self {
+        self.record_boundaries = true;
+        self
+    }
+
+    /// Get the fragment lengths of the most recently reassembled message.
+    ///
+    /// Returns `None` unless enabled with `with_fragment_boundaries` and a
+    /// data message has been decoded. Control frames decoded since then do
+    /// not change it. A message sent as a single frame has one boundary.
+    pub fn fragment_boundaries(&self) -> Option<&[usize]> {
+        self.boundaries.as_deref()
+    }
+
+    /// Get counters for the frames and messages decoded so far.
+    pub fn stats(&self) -> CodecStats {
+        self.stats
+    }
+
+    /// Discard the message currently being assembled.
+    fn reset_message(&mut self) {
+        self.fragments.clear();
+        self.message_opcode = None;
+        self.message_compressed = false;
+    }
+
+    /// Buffer a fragment of the message currently being assembled.
+    fn push_fragment(&mut self, payload: Vec<u8>) -> Result<(), WebSocketError> {
+        if let Some(max) = self.max_fragments
+            && self.fragments.len() >= max
+        {
+            self.reset_message();
+            return Err(WebSocketError::TooManyFragments(max));
+        }
+        self.fragments.push(payload);
+        Ok(())
+    }
+
+    /// Assemble the buffered fragments into a complete message.
+    fn finish_message(&mut self) -> Result<WebSocketFrame, WebSocketError> {
+        let opcode = self.message_opcode.take().unwrap();
+        let fragments = self.fragments.len();
+        if self.record_boundaries {
+            self.boundaries = Some(self.fragments.iter().map(Vec::len).collect());
         }
+        let mut payload: Vec<u8> = self.fragments.drain(..).flatten().collect();
+
+        if std::mem::take(&mut self.message_compressed)
+            && let Some(inflater) = &mut self.inflater
+        {
+            payload = inflater.decompress(&payload)?;
+            if opcode == WebSocketOpcode::Text && std::str::from_utf8(&payload).is_err() {
+                return Err(WebSocketError::InvalidUtf8);
+            }
+        }
+        self.stats.record_message(payload.len(), fragments);
+
+        Ok(WebSocketFrame::new_data(opcode, payload, true)
+            .expect("fragmented messages always start with a data opcode"))
     }
 }
 
@@ -45,30 +278,54 @@ impl Decoder for WebSocketCodec {
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         // Try to parse a frame from the buffer
-        match WebSocketFrame::parse(src) {
+        match WebSocketFrame::parse_with_deflate(src, self.inflater.is_some()) {
             Ok((frame, consumed)) => {
                 // Advance the buffer by the number of bytes consumed
                 src.advance(consumed);
+                self.stats.record_frame(&frame);
+
+                match self.role {
+                    Some(WebSocketRole::Server) if !frame.masked => {
+                        return Err(WebSocketError::UnmaskedClientFrame);
+                    }
+                    Some(WebSocketRole::Client) if frame.masked => {
+                        return Err(WebSocketError::MaskedServerFrame);
+                    }
+                    _ => {}
+                }
 
                 // Handle control frames (ping, pong, close)
                 // These are never fragmented and should be returned immediately
                 if frame.opcode.is_control() {
+                    if let Some((code, _)) = frame.parse_close_payload() {
+                        WebSocketCloseCode::new(code)?;
+                    }
+                    return Ok(Some(frame));
+                }
+
+                // Forward data frames untouched when not reassembling
+                if self.passthrough {
+                    self.stats.record_message(frame.payload.len(), 1);
                     return Ok(Some(frame));
                 }
 
                 // Handle data frames (text, binary, continuation)
                 match frame.opcode {
                     WebSocketOpcode::Text | WebSocketOpcode::Binary => {
+                        // A new message may not start inside a fragmented one
+                        if self.message_opcode.is_some() {
+                            self.reset_message();
+                            return Err(WebSocketError::InterleavedDataFrame);
+                        }
+
                         // First fragment of a new message
                         self.message_opcode = Some(frame.opcode);
-                        self.fragments.push(frame.payload.clone());
+                        self.message_compressed = frame.rsv1;
+                        self.push_fragment(frame.payload)?;
 
                         if frame.fin {
                             // Single-frame message - complete immediately
-                            let opcode = self.message_opcode.take().unwrap();
-                            let payload = self.fragments.drain(..).flatten().collect();
-
-                            Ok(Some(WebSocketFrame::new_data(opcode, payload, true)))
+                            self.finish_message().map(Some)
                         } else {
                             // More fragments coming, wait for them
                             Ok(None)
@@ -78,18 +335,14 @@ impl Decoder for WebSocketCodec {
                         // Continuation of a fragmented message
                         if self.message_opcode.is_none() {
                             // Continuation without initial frame - protocol error
-                            // For now, we'll treat this as incomplete
-                            return Ok(None);
+                            return Err(WebSocketError::UnexpectedContinuation);
                         }
 
-                        self.fragments.push(frame.payload.clone());
+                        self.push_fragment(frame.payload)?;
 
                         if frame.fin {
                             // Final fragment - assemble complete message
-                            let opcode = self.message_opcode.take().unwrap();
-                            let payload = self.fragments.drain(..).flatten().collect();
-
-                            Ok(Some(WebSocketFrame::new_data(opcode, payload, true)))
+                            self.finish_message().map(Some)
                         } else {
                             // More fragments coming, wait for them
                             Ok(None)
@@ -108,12 +361,32 @@ impl Decoder for WebSocketCodec {
     }
 }
 
-impl Encoder<WebSocketFrame> for WebSocketCodec {
-    type Error = WebSocketError;
+impl WebSocketCodec {
+    /// Encode a frame, masking the payload with `mask` if given.
+    ///
+    /// Clients must mask every frame they send (RFC 6455 Section 5.3), while
+    /// servers must not mask any.
+    pub(crate) fn encode_frame(
+        &mut self,
+        mut frame: WebSocketFrame,
+        mask: Option<[u8; 4]>,
+        dst: &mut BytesMut,
+    ) -> Result<(), WebSocketError> {
+        // Refuse to send control frames the peer is required to reject
+        if frame.opcode.is_control() {
+            if frame.payload.len() > 125 {
+                return Err(WebSocketError::ControlFrameTooLarge);
+            }
+            if !frame.fin {
+                return Err(WebSocketError::ControlFrameFragmented);
+            }
+        }
 
-    fn encode(&mut self, frame: WebSocketFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        // Encode the frame (no masking for server->client frames)
-        let encoded = frame.encode(None);
+        if let Some(deflater) = self.deflater.as_mut() {
+            deflater.compress_frame(&mut frame)?;
+        }
+
+        let encoded = frame.encode(mask);
 
         // Write to the destination buffer
         dst.extend_from_slice(&encoded);
@@ -122,6 +395,16 @@ impl Encoder<WebSocketFrame> for WebSocketCodec {
     }
 }
 
+impl Encoder<WebSocketFrame> for WebSocketCodec {
+    type Error = WebSocketError;
+
+    fn encode(&mut self, frame: WebSocketFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        // Only clients mask the frames they send
+        let mask = (self.role == Some(WebSocketRole::Client)).then(rand::random::<[u8; 4]>);
+        self.encode_frame(frame, mask, dst)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,11 +421,7 @@ mod tests {
         let mut buffer = BytesMut::from(&encoded[..]);
         let decoded = codec.decode(&mut buffer).unwrap();
 
-        assert!(decoded.is_some());
-        let decoded_frame = decoded.unwrap();
-        assert_eq!(decoded_frame.opcode, WebSocketOpcode::Text);
-        assert_eq!(decoded_frame.payload, b"Hello");
-        assert!(decoded_frame.fin);
+        assert_eq!(decoded, Some(frame));
     }
 
     #[test]
@@ -171,6 +450,32 @@ mod tests {
         assert!(decoded_frame.fin);
     }
 
+    #[test]
+    fn test_fragment_boundaries_recorded() {
+        let mut codec = WebSocketCodec::new().with_fragment_boundaries();
+        assert_eq!(codec.fragment_boundaries(), None);
+
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&WebSocketFrame::new_text("Hel".to_string(), false).encode(None));
+        buffer.extend_from_slice(&WebSocketFrame::new_ping(b"ping".to_vec()).encode(None));
+        buffer.extend_from_slice(
+            &WebSocketFrame::new_continuation(b"lo".to_vec(), false).encode(None),
+        );
+        buffer.extend_from_slice(
+            &WebSocketFrame::new_continuation(b" Bob".to_vec(), true).encode(None),
+        );
+
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        let ping = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(ping.opcode, WebSocketOpcode::Ping);
+        assert_eq!(codec.fragment_boundaries(), None);
+
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        let message = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(message.payload, b"Hello Bob");
+        assert_eq!(codec.fragment_boundaries(), Some(&[3, 2, 4][..]));
+    }
+
     #[test]
     fn test_encode_frame() {
         let mut codec = WebSocketCodec::new();
@@ -191,6 +496,37 @@ mod tests {
         assert_eq!(decoded_frame.payload, vec![1, 2, 3]);
     }
 
+    #[test]
+    fn test_large_frame_roundtrip() {
+        let mut codec = WebSocketCodec::new();
+        let mut buffer = BytesMut::new();
+
+        let payload: Vec<u8> = (0..70 * 1024).map(|i| (i % 251) as u8).collect();
+        codec
+            .encode(
+                WebSocketFrame::new_binary(payload.clone(), true),
+                &mut buffer,
+            )
+            .unwrap();
+        assert_eq!(buffer[1] & 0x7F, 127);
+        assert_eq!(buffer.len(), 10 + payload.len());
+
+        // Arriving in pieces, the frame only decodes once complete
+        let mut incoming = BytesMut::new();
+        let mut decoded = None;
+        while decoded.is_none() {
+            let n = buffer.len().min(4096);
+            incoming.extend_from_slice(&buffer.split_to(n));
+            decoded = codec.decode(&mut incoming).unwrap();
+        }
+
+        let frame = decoded.unwrap();
+        assert!(buffer.is_empty());
+        assert!(incoming.is_empty());
+        assert_eq!(frame.opcode, WebSocketOpcode::Binary);
+        assert_eq!(frame.payload, payload);
+    }
+
     #[test]
     fn test_control_frame_immediate_return() {
         let mut codec = WebSocketCodec::new();
@@ -208,4 +544,299 @@ mod tests {
         assert_eq!(decoded_frame.opcode, WebSocketOpcode::Ping);
         assert_eq!(decoded_frame.payload, b"test");
     }
+
+    #[test]
+    fn test_max_fragments_exceeded() {
+        let mut codec = WebSocketCodec::new().with_max_fragments(3);
+
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&WebSocketFrame::new_text("a".to_string(), false).encode(None));
+        for _ in 0..3 {
+            buffer.extend_from_slice(
+                &WebSocketFrame::new_continuation(b"a".to_vec(), false).encode(None),
+            );
+        }
+
+        // The first three fragments are within the limit
+        for _ in 0..3 {
+            assert!(codec.decode(&mut buffer).unwrap().is_none());
+        }
+
+        // The fourth fragment exceeds it
+        let result = codec.decode(&mut buffer);
+        assert!(matches!(result, Err(WebSocketError::TooManyFragments(3))));
+    }
+
+    #[test]
+    fn test_max_fragments_allows_complete_message() {
+        let mut codec = WebSocketCodec::new().with_max_fragments(2);
+
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&WebSocketFrame::new_text("Hel".to_string(), false).encode(None));
+        buffer.extend_from_slice(
+            &WebSocketFrame::new_continuation(b"lo".to_vec(), true).encode(None),
+        );
+
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        let frame = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(frame.payload, b"Hello");
+    }
+
+    #[test]
+    fn test_passthrough_returns_fragments() {
+        let mut codec = WebSocketCodec::passthrough();
+        assert!(codec.is_passthrough());
+
+        let first = WebSocketFrame::new_text("Hel".to_string(), false);
+        let second = WebSocketFrame::new_continuation(b"lo".to_vec(), true);
+
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&first.encode(None));
+        buffer.extend_from_slice(&second.encode(None));
+
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(first));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(second));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+    }
+
+    #[test]
+    fn test_encode_rejects_invalid_control_frames() {
+        let mut codec = WebSocketCodec::new();
+        let mut buffer = BytesMut::new();
+
+        let ping = WebSocketFrame::new_ping(vec![0; 200]);
+        let result = codec.encode(ping, &mut buffer);
+        assert!(matches!(result, Err(WebSocketError::ControlFrameTooLarge)));
+
+        let mut pong = WebSocketFrame::new_pong(vec![0; 4]);
+        pong.fin = false;
+        let result = codec.encode(pong, &mut buffer);
+        assert!(matches!(
+            result,
+            Err(WebSocketError::ControlFrameFragmented)
+        ));
+
+        assert!(buffer.is_empty());
+
+        // 125 bytes is the limit
+        let ping = WebSocketFrame::new_ping(vec![0; 125]);
+        codec.encode(ping, &mut buffer).unwrap();
+    }
+
+    #[test]
+    fn test_stats_track_fragments_and_control_frames() {
+        let mut codec = WebSocketCodec::new();
+        assert_eq!(codec.stats(), CodecStats::default());
+
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&WebSocketFrame::new_text("Hel".to_string(), false).encode(None));
+        buffer.extend_from_slice(&WebSocketFrame::new_ping(b"ping".to_vec()).encode(None));
+        buffer.extend_from_slice(
+            &WebSocketFrame::new_continuation(b"lo, ".to_vec(), false).encode(None),
+        );
+        buffer.extend_from_slice(
+            &WebSocketFrame::new_continuation(b"world".to_vec(), true).encode(None),
+        );
+        buffer.extend_from_slice(&WebSocketFrame::new_binary(vec![1, 2], true).encode(None));
+
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        let ping = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(ping.opcode, WebSocketOpcode::Ping);
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        let message = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(message.payload, b"Hello, world");
+        codec.decode(&mut buffer).unwrap().unwrap();
+
+        let stats = codec.stats();
+        assert_eq!(stats.text_frames, 1);
+        assert_eq!(stats.continuation_frames, 2);
+        assert_eq!(stats.ping_frames, 1);
+        assert_eq!(stats.binary_frames, 1);
+        assert_eq!(stats.frames(), 5);
+        assert_eq!(stats.payload_bytes, 3 + 4 + 4 + 5 + 2);
+        assert_eq!(stats.max_message_size, 12);
+        assert_eq!(stats.fragmented_messages, 1);
+    }
+
+    #[test]
+    fn test_deflate_decodes_browser_frames() {
+        let mut codec = WebSocketCodec::new().with_deflate(DeflateConfig::new());
+        let mut buffer = BytesMut::new();
+
+        // Masked client frames carrying "Hello" twice with context takeover
+        // (RFC 7692 Section 7.2.3.2), the way a browser sends them
+        buffer.extend_from_slice(&[
+            0xc1, 0x87, 0x37, 0xfa, 0x21, 0x3d, 0xc5, 0xb2, 0xec, 0xf4, 0xfe, 0xfd, 0x21,
+        ]);
+        buffer.extend_from_slice(&[
+            0xc1, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0xc5, 0xfa, 0x30, 0x3d, 0x37,
+        ]);
+        // A compressed message split over two fragments (Section 7.2.3.1)
+        buffer.extend_from_slice(&[0x41, 0x03, 0xf2, 0x48, 0xcd]);
+        buffer.extend_from_slice(&[0x80, 0x04, 0xc9, 0xc9, 0x07, 0x00]);
+        // Uncompressed messages are still accepted
+        buffer.extend_from_slice(&[0x81, 0x05, b'H', b'e', b'l', b'l', b'o']);
+
+        for _ in 0..2 {
+            let message = codec.decode(&mut buffer).unwrap().unwrap();
+            assert!(message.is_text());
+            assert!(!message.rsv1);
+            assert_eq!(message.payload, b"Hello");
+        }
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        let message = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(message.payload, b"Hello");
+        let message = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(message.payload, b"Hello");
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_deflate_roundtrip() {
+        let config = DeflateConfig::new().threshold(16);
+        let mut sender = WebSocketCodec::new().with_deflate(config);
+        let mut receiver = WebSocketCodec::new().with_deflate(config);
+
+        let large = "round and round ".repeat(64);
+        let mut buffer = BytesMut::new();
+        for text in [large.as_str(), "tiny", large.as_str()] {
+            let frame = WebSocketFrame::new_text(text.to_string(), true);
+            sender
+                .encode_frame(frame, Some([1, 2, 3, 4]), &mut buffer)
+                .unwrap();
+        }
+
+        for text in [large.as_str(), "tiny", large.as_str()] {
+            let message = receiver.decode(&mut buffer).unwrap().unwrap();
+            assert_eq!(message.payload_as_text().unwrap(), text);
+        }
+        assert_eq!(receiver.stats().max_message_size, large.len());
+    }
+
+    #[test]
+    fn test_deflate_rejects_bad_messages() {
+        // RSV1 without the extension
+        let frame = [0xc1, 0x07, 0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00];
+        let result = WebSocketCodec::new().decode(&mut BytesMut::from(&frame[..]));
+        assert!(matches!(result, Err(WebSocketError::ReservedBitsSet)));
+
+        // RSV1 on a ping
+        let mut codec = WebSocketCodec::new().with_deflate(DeflateConfig::new());
+        let result = codec.decode(&mut BytesMut::from(&[0xc9, 0x00][..]));
+        assert!(matches!(result, Err(WebSocketError::ReservedBitsSet)));
+
+        // Data that does not inflate
+        let mut codec = WebSocketCodec::new().with_deflate(DeflateConfig::new());
+        let result = codec.decode(&mut BytesMut::from(&[0xc2, 0x02, 0xff, 0xff][..]));
+        assert!(matches!(result, Err(WebSocketError::InvalidCompressedData)));
+
+        // A message inflating past the limit: 4 KiB of zeros
+        let config = DeflateConfig::new().threshold(0);
+        let mut buffer = BytesMut::new();
+        let frame = WebSocketFrame::new_binary(vec![0; 4096], true);
+        WebSocketCodec::new()
+            .with_deflate(config)
+            .encode_frame(frame, None, &mut buffer)
+            .unwrap();
+        let mut codec = WebSocketCodec::new().with_deflate(config.max_inflated_size(4095));
+        let err = codec.decode(&mut buffer).unwrap_err();
+        assert!(matches!(err, WebSocketError::FrameTooLarge));
+        assert_eq!(err.close_code(), 1009);
+
+        // Text that is not UTF-8 once inflated: a stored block holding 0xff
+        let mut codec = WebSocketCodec::new().with_deflate(DeflateConfig::new());
+        let frame = [0xc1, 0x07, 0x00, 0x01, 0x00, 0xfe, 0xff, 0xff, 0x00];
+        let result = codec.decode(&mut BytesMut::from(&frame[..]));
+        assert!(matches!(result, Err(WebSocketError::InvalidUtf8)));
+    }
+
+    #[test]
+    fn test_close_codes_validated_on_decode() {
+        let close = |code: u16| {
+            let mut buffer = BytesMut::from(&[0x88, 0x02][..]);
+            buffer.extend_from_slice(&code.to_be_bytes());
+            WebSocketCodec::new().decode(&mut buffer)
+        };
+
+        for code in [1000, 3000, 4999] {
+            let frame = close(code).unwrap().unwrap();
+            assert_eq!(frame.parse_close_payload(), Some((code, String::new())));
+        }
+        for code in [1005, 1015] {
+            assert!(matches!(
+                close(code),
+                Err(WebSocketError::InvalidCloseCode(c)) if c == code
+            ));
+        }
+
+        // A close frame without a code is still fine
+        let mut buffer = BytesMut::from(&[0x88, 0x00][..]);
+        assert!(
+            WebSocketCodec::new()
+                .decode(&mut buffer)
+                .unwrap()
+                .unwrap()
+                .is_close()
+        );
+    }
+
+    #[test]
+    fn test_continuation_without_message_rejected() {
+        let mut codec = WebSocketCodec::new();
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(
+            &WebSocketFrame::new_continuation(b"orphan".to_vec(), true).encode(None),
+        );
+
+        let result = codec.decode(&mut buffer);
+        assert!(matches!(
+            result,
+            Err(WebSocketError::UnexpectedContinuation)
+        ));
+    }
+
+    #[test]
+    fn test_interleaved_data_frame_rejected() {
+        let mut codec = WebSocketCodec::new();
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&WebSocketFrame::new_text("Hel".to_string(), false).encode(None));
+        buffer.extend_from_slice(&WebSocketFrame::new_binary(vec![1, 2], true).encode(None));
+
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        let result = codec.decode(&mut buffer);
+        assert!(matches!(result, Err(WebSocketError::InterleavedDataFrame)));
+    }
+
+    #[test]
+    fn test_server_role_rejects_unmasked_frames() {
+        let mut codec = WebSocketCodec::new().with_role(WebSocketRole::Server);
+        let mut buffer = BytesMut::new();
+        let frame = WebSocketFrame::new_text("masked".to_string(), true);
+        buffer.extend_from_slice(&frame.encode(Some([1, 2, 3, 4])));
+        buffer.extend_from_slice(&frame.encode(None));
+
+        let message = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(message.payload, b"masked");
+        let result = codec.decode(&mut buffer);
+        assert!(matches!(result, Err(WebSocketError::UnmaskedClientFrame)));
+    }
+
+    #[test]
+    fn test_client_role_rejects_masked_frames() {
+        let mut codec = WebSocketCodec::new().with_role(WebSocketRole::Client);
+        assert_eq!(codec.role(), Some(WebSocketRole::Client));
+        let mut buffer = BytesMut::new();
+        let ping = WebSocketFrame::new_ping(b"hi".to_vec());
+        buffer.extend_from_slice(&ping.encode(None));
+        buffer.extend_from_slice(&ping.encode(Some([1, 2, 3, 4])));
+
+        codec.decode(&mut buffer).unwrap().unwrap();
+        let result = codec.decode(&mut buffer);
+        assert!(matches!(result, Err(WebSocketError::MaskedServerFrame)));
+
+        // Frames it encodes are masked
+        let mut out = BytesMut::new();
+        codec.encode(ping, &mut out).unwrap();
+        assert_eq!(out[1] & 0x80, 0x80);
+    }
 }
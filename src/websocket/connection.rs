@@ -0,0 +1,292 @@
+//! WebSocket connection over a framed byte stream.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{Sink, SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::Framed;
+
+use super::{WebSocketCodec, WebSocketError, WebSocketFrame, WebSocketOpcode};
+
+/// A WebSocket connection exchanging frames over an async byte stream.
+///
+/// Wraps `Framed<T, WebSocketCodec>`, adding connection-level policy on top of
+/// frame parsing, such as which message types are accepted.
+///
+/// Outgoing frames are encoded into a write buffer. Once the buffer holds at
+/// least the high watermark, `send` and the `Sink` impl wait for the stream to
+/// accept some of it before taking another frame, so a slow peer applies
+/// backpressure instead of growing the buffer. A single frame larger than the
+/// watermark is still buffered whole.
+pub struct WebSocketConnection<T> {
+    framed: Framed<T, WebSocketCodec>,
+    expected_type: Option<WebSocketOpcode>,
+    // Type of the fragmented message being received, with a passthrough codec
+    message_type: Option<WebSocketOpcode>,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> WebSocketConnection<T> {
+    /// Create a connection over the given stream with a default codec.
+    pub fn new(io: T) -> Self {
+        Self::with_codec(io, WebSocketCodec::new())
+    }
+
+    /// Create a connection over the given stream using a configured codec.
+    pub fn with_codec(io: T, codec: WebSocketCodec) -> Self {
+        Self {
+            framed: Framed::new(io, codec),
+            expected_type: None,
+            message_type: None,
+        }
+    }
+
+    /// Only accept data messages of the given type.
+    ///
+    /// With `Some(WebSocketOpcode::Text)` or `Some(WebSocketOpcode::Binary)`,
+    /// receiving a message of the other type closes the connection with code
+    /// 1003 (Unsupported Data) and `recv` returns
+    /// `WebSocketError::UnexpectedMessageType`. With `None`, the default, both
+    /// types are accepted.
+    ///
+    /// With a passthrough codec, messages arrive as fragments. The first
+    /// fragment decides the message type, and the continuation frames after
+    /// it are accepted or rejected along with it.
+    pub fn expect_message_type(mut self, opcode: Option<WebSocketOpcode>) -> Self {
+        self.expected_type = opcode;
+        self
+    }
+
+    /// Get the message type this connection accepts, if restricted.
+    pub fn expected_message_type(&self) -> Option<WebSocketOpcode> {
+        self.expected_type
+    }
+
+    /// Set how many bytes may be buffered for writing before sends wait.
+    ///
+    /// Defaults to 8KB.
+    pub fn with_high_watermark(mut self, bytes: usize) -> Self {
+        self.framed.set_backpressure_boundary(bytes);
+        self
+    }
+
+    /// Get how many bytes may be buffered for writing before sends wait.
+    pub fn high_watermark(&self) -> usize {
+        self.framed.backpressure_boundary()
+    }
+
+    /// Receive the next frame.
+    ///
+    /// Returns `Ok(None)` once the stream has ended.
+    pub async fn recv(&mut self) -> Result<Option<WebSocketFrame>, WebSocketError> {
+        let Some(frame) = self.framed.next().await.transpose()? else {
+            return Ok(None);
+        };
+
+        // Continuation frames belong to the message the first fragment started
+        let message_type = match frame.opcode {
+            WebSocketOpcode::Continuation => self.message_type,
+            opcode if opcode.is_data() => Some(opcode),
+            _ => None,
+        };
+        if frame.opcode.is_data() {
+            self.message_type = if frame.fin { None } else { message_type };
+        }
+
+        if let Some(expected) = self.expected_type
+            && let Some(received) = message_type
+            && received != expected
+        {
+            let err = WebSocketError::UnexpectedMessageType { expected, received };
+            let reason = format!("Expected {} messages", expected);
+            // The peer may already be gone, the protocol error still stands
+            let _ = self.close(err.close_code(), &reason).await;
+            return Err(err);
+        }
+
+        Ok(Some(frame))
+    }
+
+    /// Send a frame.
+    pub async fn send(&mut self, frame: WebSocketFrame) -> Result<(), WebSocketError> {
+        self.framed.send(frame).await
+    }
+
+    /// Send a close frame with the given code and reason.
+    pub async fn close(&mut self, code: u16, reason: &str) -> Result<(), WebSocketError> {
+        self.send(WebSocketFrame::new_close(Some(code), Some(reason)))
+            .await
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &T {
+        self.framed.get_ref()
+    }
+
+    /// Consume the connection, returning the underlying stream.
+    pub fn into_inner(self) -> T {
+        self.framed.into_inner()
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Sink<WebSocketFrame> for WebSocketConnection<T> {
+    type Error = WebSocketError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.framed).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, frame: WebSocketFrame) -> Result<(), Self::Error> {
+        Pin::new(&mut self.framed).start_send(frame)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.framed).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.framed).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Waker;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_unexpected_message_type_closes_with_1003() {
+        let (server, mut client) = tokio::io::duplex(1024);
+        let mut connection =
+            WebSocketConnection::new(server).expect_message_type(Some(WebSocketOpcode::Text));
+
+        // Text is accepted
+        let text = WebSocketFrame::new_text("hello".to_string(), true);
+        client
+            .write_all(&text.encode(Some([1, 2, 3, 4])))
+            .await
+            .unwrap();
+        let received = connection.recv().await.unwrap().unwrap();
+        assert_eq!(received.payload, b"hello");
+
+        // Binary is rejected
+        let binary = WebSocketFrame::new_binary(vec![1, 2, 3], true);
+        client
+            .write_all(&binary.encode(Some([1, 2, 3, 4])))
+            .await
+            .unwrap();
+        let err = connection.recv().await.unwrap_err();
+        assert!(matches!(
+            err,
+            WebSocketError::UnexpectedMessageType {
+                expected: WebSocketOpcode::Text,
+                received: WebSocketOpcode::Binary,
+            }
+        ));
+
+        let mut buf = vec![0u8; 128];
+        let n = client.read(&mut buf).await.unwrap();
+        let (close, _) = WebSocketFrame::parse(&buf[..n]).unwrap();
+        let (code, reason) = close.parse_close_payload().unwrap();
+        assert_eq!(code, 1003);
+        assert_eq!(reason, "Expected text messages");
+    }
+
+    #[tokio::test]
+    async fn test_passthrough_fragmented_messages() {
+        let (server, mut client) = tokio::io::duplex(1024);
+        let mut connection = WebSocketConnection::with_codec(server, WebSocketCodec::passthrough())
+            .expect_message_type(Some(WebSocketOpcode::Text));
+
+        // A fragmented text message, with a ping between its fragments
+        for frame in [
+            WebSocketFrame::new_text("hel".to_string(), false),
+            WebSocketFrame::new_ping(vec![]),
+            WebSocketFrame::new_continuation(b"lo".to_vec(), false),
+            WebSocketFrame::new_continuation(b"!".to_vec(), true),
+        ] {
+            client
+                .write_all(&frame.encode(Some([1, 2, 3, 4])))
+                .await
+                .unwrap();
+        }
+        for opcode in [
+            WebSocketOpcode::Text,
+            WebSocketOpcode::Ping,
+            WebSocketOpcode::Continuation,
+            WebSocketOpcode::Continuation,
+        ] {
+            assert_eq!(connection.recv().await.unwrap().unwrap().opcode, opcode);
+        }
+
+        // A fragmented binary message is rejected on its first fragment
+        client
+            .write_all(&WebSocketFrame::new_binary(vec![1], false).encode(Some([1, 2, 3, 4])))
+            .await
+            .unwrap();
+        let err = connection.recv().await.unwrap_err();
+        assert!(matches!(
+            err,
+            WebSocketError::UnexpectedMessageType {
+                expected: WebSocketOpcode::Text,
+                received: WebSocketOpcode::Binary,
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_accepts_both_types_by_default() {
+        let (server, mut client) = tokio::io::duplex(1024);
+        let mut connection = WebSocketConnection::new(server);
+        assert_eq!(connection.expected_message_type(), None);
+
+        client
+            .write_all(&WebSocketFrame::new_binary(vec![9], true).encode(None))
+            .await
+            .unwrap();
+        client
+            .write_all(&WebSocketFrame::new_text("hi".to_string(), true).encode(None))
+            .await
+            .unwrap();
+
+        assert!(connection.recv().await.unwrap().unwrap().is_binary());
+        assert!(connection.recv().await.unwrap().unwrap().is_text());
+    }
+
+    #[tokio::test]
+    async fn test_sink_applies_backpressure() {
+        // The peer only has room for 64 unread bytes
+        let (server, mut client) = tokio::io::duplex(64);
+        let mut connection = WebSocketConnection::new(server).with_high_watermark(256);
+        assert_eq!(connection.high_watermark(), 256);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let frame = WebSocketFrame::binary(vec![0u8; 100]);
+        let encoded_len = frame.encode(None).len();
+
+        // Keep offering frames while the peer reads nothing
+        let mut accepted = 0;
+        for _ in 0..100 {
+            if Pin::new(&mut connection).poll_ready(&mut cx).is_ready() {
+                Pin::new(&mut connection).start_send(frame.clone()).unwrap();
+                accepted += 1;
+            }
+            assert!(connection.framed.write_buffer().len() < 256 + encoded_len);
+        }
+        assert!(accepted < 10, "accepted {} frames", accepted);
+        assert!(Pin::new(&mut connection).poll_ready(&mut cx).is_pending());
+
+        // Once the peer reads, everything buffered is delivered
+        let reader = tokio::spawn(async move {
+            let mut received = Vec::new();
+            client.read_to_end(&mut received).await.unwrap();
+            received.len()
+        });
+        connection.send(frame).await.unwrap();
+        drop(connection);
+        assert_eq!(reader.await.unwrap(), (accepted + 1) * encoded_len);
+    }
+}
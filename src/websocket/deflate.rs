@@ -0,0 +1,315 @@
+//! permessage-deflate compression (RFC 7692).
+
+use std::io;
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+
+use super::frame::{WebSocketError, WebSocketFrame, WebSocketOpcode};
+
+/// Default smallest message payload worth compressing, in bytes
+const DEFAULT_THRESHOLD: usize = 860;
+
+/// Default largest size a received message may inflate to (64 MiB)
+const DEFAULT_MAX_INFLATED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Trailer a sync flush ends with, which RFC 7692 Section 7.2.1 says to strip
+const SYNC_TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Settings for the permessage-deflate extension
+///
+/// Compression is decided per message, as RFC 7692 allows: messages smaller
+/// than the threshold are sent uncompressed with RSV1 clear, since deflating
+/// a few bytes costs CPU and usually makes them bigger.
+///
+/// Received messages may inflate to at most 64 MiB by default, since a small
+/// compressed message can expand enormously. See `max_inflated_size`.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::websocket::{DeflateConfig, WebSocketCodec};
+///
+/// let codec = WebSocketCodec::new().with_deflate(DeflateConfig::new().threshold(1024));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeflateConfig {
+    threshold: usize,
+    level: Compression,
+    no_context_takeover: bool,
+    max_inflated_size: usize,
+}
+
+impl DeflateConfig {
+    /// Create settings compressing messages of at least 860 bytes
+    pub fn new() -> Self {
+        Self {
+            threshold: DEFAULT_THRESHOLD,
+            level: Compression::default(),
+            no_context_takeover: false,
+            max_inflated_size: DEFAULT_MAX_INFLATED_SIZE,
+        }
+    }
+
+    /// Set the smallest message payload to compress, in bytes
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the compression level
+    pub fn level(mut self, level: Compression) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Reset the compression context after every message sent
+    ///
+    /// Set this when the parameter for this side's role was negotiated:
+    /// `server_no_context_takeover` when sending as the server, or
+    /// `client_no_context_takeover` when sending as the client. It costs
+    /// some compression but frees the peer from keeping a window per
+    /// connection.
+    pub fn no_context_takeover(mut self, enabled: bool) -> Self {
+        self.no_context_takeover = enabled;
+        self
+    }
+
+    /// Set the largest size a received message may inflate to, in bytes
+    ///
+    /// Inflating stops as soon as a message grows past this, and decoding
+    /// fails with `WebSocketError::FrameTooLarge` (close code 1009).
+    pub fn max_inflated_size(mut self, bytes: usize) -> Self {
+        self.max_inflated_size = bytes;
+        self
+    }
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Outgoing compression state for one connection
+pub(crate) struct Deflater {
+    config: DeflateConfig,
+    compress: Compress,
+}
+
+impl Deflater {
+    pub(crate) fn new(config: DeflateConfig) -> Self {
+        Self {
+            config,
+            compress: Compress::new(config.level, false),
+        }
+    }
+
+    /// Compress a frame in place if it is a whole data message at or above
+    /// the threshold, setting RSV1.
+    ///
+    /// Fragments are left alone, since a compressed message spans all of its
+    /// fragments and only the first may carry RSV1.
+    pub(crate) fn compress_frame(
+        &mut self,
+        frame: &mut WebSocketFrame,
+    ) -> Result<(), WebSocketError> {
+        let whole_message = frame.fin
+            && matches!(
+                frame.opcode,
+                WebSocketOpcode::Text | WebSocketOpcode::Binary
+            );
+        if !whole_message || frame.rsv1 || frame.payload.len() < self.config.threshold {
+            return Ok(());
+        }
+
+        frame.payload = self.compress(&frame.payload)?;
+        frame.rsv1 = true;
+        Ok(())
+    }
+
+    /// Deflate one message, without the sync flush trailer
+    fn compress(&mut self, payload: &[u8]) -> Result<Vec<u8>, WebSocketError> {
+        let start = self.compress.total_in();
+        let mut out = Vec::with_capacity(payload.len() / 2 + 64);
+        loop {
+            if out.len() == out.capacity() {
+                out.reserve(out.capacity());
+            }
+            let consumed = (self.compress.total_in() - start) as usize;
+            self.compress
+                .compress_vec(&payload[consumed..], &mut out, FlushCompress::Sync)
+                .map_err(io::Error::other)?;
+
+            // The flush is complete once all input is taken and output space remains
+            let consumed = (self.compress.total_in() - start) as usize;
+            if consumed == payload.len() && out.len() < out.capacity() {
+                break;
+            }
+        }
+
+        if out.ends_with(&SYNC_TRAILER) {
+            out.truncate(out.len() - SYNC_TRAILER.len());
+        }
+        if self.config.no_context_takeover {
+            self.compress.reset();
+        }
+        Ok(out)
+    }
+}
+
+/// Incoming decompression state for one connection
+///
+/// The window is always carried over between messages. That is correct
+/// whether or not the peer resets its own context, since a peer without
+/// context takeover simply never refers back to earlier messages.
+pub(crate) struct Inflater {
+    decompress: Decompress,
+    max_size: usize,
+}
+
+impl Inflater {
+    pub(crate) fn new(config: DeflateConfig) -> Self {
+        Self {
+            decompress: Decompress::new(false),
+            max_size: config.max_inflated_size,
+        }
+    }
+
+    /// Inflate one whole message whose sync flush trailer was stripped
+    ///
+    /// Fails with `WebSocketError::FrameTooLarge` once the output passes the
+    /// configured maximum, without allocating much beyond it.
+    pub(crate) fn decompress(&mut self, payload: &[u8]) -> Result<Vec<u8>, WebSocketError> {
+        let mut input = Vec::with_capacity(payload.len() + SYNC_TRAILER.len());
+        input.extend_from_slice(payload);
+        input.extend_from_slice(&SYNC_TRAILER);
+
+        // Room for one byte past the limit, so exceeding it can be detected
+        let max_capacity = self.max_size.saturating_add(1);
+        let start = self.decompress.total_in();
+        let mut out = Vec::with_capacity((payload.len() * 2 + 64).min(max_capacity));
+        loop {
+            if out.len() == out.capacity() {
+                if out.len() > self.max_size {
+                    return Err(WebSocketError::FrameTooLarge);
+                }
+                out.reserve(out.capacity().min(max_capacity - out.len()));
+            }
+            let consumed = (self.decompress.total_in() - start) as usize;
+            let produced = out.len();
+            self.decompress
+                .decompress_vec(&input[consumed..], &mut out, FlushDecompress::Sync)
+                .map_err(|_| WebSocketError::InvalidCompressedData)?;
+
+            // Done once all input is taken and output space remains
+            let now_consumed = (self.decompress.total_in() - start) as usize;
+            if out.len() > self.max_size {
+                return Err(WebSocketError::FrameTooLarge);
+            }
+            if now_consumed == input.len() && out.len() < out.capacity() {
+                break;
+            }
+            // No progress with room to spare means the stream ended early
+            if now_consumed == consumed && out.len() == produced {
+                return Err(WebSocketError::InvalidCompressedData);
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_roundtrip_with_context_takeover() {
+        let mut deflater = Deflater::new(DeflateConfig::new().threshold(0));
+        let mut inflater = Inflater::new(DeflateConfig::new());
+
+        let message = "the quick brown fox jumps over the lazy dog ".repeat(50);
+        let first = deflater.compress(message.as_bytes()).unwrap();
+        assert!(first.len() < message.len());
+        assert_eq!(inflater.decompress(&first).unwrap(), message.as_bytes());
+
+        // The second copy refers back into the shared window
+        let second = deflater.compress(message.as_bytes()).unwrap();
+        assert!(second.len() < first.len());
+        assert_eq!(inflater.decompress(&second).unwrap(), message.as_bytes());
+    }
+
+    #[test]
+    fn test_compress_without_context_takeover() {
+        let config = DeflateConfig::new().threshold(0).no_context_takeover(true);
+        let mut deflater = Deflater::new(config);
+
+        let message = "hello hello hello hello".repeat(10);
+        let first = deflater.compress(message.as_bytes()).unwrap();
+        let second = deflater.compress(message.as_bytes()).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(
+            Inflater::new(DeflateConfig::new())
+                .decompress(&second)
+                .unwrap(),
+            message.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_decompress_rfc_examples() {
+        // RFC 7692 Section 7.2.3.2: "Hello" twice with context takeover, the
+        // second message referring back to the first
+        let mut inflater = Inflater::new(DeflateConfig::new());
+        let first = [0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00];
+        let second = [0xf2, 0x00, 0x11, 0x00, 0x00];
+        assert_eq!(inflater.decompress(&first).unwrap(), b"Hello");
+        assert_eq!(inflater.decompress(&second).unwrap(), b"Hello");
+
+        // Section 7.2.3.3: a block with no compression
+        let stored = [
+            0x00, 0x05, 0x00, 0xfa, 0xff, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x00,
+        ];
+        assert_eq!(
+            Inflater::new(DeflateConfig::new())
+                .decompress(&stored)
+                .unwrap(),
+            b"Hello"
+        );
+    }
+
+    #[test]
+    fn test_decompress_large_message() {
+        let message = "0123456789".repeat(10_000);
+        let mut deflater = Deflater::new(DeflateConfig::new());
+        let compressed = deflater.compress(message.as_bytes()).unwrap();
+        assert_eq!(
+            Inflater::new(DeflateConfig::new())
+                .decompress(&compressed)
+                .unwrap(),
+            message.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_decompress_stops_at_max_inflated_size() {
+        // 16 MiB of zeros deflates to a few KiB
+        let bomb = vec![0u8; 16 * 1024 * 1024];
+        let compressed = Deflater::new(DeflateConfig::new()).compress(&bomb).unwrap();
+        assert!(compressed.len() < 32 * 1024);
+
+        let config = DeflateConfig::new().max_inflated_size(1024 * 1024);
+        let result = Inflater::new(config).decompress(&compressed);
+        assert!(matches!(result, Err(WebSocketError::FrameTooLarge)));
+
+        // Exactly at the limit is fine
+        let config = DeflateConfig::new().max_inflated_size(bomb.len());
+        let inflated = Inflater::new(config).decompress(&compressed).unwrap();
+        assert_eq!(inflated.len(), bomb.len());
+    }
+
+    #[test]
+    fn test_decompress_rejects_garbage() {
+        let result = Inflater::new(DeflateConfig::new()).decompress(&[0xff, 0xff, 0xff, 0xff]);
+        assert!(matches!(result, Err(WebSocketError::InvalidCompressedData)));
+    }
+}
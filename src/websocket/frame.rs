@@ -1,6 +1,7 @@
 //! WebSocket frame parsing and encoding conforming to RFC 6455.
 
-use std::fmt;
+use bytes::Bytes;
+use std::{fmt, io};
 
 /// WebSocket opcodes as defined in RFC 6455 Section 5.2.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -49,10 +50,69 @@ impl WebSocketOpcode {
             WebSocketOpcode::Text | WebSocketOpcode::Binary | WebSocketOpcode::Continuation
         )
     }
+
+    /// Get a lowercase name for this opcode, for logging.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebSocketOpcode::Continuation => "continuation",
+            WebSocketOpcode::Text => "text",
+            WebSocketOpcode::Binary => "binary",
+            WebSocketOpcode::Close => "close",
+            WebSocketOpcode::Ping => "ping",
+            WebSocketOpcode::Pong => "pong",
+        }
+    }
+}
+
+impl fmt::Display for WebSocketOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A close status code that may be sent in a close frame.
+///
+/// RFC 6455 Section 7.4 reserves 0-999 and codes such as 1005 (no status),
+/// 1006 (abnormal closure) and 1015 (TLS failure), which only describe a
+/// closure locally and must never appear on the wire. The codes allowed are
+/// 1000-1003 and 1007-1011 from the protocol, and 3000-4999 for libraries
+/// and applications.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WebSocketCloseCode(u16);
+
+impl WebSocketCloseCode {
+    /// Validate a close code, failing with
+    /// [`WebSocketError::InvalidCloseCode`] if it may not be sent.
+    pub fn new(code: u16) -> Result<Self, WebSocketError> {
+        if Self::is_valid(code) {
+            Ok(Self(code))
+        } else {
+            Err(WebSocketError::InvalidCloseCode(code))
+        }
+    }
+
+    /// Check whether a close code may appear in a close frame.
+    pub fn is_valid(code: u16) -> bool {
+        matches!(code, 1000..=1003 | 1007..=1011 | 3000..=4999)
+    }
+
+    /// Get the numeric code.
+    pub fn get(self) -> u16 {
+        self.0
+    }
+}
+
+impl From<WebSocketCloseCode> for u16 {
+    fn from(code: WebSocketCloseCode) -> Self {
+        code.0
+    }
 }
 
 /// WebSocket frame structure per RFC 6455 Section 5.2.
-#[derive(Debug, Clone)]
+///
+/// Frames compare equal when every header bit, the opcode, the `masked` flag
+/// and the (unmasked) payload all match.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WebSocketFrame {
     /// FIN bit: indicates this is the final fragment of a message
     pub fin: bool,
@@ -85,34 +145,144 @@ pub enum WebSocketError {
     ReservedBitsSet,
     /// Invalid UTF-8 in text frame
     InvalidUtf8,
+    /// Compressed message could not be inflated
+    InvalidCompressedData,
     /// Frame too large
     FrameTooLarge,
+    /// Message was split into more fragments than the configured limit
+    TooManyFragments(usize),
+    /// Continuation frame received with no fragmented message in progress
+    UnexpectedContinuation,
+    /// New text or binary frame received before the fragmented message in
+    /// progress was finished
+    InterleavedDataFrame,
+    /// Server received a frame the client did not mask
+    UnmaskedClientFrame,
+    /// Client received a frame the server masked
+    MaskedServerFrame,
+    /// A data message was sent while a fragmented message was in progress
+    MessageInProgress,
+    /// Close frame carries a status code that may not be sent on the wire
+    InvalidCloseCode(u16),
+    /// Received a data message of a type the connection does not accept
+    UnexpectedMessageType {
+        /// The message type the connection accepts
+        expected: WebSocketOpcode,
+        /// The message type that was received
+        received: WebSocketOpcode,
+    },
     /// I/O error
-    IoError(String),
+    IoError {
+        /// Kind of the underlying I/O error
+        kind: io::ErrorKind,
+        /// Description of the underlying I/O error
+        message: String,
+    },
+}
+
+impl WebSocketError {
+    /// Get the close code RFC 6455 Section 7.4.1 recommends sending to the
+    /// peer when failing the connection because of this error.
+    pub fn close_code(&self) -> u16 {
+        match self {
+            WebSocketError::InvalidOpcode(_)
+            | WebSocketError::IncompleteFrame
+            | WebSocketError::ControlFrameTooLarge
+            | WebSocketError::ControlFrameFragmented
+            | WebSocketError::ReservedBitsSet
+            | WebSocketError::UnexpectedContinuation
+            | WebSocketError::InterleavedDataFrame
+            | WebSocketError::UnmaskedClientFrame
+            | WebSocketError::MaskedServerFrame
+            | WebSocketError::InvalidCloseCode(_) => 1002,
+            WebSocketError::InvalidUtf8 | WebSocketError::InvalidCompressedData => 1007,
+            WebSocketError::UnexpectedMessageType { .. } => 1003,
+            WebSocketError::FrameTooLarge | WebSocketError::TooManyFragments(_) => 1009,
+            WebSocketError::MessageInProgress | WebSocketError::IoError { .. } => 1011,
+        }
+    }
+
+    /// Get the kind of the underlying I/O error, if this is an I/O error.
+    pub fn as_io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            WebSocketError::IoError { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for WebSocketError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            WebSocketError::InvalidOpcode(op) => write!(f, "Invalid WebSocket opcode: {:#x}", op),
-            WebSocketError::IncompleteFrame => write!(f, "Incomplete WebSocket frame"),
+            WebSocketError::InvalidOpcode(op) => {
+                write!(f, "protocol error: invalid WebSocket opcode {:#x}", op)?
+            }
+            WebSocketError::IncompleteFrame => write!(f, "protocol error: incomplete frame")?,
             WebSocketError::ControlFrameTooLarge => {
-                write!(f, "Control frame payload exceeds 125 bytes")
+                write!(f, "protocol error: control frame payload exceeds 125 bytes")?
+            }
+            WebSocketError::ControlFrameFragmented => {
+                write!(f, "protocol error: control frame is fragmented")?
+            }
+            WebSocketError::ReservedBitsSet => {
+                write!(f, "protocol error: reserved bits set without extension")?
+            }
+            WebSocketError::UnexpectedContinuation => {
+                write!(f, "protocol error: continuation frame without a message")?
+            }
+            WebSocketError::InterleavedDataFrame => {
+                write!(f, "protocol error: data frame inside a fragmented message")?
+            }
+            WebSocketError::UnmaskedClientFrame => {
+                write!(f, "protocol error: client frame is not masked")?
+            }
+            WebSocketError::MaskedServerFrame => {
+                write!(f, "protocol error: server frame is masked")?
+            }
+            WebSocketError::MessageInProgress => {
+                write!(f, "internal error: a fragmented message is in progress")?
+            }
+            WebSocketError::InvalidCloseCode(code) => {
+                write!(f, "protocol error: invalid close code {}", code)?
+            }
+            WebSocketError::UnexpectedMessageType { expected, received } => write!(
+                f,
+                "unsupported data: expected {} message, received {}",
+                expected, received
+            )?,
+            WebSocketError::InvalidUtf8 => write!(f, "invalid UTF-8 in text frame")?,
+            WebSocketError::InvalidCompressedData => write!(f, "invalid compressed message data")?,
+            WebSocketError::FrameTooLarge => write!(f, "message too big: frame too large")?,
+            WebSocketError::TooManyFragments(max) => write!(
+                f,
+                "message too big: message exceeds maximum of {} fragments",
+                max
+            )?,
+            WebSocketError::IoError { message, .. } => {
+                write!(f, "internal error: I/O error: {}", message)?
             }
-            WebSocketError::ControlFrameFragmented => write!(f, "Control frame is fragmented"),
-            WebSocketError::ReservedBitsSet => write!(f, "Reserved bits set without extension"),
-            WebSocketError::InvalidUtf8 => write!(f, "Invalid UTF-8 in text frame"),
-            WebSocketError::FrameTooLarge => write!(f, "Frame too large"),
-            WebSocketError::IoError(msg) => write!(f, "I/O error: {}", msg),
         }
+        write!(f, " (close {})", self.close_code())
     }
 }
 
 impl std::error::Error for WebSocketError {}
 
-impl From<std::io::Error> for WebSocketError {
-    fn from(err: std::io::Error) -> Self {
-        WebSocketError::IoError(err.to_string())
+impl From<io::Error> for WebSocketError {
+    fn from(err: io::Error) -> Self {
+        WebSocketError::IoError {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<WebSocketError> for io::Error {
+    fn from(err: WebSocketError) -> Self {
+        match err {
+            WebSocketError::IoError { kind, message } => io::Error::new(kind, message),
+            other => io::Error::new(io::ErrorKind::InvalidData, other),
+        }
     }
 }
 
@@ -145,13 +315,25 @@ impl WebSocketFrame {
     /// +---------------------------------------------------------------+
     /// ```
     pub fn parse(data: &[u8]) -> Result<(Self, usize), WebSocketError> {
+        Self::parse_with_deflate(data, false)
+    }
+
+    /// Parse a WebSocket frame, allowing RSV1 if `deflate` is set.
+    ///
+    /// With permessage-deflate negotiated (RFC 7692), RSV1 on the first
+    /// frame of a text or binary message marks it as compressed. It is still
+    /// rejected on continuation and control frames, and text payloads carrying
+    /// it are not checked for UTF-8 until inflated.
+    pub fn parse_with_deflate(data: &[u8], deflate: bool) -> Result<(Self, usize), WebSocketError> {
+        // All reads go through checked slicing, so a short or adversarial
+        // input yields an error rather than a panic or an overflow.
+
         // Need at least 2 bytes for header
-        if data.len() < 2 {
+        let Some((&[byte1, byte2], mut rest)) = data.split_first_chunk::<2>() else {
             return Err(WebSocketError::IncompleteFrame);
-        }
+        };
 
         // Parse first byte: FIN, RSV1-3, Opcode
-        let byte1 = data[0];
         let fin = (byte1 & 0b1000_0000) != 0;
         let rsv1 = (byte1 & 0b0100_0000) != 0;
         let rsv2 = (byte1 & 0b0010_0000) != 0;
@@ -159,41 +341,27 @@ impl WebSocketFrame {
         let opcode = WebSocketOpcode::from_u8(byte1 & 0b0000_1111)?;
 
         // Parse second byte: MASK, Payload length
-        let byte2 = data[1];
         let masked = (byte2 & 0b1000_0000) != 0;
         let mut payload_len = (byte2 & 0b0111_1111) as u64;
 
-        let mut offset = 2;
-
         // Parse extended payload length if needed
         if payload_len == 126 {
-            if data.len() < offset + 2 {
-                return Err(WebSocketError::IncompleteFrame);
-            }
-            payload_len = u16::from_be_bytes([data[offset], data[offset + 1]]) as u64;
-            offset += 2;
+            let (len, tail) = rest
+                .split_first_chunk::<2>()
+                .ok_or(WebSocketError::IncompleteFrame)?;
+            payload_len = u16::from_be_bytes(*len) as u64;
+            rest = tail;
         } else if payload_len == 127 {
-            if data.len() < offset + 8 {
-                return Err(WebSocketError::IncompleteFrame);
-            }
-            payload_len = u64::from_be_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-                data[offset + 4],
-                data[offset + 5],
-                data[offset + 6],
-                data[offset + 7],
-            ]);
-            offset += 8;
+            let (len, tail) = rest
+                .split_first_chunk::<8>()
+                .ok_or(WebSocketError::IncompleteFrame)?;
+            payload_len = u64::from_be_bytes(*len);
+            rest = tail;
         }
 
         // Validate payload length
-        if payload_len > usize::MAX as u64 {
-            return Err(WebSocketError::FrameTooLarge);
-        }
-        let payload_len = payload_len as usize;
+        let payload_len =
+            usize::try_from(payload_len).map_err(|_| WebSocketError::FrameTooLarge)?;
 
         // Validate control frames
         if opcode.is_control() {
@@ -206,34 +374,31 @@ impl WebSocketFrame {
         }
 
         // Validate reserved bits (must be 0 unless extension is negotiated)
-        if rsv1 || rsv2 || rsv3 {
+        let compressed =
+            rsv1 && deflate && matches!(opcode, WebSocketOpcode::Text | WebSocketOpcode::Binary);
+        if (rsv1 && !compressed) || rsv2 || rsv3 {
             return Err(WebSocketError::ReservedBitsSet);
         }
 
         // Parse masking key if present
         let masking_key = if masked {
-            if data.len() < offset + 4 {
-                return Err(WebSocketError::IncompleteFrame);
-            }
-            let key = [
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ];
-            offset += 4;
-            Some(key)
+            let (key, tail) = rest
+                .split_first_chunk::<4>()
+                .ok_or(WebSocketError::IncompleteFrame)?;
+            rest = tail;
+            Some(*key)
         } else {
             None
         };
 
         // Parse payload
-        if data.len() < offset + payload_len {
-            return Err(WebSocketError::IncompleteFrame);
-        }
+        let (payload, _) = rest
+            .split_at_checked(payload_len)
+            .ok_or(WebSocketError::IncompleteFrame)?;
+        let mut payload = payload.to_vec();
 
-        let mut payload = data[offset..offset + payload_len].to_vec();
-        offset += payload_len;
+        // Header and payload both fit in `data`, so this can't overflow
+        let consumed = data.len() - rest.len() + payload_len;
 
         // Unmask payload if masked
         if let Some(mask) = masking_key {
@@ -241,7 +406,11 @@ impl WebSocketFrame {
         }
 
         // Validate UTF-8 for text frames
-        if opcode == WebSocketOpcode::Text && fin && std::str::from_utf8(&payload).is_err() {
+        if opcode == WebSocketOpcode::Text
+            && fin
+            && !compressed
+            && std::str::from_utf8(&payload).is_err()
+        {
             return Err(WebSocketError::InvalidUtf8);
         }
 
@@ -255,7 +424,7 @@ impl WebSocketFrame {
                 masked,
                 payload,
             },
-            offset,
+            consumed,
         ))
     }
 
@@ -321,6 +490,11 @@ impl WebSocketFrame {
         frame
     }
 
+    /// Get the lowercase name of this frame's opcode, for logging.
+    pub fn opcode_str(&self) -> &'static str {
+        self.opcode.as_str()
+    }
+
     /// Apply XOR mask to payload data per RFC 6455 Section 5.3.
     ///
     /// This operation is reversible (applying the same mask twice yields the original data).
@@ -330,9 +504,39 @@ impl WebSocketFrame {
         }
     }
 
-    /// Create a new data frame (text or binary).
-    pub fn new_data(opcode: WebSocketOpcode, payload: Vec<u8>, fin: bool) -> Self {
-        debug_assert!(opcode.is_data());
+    /// Create a new data frame (text, binary or continuation).
+    ///
+    /// Returns [`WebSocketError::InvalidOpcode`] if `opcode` is a control
+    /// opcode; use [`WebSocketFrame::new_control`] for those.
+    pub fn new_data(
+        opcode: WebSocketOpcode,
+        payload: Vec<u8>,
+        fin: bool,
+    ) -> Result<Self, WebSocketError> {
+        if !opcode.is_data() {
+            return Err(WebSocketError::InvalidOpcode(opcode as u8));
+        }
+        Ok(Self::data(opcode, payload, fin))
+    }
+
+    /// Create a new control frame (close, ping or pong).
+    ///
+    /// Control frames are never fragmented, so `fin` is always set. Returns
+    /// [`WebSocketError::InvalidOpcode`] if `opcode` is a data opcode and
+    /// [`WebSocketError::ControlFrameTooLarge`] if the payload exceeds 125
+    /// bytes.
+    pub fn new_control(opcode: WebSocketOpcode, payload: Vec<u8>) -> Result<Self, WebSocketError> {
+        if !opcode.is_control() {
+            return Err(WebSocketError::InvalidOpcode(opcode as u8));
+        }
+        if payload.len() > 125 {
+            return Err(WebSocketError::ControlFrameTooLarge);
+        }
+        Ok(Self::control(opcode, payload))
+    }
+
+    /// Build a data frame for an opcode known to be a data opcode.
+    fn data(opcode: WebSocketOpcode, payload: Vec<u8>, fin: bool) -> Self {
         WebSocketFrame {
             fin,
             rsv1: false,
@@ -344,77 +548,104 @@ impl WebSocketFrame {
         }
     }
 
+    /// Build a final control frame for an opcode known to be a control opcode.
+    fn control(opcode: WebSocketOpcode, payload: Vec<u8>) -> Self {
+        WebSocketFrame {
+            fin: true,
+            rsv1: false,
+            rsv2: false,
+            rsv3: false,
+            opcode,
+            masked: false,
+            payload,
+        }
+    }
+
     /// Create a new text frame.
     pub fn new_text(text: String, fin: bool) -> Self {
-        Self::new_data(WebSocketOpcode::Text, text.into_bytes(), fin)
+        Self::data(WebSocketOpcode::Text, text.into_bytes(), fin)
     }
 
     /// Create a new binary frame.
     pub fn new_binary(data: Vec<u8>, fin: bool) -> Self {
-        Self::new_data(WebSocketOpcode::Binary, data, fin)
+        Self::data(WebSocketOpcode::Binary, data, fin)
     }
 
     /// Create a new continuation frame.
     pub fn new_continuation(data: Vec<u8>, fin: bool) -> Self {
-        Self::new_data(WebSocketOpcode::Continuation, data, fin)
+        Self::data(WebSocketOpcode::Continuation, data, fin)
+    }
+
+    /// Create a final binary frame from any byte source.
+    ///
+    /// The payload is stored as a `Vec<u8>`, so an owned `Vec<u8>` is used
+    /// as it is. Anything else is copied into a new vector, except `Bytes`
+    /// that uniquely owns the vector it was made from.
+    pub fn binary(data: impl Into<Bytes>) -> Self {
+        Self::data(WebSocketOpcode::Binary, Vec::from(data.into()), true)
+    }
+
+    /// Create a final text frame.
+    ///
+    /// An owned `String` becomes the payload without copying; a `&str` is
+    /// copied. Use [`WebSocketFrame::text_from_utf8`] for text held as bytes.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::data(WebSocketOpcode::Text, text.into().into_bytes(), true)
+    }
+
+    /// Create a final text frame from bytes holding UTF-8 text.
+    ///
+    /// Returns [`WebSocketError::InvalidUtf8`] if the data isn't valid UTF-8.
+    /// Copies like [`WebSocketFrame::binary`].
+    pub fn text_from_utf8(data: impl Into<Bytes>) -> Result<Self, WebSocketError> {
+        let data = Vec::from(data.into());
+        if std::str::from_utf8(&data).is_err() {
+            return Err(WebSocketError::InvalidUtf8);
+        }
+        Ok(Self::data(WebSocketOpcode::Text, data, true))
     }
 
     /// Create a new close frame with optional status code and reason.
+    ///
+    /// The code must be one that may be sent on the wire, see
+    /// [`WebSocketCloseCode`]; this is checked in debug builds.
     pub fn new_close(code: Option<u16>, reason: Option<&str>) -> Self {
         let mut payload = Vec::new();
         if let Some(code) = code {
+            debug_assert!(
+                WebSocketCloseCode::is_valid(code),
+                "close code {} may not be sent",
+                code
+            );
             payload.extend_from_slice(&code.to_be_bytes());
             if let Some(reason) = reason {
                 payload.extend_from_slice(reason.as_bytes());
             }
         }
-        WebSocketFrame {
-            fin: true,
-            rsv1: false,
-            rsv2: false,
-            rsv3: false,
-            opcode: WebSocketOpcode::Close,
-            masked: false,
-            payload,
-        }
+        Self::control(WebSocketOpcode::Close, payload)
     }
 
     /// Create a new ping frame.
     pub fn new_ping(data: Vec<u8>) -> Self {
-        WebSocketFrame {
-            fin: true,
-            rsv1: false,
-            rsv2: false,
-            rsv3: false,
-            opcode: WebSocketOpcode::Ping,
-            masked: false,
-            payload: data,
-        }
+        Self::control(WebSocketOpcode::Ping, data)
     }
 
     /// Create a new pong frame.
     pub fn new_pong(data: Vec<u8>) -> Self {
-        WebSocketFrame {
-            fin: true,
-            rsv1: false,
-            rsv2: false,
-            rsv3: false,
-            opcode: WebSocketOpcode::Pong,
-            masked: false,
-            payload: data,
-        }
+        Self::control(WebSocketOpcode::Pong, data)
     }
 
     /// Parse close frame payload to extract status code and reason.
+    ///
+    /// The code is returned as received; frames decoded by `WebSocketCodec`
+    /// have already had it checked with [`WebSocketCloseCode`].
     pub fn parse_close_payload(&self) -> Option<(u16, String)> {
         if self.opcode != WebSocketOpcode::Close {
             return None;
         }
-        if self.payload.len() < 2 {
-            return None;
-        }
-        let code = u16::from_be_bytes([self.payload[0], self.payload[1]]);
-        let reason = String::from_utf8_lossy(&self.payload[2..]).to_string();
+        let (code, reason) = self.payload.split_first_chunk::<2>()?;
+        let code = u16::from_be_bytes(*code);
+        let reason = String::from_utf8_lossy(reason).to_string();
         Some((code, reason))
     }
 
@@ -483,8 +714,13 @@ mod tests {
 
         let (frame, consumed) = WebSocketFrame::parse(&data).unwrap();
         assert_eq!(consumed, 11);
-        assert!(frame.fin);
-        assert_eq!(frame.payload, b"Hello");
+        assert_eq!(
+            frame,
+            WebSocketFrame {
+                masked: true,
+                ..WebSocketFrame::new_text("Hello".to_string(), true)
+            }
+        );
     }
 
     #[test]
@@ -520,6 +756,41 @@ mod tests {
         assert_eq!(frame.payload.len(), 200);
     }
 
+    #[test]
+    fn test_extended_length_64bit_roundtrip() {
+        let payload: Vec<u8> = (0..70 * 1024).map(|i| (i % 251) as u8).collect();
+        let frame = WebSocketFrame::new_binary(payload.clone(), true);
+
+        for mask in [None, Some([0x12, 0x34, 0x56, 0x78])] {
+            let encoded = frame.encode(mask);
+            let header_len = if mask.is_some() { 14 } else { 10 };
+            assert_eq!(encoded.len(), header_len + payload.len());
+
+            // Extended 64-bit length indicator, then the length in 8 bytes
+            assert_eq!(encoded[1] & 0x7F, 127);
+            assert_eq!(encoded[2..10], (payload.len() as u64).to_be_bytes());
+
+            let (parsed, consumed) = WebSocketFrame::parse(&encoded).unwrap();
+            assert_eq!(consumed, encoded.len());
+            assert_eq!(parsed.opcode, WebSocketOpcode::Binary);
+            assert!(parsed.fin);
+            assert_eq!(parsed.payload, payload);
+        }
+    }
+
+    #[test]
+    fn test_extended_length_boundaries() {
+        for (len, indicator) in [(125, 125), (126, 126), (65535, 126), (65536, 127)] {
+            let frame = WebSocketFrame::new_binary(vec![0xAB; len], true);
+            let encoded = frame.encode(None);
+            assert_eq!(encoded[1], indicator, "length {}", len);
+
+            let (parsed, consumed) = WebSocketFrame::parse(&encoded).unwrap();
+            assert_eq!(consumed, encoded.len());
+            assert_eq!(parsed.payload.len(), len);
+        }
+    }
+
     #[test]
     fn test_close_frame() {
         let frame = WebSocketFrame::new_close(Some(1000), Some("Normal closure"));
@@ -531,6 +802,50 @@ mod tests {
         assert_eq!(reason, "Normal closure");
     }
 
+    #[test]
+    fn test_rsv1_allowed_only_with_deflate() {
+        // Compressed "Hello" from RFC 7692 Section 7.2.3.1
+        let data = [0xc1, 0x07, 0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00];
+        assert!(matches!(
+            WebSocketFrame::parse(&data),
+            Err(WebSocketError::ReservedBitsSet)
+        ));
+
+        // The compressed text payload is not UTF-8, and is not checked yet
+        let (frame, consumed) = WebSocketFrame::parse_with_deflate(&data, true).unwrap();
+        assert_eq!(consumed, 9);
+        assert!(frame.rsv1);
+        assert_eq!(frame.payload, &data[2..]);
+
+        // Never allowed on continuation or control frames
+        for byte1 in [0xc0, 0xc9] {
+            assert!(matches!(
+                WebSocketFrame::parse_with_deflate(&[byte1, 0x00], true),
+                Err(WebSocketError::ReservedBitsSet)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_close_code_validation() {
+        for code in [1000, 1001, 1003, 1007, 1011, 3000, 4000, 4999] {
+            assert_eq!(WebSocketCloseCode::new(code).unwrap().get(), code);
+        }
+        for code in [0, 999, 1004, 1005, 1006, 1015, 2999, 5000] {
+            assert!(matches!(
+                WebSocketCloseCode::new(code),
+                Err(WebSocketError::InvalidCloseCode(c)) if c == code
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "close code 1006 may not be sent")]
+    fn test_new_close_rejects_reserved_code() {
+        WebSocketFrame::new_close(Some(1006), None);
+    }
+
     #[test]
     fn test_control_frame_too_large() {
         // Control frame with payload > 125 bytes
@@ -551,4 +866,216 @@ mod tests {
         let result = WebSocketFrame::parse(&data);
         assert!(matches!(result, Err(WebSocketError::IncompleteFrame)));
     }
+
+    #[test]
+    fn test_opcode_as_str() {
+        let cases = [
+            (WebSocketOpcode::Continuation, "continuation"),
+            (WebSocketOpcode::Text, "text"),
+            (WebSocketOpcode::Binary, "binary"),
+            (WebSocketOpcode::Close, "close"),
+            (WebSocketOpcode::Ping, "ping"),
+            (WebSocketOpcode::Pong, "pong"),
+        ];
+
+        for (opcode, name) in cases {
+            assert_eq!(opcode.as_str(), name);
+            assert_eq!(opcode.to_string(), name);
+        }
+
+        let frame = WebSocketFrame::new_ping(vec![]);
+        assert_eq!(frame.opcode_str(), "ping");
+    }
+
+    #[test]
+    fn test_error_display_includes_close_code() {
+        let cases = [
+            (WebSocketError::InvalidOpcode(0x3), 1002),
+            (WebSocketError::IncompleteFrame, 1002),
+            (WebSocketError::ControlFrameTooLarge, 1002),
+            (WebSocketError::ControlFrameFragmented, 1002),
+            (WebSocketError::ReservedBitsSet, 1002),
+            (WebSocketError::UnexpectedContinuation, 1002),
+            (WebSocketError::InterleavedDataFrame, 1002),
+            (WebSocketError::InvalidCloseCode(1005), 1002),
+            (WebSocketError::UnmaskedClientFrame, 1002),
+            (WebSocketError::MaskedServerFrame, 1002),
+            (WebSocketError::MessageInProgress, 1011),
+            (
+                WebSocketError::UnexpectedMessageType {
+                    expected: WebSocketOpcode::Text,
+                    received: WebSocketOpcode::Binary,
+                },
+                1003,
+            ),
+            (WebSocketError::InvalidUtf8, 1007),
+            (WebSocketError::InvalidCompressedData, 1007),
+            (WebSocketError::FrameTooLarge, 1009),
+            (WebSocketError::TooManyFragments(4), 1009),
+            (
+                WebSocketError::IoError {
+                    kind: io::ErrorKind::BrokenPipe,
+                    message: "broken pipe".to_string(),
+                },
+                1011,
+            ),
+        ];
+
+        for (error, code) in cases {
+            assert_eq!(error.close_code(), code);
+            let message = error.to_string();
+            assert!(
+                message.ends_with(&format!("(close {})", code)),
+                "{message:?} does not mention close code {code}"
+            );
+        }
+
+        assert_eq!(
+            WebSocketError::InvalidOpcode(0x3).to_string(),
+            "protocol error: invalid WebSocket opcode 0x3 (close 1002)"
+        );
+    }
+
+    #[test]
+    fn test_io_error_kind_preserved() {
+        let io_err = io::Error::new(io::ErrorKind::BrokenPipe, "connection went away");
+        let err = WebSocketError::from(io_err);
+        assert_eq!(err.as_io_kind(), Some(io::ErrorKind::BrokenPipe));
+        assert!(err.to_string().contains("connection went away"));
+
+        // And back again
+        let io_err = io::Error::from(err);
+        assert_eq!(io_err.kind(), io::ErrorKind::BrokenPipe);
+
+        assert_eq!(WebSocketError::InvalidUtf8.as_io_kind(), None);
+        let io_err = io::Error::from(WebSocketError::InvalidUtf8);
+        assert_eq!(io_err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_constructors_reject_mismatched_opcodes() {
+        assert!(matches!(
+            WebSocketFrame::new_data(WebSocketOpcode::Ping, vec![], true),
+            Err(WebSocketError::InvalidOpcode(0x9))
+        ));
+        assert!(matches!(
+            WebSocketFrame::new_data(WebSocketOpcode::Close, vec![], false),
+            Err(WebSocketError::InvalidOpcode(0x8))
+        ));
+        assert!(matches!(
+            WebSocketFrame::new_control(WebSocketOpcode::Text, vec![]),
+            Err(WebSocketError::InvalidOpcode(0x1))
+        ));
+        assert!(matches!(
+            WebSocketFrame::new_control(WebSocketOpcode::Ping, vec![0; 126]),
+            Err(WebSocketError::ControlFrameTooLarge)
+        ));
+
+        let frame = WebSocketFrame::new_data(WebSocketOpcode::Binary, vec![1], false).unwrap();
+        assert!(!frame.fin);
+        assert_eq!(frame.opcode, WebSocketOpcode::Binary);
+
+        let frame = WebSocketFrame::new_control(WebSocketOpcode::Pong, vec![0; 125]).unwrap();
+        assert!(frame.fin);
+        assert_eq!(frame.opcode, WebSocketOpcode::Pong);
+
+        for frame in [
+            WebSocketFrame::new_close(Some(1000), Some("bye")),
+            WebSocketFrame::new_ping(b"ping".to_vec()),
+            WebSocketFrame::new_pong(b"pong".to_vec()),
+        ] {
+            assert!(frame.fin);
+            assert!(frame.opcode.is_control());
+        }
+    }
+
+    #[test]
+    fn test_frames_from_any_bytes() {
+        let expected = WebSocketFrame::new_text("hello".to_string(), true);
+        assert_eq!(WebSocketFrame::text("hello"), expected);
+        assert_eq!(WebSocketFrame::text(String::from("hello")), expected);
+        assert_eq!(
+            WebSocketFrame::text_from_utf8(Bytes::from_static(b"hello")).unwrap(),
+            expected
+        );
+        assert!(matches!(
+            WebSocketFrame::text_from_utf8(&[0xffu8, 0xfe][..]),
+            Err(WebSocketError::InvalidUtf8)
+        ));
+
+        let expected = WebSocketFrame::new_binary(vec![1, 2, 3], true);
+        assert_eq!(WebSocketFrame::binary(&[1u8, 2, 3][..]), expected);
+        assert_eq!(WebSocketFrame::binary(vec![1u8, 2, 3]), expected);
+        assert_eq!(
+            WebSocketFrame::binary(Bytes::from(vec![1u8, 2, 3])),
+            expected
+        );
+
+        // An owned Vec keeps its allocation
+        let data = vec![7u8; 1024];
+        let ptr = data.as_ptr();
+        assert_eq!(WebSocketFrame::binary(data).payload.as_ptr(), ptr);
+    }
+
+    /// Small xorshift generator, so the property tests are reproducible
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_parse_adversarial_lengths() {
+        // 64-bit length of u64::MAX with no payload
+        let mut data = vec![0b1000_0010, 127];
+        data.extend_from_slice(&u64::MAX.to_be_bytes());
+        assert!(matches!(
+            WebSocketFrame::parse(&data),
+            Err(WebSocketError::IncompleteFrame | WebSocketError::FrameTooLarge)
+        ));
+
+        // Same, with a masking key
+        let mut data = vec![0b1000_0010, 0b1111_1111];
+        data.extend_from_slice(&u64::MAX.to_be_bytes());
+        data.extend_from_slice(&[1, 2, 3, 4]);
+        assert!(WebSocketFrame::parse(&data).is_err());
+
+        // Truncated extended lengths and masking key
+        assert!(WebSocketFrame::parse(&[0b1000_0010, 126, 0]).is_err());
+        assert!(WebSocketFrame::parse(&[0b1000_0010, 127, 0, 0, 0]).is_err());
+        assert!(WebSocketFrame::parse(&[0b1000_0010, 0b1000_0001, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_parse_never_panics_on_random_input() {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..100_000 {
+            let len = (rng.next() % 48) as usize;
+            let mut data: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+
+            // Bias towards valid opcodes and extended lengths so parsing gets
+            // past the first checks
+            if let Some(first) = data.first_mut() {
+                *first =
+                    (*first & 0b1000_0000) | [0x0, 0x1, 0x2, 0x8, 0x9, 0xA][*first as usize % 6];
+            }
+            if let Some(second) = data.get_mut(1)
+                && rng.next().is_multiple_of(2)
+            {
+                *second |= 0b0111_1110;
+            }
+
+            if let Ok((frame, consumed)) = WebSocketFrame::parse(&data) {
+                assert!(consumed <= data.len());
+                assert!(frame.payload.len() <= consumed);
+                let _ = frame.parse_close_payload();
+            }
+        }
+    }
 }
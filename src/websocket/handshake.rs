@@ -0,0 +1,49 @@
+//! Opening handshake helpers for WebSocket upgrades.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use sha1::{Digest, Sha1};
+
+/// GUID appended to the client's key when computing the accept key, from
+/// RFC 6455 Section 1.3.
+pub const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Compute the `Sec-WebSocket-Accept` value for a `Sec-WebSocket-Key`.
+///
+/// The accept key is the base64 of the SHA-1 of the key followed by
+/// `WEBSOCKET_GUID`. Surrounding whitespace in the key is ignored, but the
+/// key is otherwise used as sent.
+///
+/// # Examples
+///
+/// ```
+/// use http_handler::websocket::compute_accept_key;
+///
+/// assert_eq!(
+///     compute_accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+///     "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+/// );
+/// ```
+pub fn compute_accept_key(sec_websocket_key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(sec_websocket_key.trim().as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    STANDARD.encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_accept_key() {
+        // Example from RFC 6455 Section 1.3
+        assert_eq!(
+            compute_accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(
+            compute_accept_key(" dGhlIHNhbXBsZSBub25jZQ== "),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Typed WebSocket messages built from assembled frames.
+
+use super::{WebSocketError, WebSocketFrame, WebSocketOpcode};
+
+/// A complete WebSocket message, decoded according to its opcode.
+///
+/// Text payloads are already checked to be UTF-8 and close payloads are
+/// split into their status code and reason.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    /// Text message
+    Text(String),
+    /// Binary message
+    Binary(Vec<u8>),
+    /// Ping, with its application data
+    Ping(Vec<u8>),
+    /// Pong, with its application data
+    Pong(Vec<u8>),
+    /// Close, with the status code and reason if the peer sent one
+    Close {
+        /// Status code, `None` if the close frame had no payload
+        code: Option<u16>,
+        /// Reason, empty if none was given
+        reason: String,
+    },
+}
+
+impl TryFrom<WebSocketFrame> for WebSocketMessage {
+    type Error = WebSocketError;
+
+    /// Convert an assembled frame into a message.
+    ///
+    /// Fails with `InvalidUtf8` if a text payload or close reason is not
+    /// UTF-8, and with `UnexpectedContinuation` for a continuation frame,
+    /// which is not a message on its own.
+    fn try_from(frame: WebSocketFrame) -> Result<Self, Self::Error> {
+        match frame.opcode {
+            WebSocketOpcode::Text => String::from_utf8(frame.payload)
+                .map(WebSocketMessage::Text)
+                .map_err(|_| WebSocketError::InvalidUtf8),
+            WebSocketOpcode::Binary => Ok(WebSocketMessage::Binary(frame.payload)),
+            WebSocketOpcode::Ping => Ok(WebSocketMessage::Ping(frame.payload)),
+            WebSocketOpcode::Pong => Ok(WebSocketMessage::Pong(frame.payload)),
+            WebSocketOpcode::Close => {
+                let Some((code, reason)) = frame.payload.split_first_chunk::<2>() else {
+                    return Ok(WebSocketMessage::Close {
+                        code: None,
+                        reason: String::new(),
+                    });
+                };
+                let reason =
+                    String::from_utf8(reason.to_vec()).map_err(|_| WebSocketError::InvalidUtf8)?;
+                Ok(WebSocketMessage::Close {
+                    code: Some(u16::from_be_bytes(*code)),
+                    reason,
+                })
+            }
+            WebSocketOpcode::Continuation => Err(WebSocketError::UnexpectedContinuation),
+        }
+    }
+}
+
+impl From<WebSocketMessage> for WebSocketFrame {
+    /// Build the single, final frame carrying a message.
+    ///
+    /// A close reason is only sent along with a code.
+    fn from(message: WebSocketMessage) -> Self {
+        match message {
+            WebSocketMessage::Text(text) => WebSocketFrame::new_text(text, true),
+            WebSocketMessage::Binary(data) => WebSocketFrame::new_binary(data, true),
+            WebSocketMessage::Ping(data) => WebSocketFrame::new_ping(data),
+            WebSocketMessage::Pong(data) => WebSocketFrame::new_pong(data),
+            WebSocketMessage::Close { code, reason } => {
+                WebSocketFrame::new_close(code, Some(&reason))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_from_frame() {
+        let message = WebSocketMessage::try_from(WebSocketFrame::text("héllo")).unwrap();
+        assert_eq!(message, WebSocketMessage::Text("héllo".to_string()));
+
+        let message = WebSocketMessage::try_from(WebSocketFrame::binary(vec![0xff])).unwrap();
+        assert_eq!(message, WebSocketMessage::Binary(vec![0xff]));
+
+        let message =
+            WebSocketMessage::try_from(WebSocketFrame::new_ping(b"tick".to_vec())).unwrap();
+        assert_eq!(message, WebSocketMessage::Ping(b"tick".to_vec()));
+
+        let frame = WebSocketFrame::new_close(Some(1001), Some("Going away"));
+        assert_eq!(
+            WebSocketMessage::try_from(frame).unwrap(),
+            WebSocketMessage::Close {
+                code: Some(1001),
+                reason: "Going away".to_string(),
+            }
+        );
+        assert_eq!(
+            WebSocketMessage::try_from(WebSocketFrame::new_close(None, None)).unwrap(),
+            WebSocketMessage::Close {
+                code: None,
+                reason: String::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_message_rejects_invalid_frames() {
+        let frame = WebSocketFrame::new_data(WebSocketOpcode::Text, vec![0xff, 0xfe], true);
+        assert!(matches!(
+            WebSocketMessage::try_from(frame.unwrap()),
+            Err(WebSocketError::InvalidUtf8)
+        ));
+
+        let frame = WebSocketFrame::new_continuation(b"more".to_vec(), true);
+        assert!(matches!(
+            WebSocketMessage::try_from(frame),
+            Err(WebSocketError::UnexpectedContinuation)
+        ));
+    }
+}
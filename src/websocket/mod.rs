@@ -3,10 +3,22 @@
 //! This module provides WebSocket frame parsing, encoding, and message assembly
 //! for bidirectional WebSocket communication using tokio_util::codec.
 
+mod broadcast;
 mod codec;
+mod connection;
+mod deflate;
 mod frame;
+mod handshake;
+mod message;
+mod stream;
 mod wrapper;
 
-pub use codec::WebSocketCodec;
-pub use frame::{WebSocketError, WebSocketFrame, WebSocketOpcode};
-pub use wrapper::{WebSocketDecoder, WebSocketEncoder};
+pub use broadcast::Broadcaster;
+pub use codec::{CodecStats, WebSocketCodec, WebSocketRole};
+pub use connection::WebSocketConnection;
+pub use deflate::DeflateConfig;
+pub use frame::{WebSocketCloseCode, WebSocketError, WebSocketFrame, WebSocketOpcode};
+pub use handshake::{WEBSOCKET_GUID, compute_accept_key};
+pub use message::WebSocketMessage;
+pub use stream::WebSocketStream;
+pub use wrapper::{FragmentedMessage, Keepalive, WebSocketDecoder, WebSocketEncoder};
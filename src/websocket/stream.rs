@@ -0,0 +1,166 @@
+//! WebSocket messages as a `Stream` and `Sink` over a byte stream.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::Framed;
+
+use super::{WebSocketCodec, WebSocketError, WebSocketFrame, WebSocketMessage, WebSocketRole};
+
+/// One side of a WebSocket connection, exchanging typed messages.
+///
+/// Wraps `Framed<T, WebSocketCodec>`: incoming messages are reassembled and
+/// decoded into `WebSocketMessage`s, and outgoing ones are sent as single
+/// frames, masked when this is the client side. Incoming frames must follow
+/// the masking rules for this side, see `WebSocketCodec::with_role`. Use
+/// `StreamExt::next` and `SinkExt::send` from `futures_util` to read and
+/// write.
+///
+/// Control frames are passed through both ways; answering pings and closes
+/// is up to the caller.
+pub struct WebSocketStream<T> {
+    framed: Framed<T, WebSocketCodec>,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> WebSocketStream<T> {
+    /// Create the client side of a connection, masking every frame it sends.
+    pub fn client(io: T) -> Self {
+        Self {
+            framed: Framed::new(io, WebSocketCodec::new().with_role(WebSocketRole::Client)),
+        }
+    }
+
+    /// Create the server side of a connection, sending unmasked frames.
+    pub fn server(io: T) -> Self {
+        Self {
+            framed: Framed::new(io, WebSocketCodec::new().with_role(WebSocketRole::Server)),
+        }
+    }
+
+    /// Check if this is the client side of the connection.
+    pub fn is_client(&self) -> bool {
+        self.framed.codec().role() == Some(WebSocketRole::Client)
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &T {
+        self.framed.get_ref()
+    }
+
+    /// Consume the stream, returning the underlying byte stream.
+    pub fn into_inner(self) -> T {
+        self.framed.into_inner()
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Stream for WebSocketStream<T> {
+    type Item = Result<WebSocketMessage, WebSocketError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.framed)
+            .poll_next(cx)
+            .map(|frame| frame.map(|frame| frame.and_then(WebSocketMessage::try_from)))
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Sink<WebSocketMessage> for WebSocketStream<T> {
+    type Error = WebSocketError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.framed).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, message: WebSocketMessage) -> Result<(), Self::Error> {
+        Pin::new(&mut self.framed).start_send(WebSocketFrame::from(message))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.framed).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.framed).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::io::{AsyncReadExt, duplex};
+
+    #[tokio::test]
+    async fn test_client_server_roundtrip() {
+        let (client_io, server_io) = duplex(1024);
+        let mut client = WebSocketStream::client(client_io);
+        let mut server = WebSocketStream::server(server_io);
+        assert!(client.is_client());
+        assert!(!server.is_client());
+
+        client
+            .send(WebSocketMessage::Text("Hello".to_string()))
+            .await
+            .unwrap();
+        client
+            .send(WebSocketMessage::Ping(b"ping".to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            WebSocketMessage::Text("Hello".to_string())
+        );
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            WebSocketMessage::Ping(b"ping".to_vec())
+        );
+
+        server
+            .send(WebSocketMessage::Binary(vec![1, 2, 3]))
+            .await
+            .unwrap();
+        server
+            .send(WebSocketMessage::Close {
+                code: Some(1000),
+                reason: "bye".to_string(),
+            })
+            .await
+            .unwrap();
+        server.close().await.unwrap();
+
+        let received: Vec<_> = client.map(Result::unwrap).collect().await;
+        assert_eq!(
+            received,
+            [
+                WebSocketMessage::Binary(vec![1, 2, 3]),
+                WebSocketMessage::Close {
+                    code: Some(1000),
+                    reason: "bye".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_client_masks() {
+        for (client, masked) in [(true, 0x80), (false, 0)] {
+            let (io, mut peer) = duplex(1024);
+            let mut stream = if client {
+                WebSocketStream::client(io)
+            } else {
+                WebSocketStream::server(io)
+            };
+            stream
+                .send(WebSocketMessage::Binary(vec![0; 4]))
+                .await
+                .unwrap();
+
+            let mut header = [0u8; 2];
+            peer.read_exact(&mut header).await.unwrap();
+            assert_eq!(header, [0x82, masked | 4]);
+        }
+    }
+}
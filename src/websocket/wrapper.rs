@@ -3,12 +3,76 @@
 //! These types provide a clean API for JavaScript bindings while using
 //! the WebSocketCodec for frame parsing and encoding.
 
-use super::{WebSocketCodec, WebSocketError, WebSocketFrame};
+use super::{
+    DeflateConfig, WebSocketCodec, WebSocketError, WebSocketFrame, WebSocketMessage,
+    WebSocketOpcode, WebSocketRole,
+};
 use bytes::BytesMut;
-use std::sync::Arc;
+use futures_util::future::BoxFuture;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::Mutex;
-use tokio_util::codec::{Decoder, Encoder};
+use tokio::sync::{Mutex, MutexGuard, Notify};
+use tokio_util::codec::Decoder;
+
+/// Sends the reply to a received close frame, given its status code
+type CloseEcho =
+    Box<dyn Fn(Option<u16>) -> BoxFuture<'static, Result<(), WebSocketError>> + Send + Sync>;
+
+/// Sends a pong frame carrying the given payload
+type PongReply =
+    Box<dyn Fn(Vec<u8>) -> BoxFuture<'static, Result<(), WebSocketError>> + Send + Sync>;
+
+/// Shared by a keepalive encoder and the decoder reading the same connection,
+/// to report the pongs answering its pings.
+///
+/// Get one from `WebSocketEncoder::keepalive` and hand it to
+/// `WebSocketDecoder::with_keepalive`, or call `pong_received` from your own
+/// read loop.
+///
+/// Each keepalive ping carries a sequence number as its payload, which the
+/// peer echoes in its pong. Only a pong echoing the latest ping counts, so
+/// unsolicited or stale pongs don't keep a dead connection alive.
+#[derive(Clone, Debug, Default)]
+pub struct Keepalive {
+    state: Arc<KeepaliveState>,
+}
+
+#[derive(Debug, Default)]
+struct KeepaliveState {
+    /// Highest ping sequence number answered by a pong
+    answered: AtomicU64,
+    /// Wakes the ping task waiting for an answer
+    pong: Notify,
+}
+
+impl Keepalive {
+    /// Report that a pong carrying `payload` was received.
+    pub fn pong_received(&self, payload: &[u8]) {
+        let Ok(sequence) = <[u8; 8]>::try_from(payload) else {
+            return;
+        };
+        self.state
+            .answered
+            .fetch_max(u64::from_be_bytes(sequence), Ordering::AcqRel);
+        self.state.pong.notify_waiters();
+    }
+
+    /// Wait until the ping with `sequence` is answered.
+    async fn answered(&self, sequence: u64) {
+        loop {
+            let notified = self.state.pong.notified();
+            tokio::pin!(notified);
+            // Register before checking, so a pong arriving in between wakes us
+            notified.as_mut().enable();
+            if self.state.answered.load(Ordering::Acquire) >= sequence {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
 
 /// WebSocket message decoder that reads and assembles frames.
 ///
@@ -17,6 +81,10 @@ pub struct WebSocketDecoder<R> {
     reader: R,
     codec: WebSocketCodec,
     buffer: BytesMut,
+    close_echo: Option<CloseEcho>,
+    auto_pong: Option<PongReply>,
+    keepalive: Option<Keepalive>,
+    closed: bool,
 }
 
 impl<R: AsyncReadExt + Unpin> WebSocketDecoder<R> {
@@ -26,18 +94,107 @@ impl<R: AsyncReadExt + Unpin> WebSocketDecoder<R> {
             reader,
             codec: WebSocketCodec::new(),
             buffer: BytesMut::with_capacity(8192),
+            close_echo: None,
+            auto_pong: None,
+            keepalive: None,
+            closed: false,
         }
     }
 
+    /// Create a WebSocketDecoder that answers pings through `encoder`.
+    ///
+    /// Each ping read by `read_data_message` is answered with a pong carrying
+    /// the same payload before reading on. Pings returned by `read_message`
+    /// are not answered.
+    pub fn with_auto_pong<W>(reader: R, encoder: Arc<WebSocketEncoder<W>>) -> Self
+    where
+        W: AsyncWriteExt + Unpin + Send + 'static,
+    {
+        let mut decoder = Self::new(reader);
+        decoder.auto_pong = Some(Box::new(move |payload| {
+            let encoder = Arc::clone(&encoder);
+            Box::pin(async move {
+                let pong = WebSocketFrame::new_control(WebSocketOpcode::Pong, payload)?;
+                encoder.write_frame(pong).await
+            })
+        }));
+        decoder
+    }
+
+    /// Inflate incoming messages compressed with permessage-deflate (RFC 7692).
+    ///
+    /// See `WebSocketCodec::with_deflate`. Only use this once the extension
+    /// has been negotiated; without it, frames with RSV1 set are rejected.
+    pub fn with_deflate(mut self, config: DeflateConfig) -> Self {
+        self.codec = std::mem::take(&mut self.codec).with_deflate(config);
+        self
+    }
+
+    /// Report received pongs to a keepalive encoder.
+    ///
+    /// See `WebSocketEncoder::with_keepalive`. Pongs are still returned by
+    /// `read_message`.
+    pub fn with_keepalive(mut self, keepalive: Keepalive) -> Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    /// Complete the closing handshake automatically through `encoder`.
+    ///
+    /// When a close frame is received, a close frame with the same status
+    /// code is sent back through the encoder, unless it already sent one, and
+    /// the encoder's stream is shut down. The received close frame is still
+    /// returned, and every read after it returns `Ok(None)`.
+    ///
+    /// Without this, close frames are returned like any other frame and the
+    /// caller is responsible for replying.
+    pub fn with_close_echo<W>(mut self, encoder: Arc<WebSocketEncoder<W>>) -> Self
+    where
+        W: AsyncWriteExt + Unpin + Send + 'static,
+    {
+        self.close_echo = Some(Box::new(move |code| {
+            let encoder = Arc::clone(&encoder);
+            Box::pin(async move {
+                if encoder.close_sent() {
+                    return Ok(());
+                }
+                encoder.write_close(code, None).await
+            })
+        }));
+        self
+    }
+
     /// Read the next WebSocket message.
     ///
     /// Returns `Ok(Some(frame))` if a complete frame was read,
     /// `Ok(None)` if the stream ended, or `Err` on error.
     pub async fn read_message(&mut self) -> Result<Option<WebSocketFrame>, WebSocketError> {
+        if self.closed {
+            return Ok(None);
+        }
+
         loop {
             // Try to decode a frame from the buffer
+            let buffered = self.buffer.len();
             match self.codec.decode(&mut self.buffer)? {
-                Some(frame) => return Ok(Some(frame)),
+                Some(frame) if frame.is_close() && self.close_echo.is_some() => {
+                    self.closed = true;
+                    let code = frame.parse_close_payload().map(|(code, _)| code);
+                    if let Some(echo) = &self.close_echo {
+                        echo(code).await?;
+                    }
+                    return Ok(Some(frame));
+                }
+                Some(frame) => {
+                    if frame.opcode == WebSocketOpcode::Pong
+                        && let Some(keepalive) = &self.keepalive
+                    {
+                        keepalive.pong_received(&frame.payload);
+                    }
+                    return Ok(Some(frame));
+                }
+                // A fragment was consumed, more frames may already be buffered
+                None if self.buffer.len() < buffered => {}
                 None => {
                     // Need more data - read from stream
                     let mut temp_buf = vec![0u8; 8192];
@@ -48,12 +205,43 @@ impl<R: AsyncReadExt + Unpin> WebSocketDecoder<R> {
                             self.buffer.extend_from_slice(&temp_buf[..n]);
                             // Loop to try decoding again
                         }
-                        Err(e) => return Err(WebSocketError::IoError(e.to_string())),
+                        Err(e) => return Err(e.into()),
                     }
                 }
             }
         }
     }
+
+    /// Read the next message, decoded according to its type.
+    ///
+    /// Like `read_message`, but text payloads are checked to be UTF-8 and
+    /// close frames are split into their code and reason. Returns `Ok(None)`
+    /// if the stream ended.
+    pub async fn read_typed(&mut self) -> Result<Option<WebSocketMessage>, WebSocketError> {
+        self.read_message()
+            .await?
+            .map(WebSocketMessage::try_from)
+            .transpose()
+    }
+
+    /// Read the next text, binary or close message.
+    ///
+    /// Pings and pongs are consumed rather than returned. Pings are answered
+    /// when the decoder was created with `with_auto_pong`, and ignored
+    /// otherwise. Close frames are always returned.
+    pub async fn read_data_message(&mut self) -> Result<Option<WebSocketFrame>, WebSocketError> {
+        loop {
+            match self.read_message().await? {
+                Some(frame) if frame.opcode == WebSocketOpcode::Ping => {
+                    if let Some(reply) = &self.auto_pong {
+                        reply(frame.payload).await?;
+                    }
+                }
+                Some(frame) if frame.opcode == WebSocketOpcode::Pong => {}
+                other => return Ok(other),
+            }
+        }
+    }
 }
 
 /// WebSocket message encoder that generates and writes frames.
@@ -62,6 +250,9 @@ impl<R: AsyncReadExt + Unpin> WebSocketDecoder<R> {
 pub struct WebSocketEncoder<W> {
     writer: Arc<Mutex<W>>,
     codec: Mutex<WebSocketCodec>,
+    close_sent: AtomicBool,
+    fragmenting: AtomicBool,
+    keepalive: Option<Keepalive>,
 }
 
 impl<W: AsyncWriteExt + Unpin + Send> WebSocketEncoder<W> {
@@ -70,72 +261,198 @@ impl<W: AsyncWriteExt + Unpin + Send> WebSocketEncoder<W> {
         WebSocketEncoder {
             writer: Arc::new(Mutex::new(writer)),
             codec: Mutex::new(WebSocketCodec::new()),
+            close_sent: AtomicBool::new(false),
+            fragmenting: AtomicBool::new(false),
+            keepalive: None,
         }
     }
 
-    /// Write a text message.
-    pub async fn write_text(&self, text: &str, _masked: bool) -> Result<(), WebSocketError> {
-        let frame = WebSocketFrame::new_text(text.to_string(), true);
-        let mut buffer = BytesMut::new();
+    /// Create a new WebSocketEncoder for the client side of a connection.
+    ///
+    /// Every frame is masked with a fresh random key, as RFC 6455 Section 5.3
+    /// requires of clients.
+    pub fn new_client(writer: W) -> Self {
+        WebSocketEncoder {
+            codec: Mutex::new(WebSocketCodec::new().with_role(WebSocketRole::Client)),
+            ..Self::new(writer)
+        }
+    }
 
-        // Lock the codec to encode the frame
-        let mut codec = self.codec.lock().await;
-        codec.encode(frame, &mut buffer)?;
-        drop(codec); // Release lock early
+    /// Keep the connection alive, for encoders of either role and with or
+    /// without deflate.
+    ///
+    /// A task is spawned that sends a ping every `interval`. If no pong
+    /// answering that ping is reported through `keepalive` within `timeout`,
+    /// it sends a close frame with code 1011 and shuts the writer down. The
+    /// task stops once a close frame was sent or the encoder is dropped.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn with_keepalive(mut self, interval: Duration, timeout: Duration) -> Arc<Self>
+    where
+        W: 'static,
+    {
+        let keepalive = Keepalive::default();
+        self.keepalive = Some(keepalive.clone());
+        let encoder = Arc::new(self);
 
-        let mut writer = self.writer.lock().await;
-        writer
-            .write_all(&buffer)
-            .await
-            .map_err(|e| WebSocketError::IoError(e.to_string()))?;
+        let weak = Arc::downgrade(&encoder);
+        tokio::spawn(async move {
+            for sequence in 1.. {
+                tokio::time::sleep(interval).await;
+                if !Self::send_keepalive_ping(&weak, sequence).await {
+                    return;
+                }
+                if tokio::time::timeout(timeout, keepalive.answered(sequence))
+                    .await
+                    .is_err()
+                {
+                    if let Some(encoder) = weak.upgrade()
+                        && !encoder.close_sent()
+                    {
+                        let _ = encoder
+                            .write_close(Some(1011), Some("keepalive timeout"))
+                            .await;
+                    }
+                    return;
+                }
+            }
+        });
+        encoder
+    }
 
-        Ok(())
+    /// Send a keepalive ping, returning whether the keepalive should go on.
+    async fn send_keepalive_ping(encoder: &Weak<Self>, sequence: u64) -> bool {
+        let Some(encoder) = encoder.upgrade() else {
+            return false;
+        };
+        let ping = WebSocketFrame::new_ping(sequence.to_be_bytes().to_vec());
+        !encoder.close_sent() && encoder.write_frame(ping).await.is_ok()
     }
 
-    /// Write a binary message.
-    pub async fn write_binary(&self, data: &[u8], _masked: bool) -> Result<(), WebSocketError> {
-        let frame = WebSocketFrame::new_binary(data.to_vec(), true);
-        let mut buffer = BytesMut::new();
+    /// Get the handle to report pongs through, if this encoder was created
+    /// with `with_keepalive`.
+    pub fn keepalive(&self) -> Option<Keepalive> {
+        self.keepalive.clone()
+    }
 
-        // Lock the codec to encode the frame
-        let mut codec = self.codec.lock().await;
-        codec.encode(frame, &mut buffer)?;
-        drop(codec); // Release lock early
+    /// Compress outgoing messages with permessage-deflate (RFC 7692).
+    ///
+    /// See `WebSocketCodec::with_deflate`. Messages below the configured
+    /// threshold are still sent uncompressed.
+    pub fn with_deflate(mut self, config: DeflateConfig) -> Self {
+        let codec = std::mem::take(self.codec.get_mut());
+        *self.codec.get_mut() = codec.with_deflate(config);
+        self
+    }
 
+    /// Check whether a close frame was sent with `write_close`.
+    pub fn close_sent(&self) -> bool {
+        self.close_sent.load(Ordering::Acquire)
+    }
+
+    /// Encode and write a frame, masking it with a random key if `masked` is
+    /// set or this is a client encoder.
+    ///
+    /// Data frames are refused while a fragmented message is in progress.
+    async fn send(&self, frame: WebSocketFrame, masked: bool) -> Result<(), WebSocketError> {
+        if frame.opcode.is_data() && self.fragmenting.load(Ordering::Acquire) {
+            return Err(WebSocketError::MessageInProgress);
+        }
+        self.send_unchecked(frame, masked).await.map(drop)
+    }
+
+    /// Encode and write a frame without checking for a fragmented message in
+    /// progress, and return the still locked writer.
+    ///
+    /// The codec stays locked until the frame is written, so frames reach the
+    /// stream in the order they were encoded. Compression with context
+    /// takeover depends on that, as each message may refer back to the ones
+    /// compressed before it.
+    async fn send_unchecked(
+        &self,
+        frame: WebSocketFrame,
+        masked: bool,
+    ) -> Result<MutexGuard<'_, W>, WebSocketError> {
+        let mut buffer = BytesMut::new();
+        let mut codec = self.codec.lock().await;
+        let client = codec.role() == Some(WebSocketRole::Client);
+        let mask = (masked || client).then(rand::random::<[u8; 4]>);
+        codec.encode_frame(frame, mask, &mut buffer)?;
         let mut writer = self.writer.lock().await;
-        writer
-            .write_all(&buffer)
-            .await
-            .map_err(|e| WebSocketError::IoError(e.to_string()))?;
+        writer.write_all(&buffer).await?;
+        Ok(writer)
+    }
+
+    /// Start sending a text message in fragments.
+    ///
+    /// See `FragmentedMessage`. The fragments together must be valid UTF-8,
+    /// though each one on its own need not be. Fails with
+    /// `WebSocketError::MessageInProgress` if another fragmented message has
+    /// not been finished yet.
+    pub fn begin_text(&self) -> Result<FragmentedMessage<'_, W>, WebSocketError> {
+        self.begin(WebSocketOpcode::Text)
+    }
+
+    /// Start sending a binary message in fragments.
+    ///
+    /// See `FragmentedMessage`. Fails with `WebSocketError::MessageInProgress`
+    /// if another fragmented message has not been finished yet.
+    pub fn begin_binary(&self) -> Result<FragmentedMessage<'_, W>, WebSocketError> {
+        self.begin(WebSocketOpcode::Binary)
+    }
 
+    fn begin(&self, opcode: WebSocketOpcode) -> Result<FragmentedMessage<'_, W>, WebSocketError> {
+        self.fragmenting
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .map_err(|_| WebSocketError::MessageInProgress)?;
+        Ok(FragmentedMessage {
+            encoder: self,
+            opcode,
+            started: false,
+        })
+    }
+
+    /// Write a single frame.
+    ///
+    /// Control frames with payloads over 125 bytes or without FIN set are
+    /// rejected before anything is written. Data frames are rejected while a
+    /// fragmented message is in progress. Client encoders mask the frame.
+    pub async fn write_frame(&self, frame: WebSocketFrame) -> Result<(), WebSocketError> {
+        self.send(frame, false).await?;
+        Ok(())
+    }
+
+    /// Write a text message, masked if `masked` is set or this is a client
+    /// encoder.
+    pub async fn write_text(&self, text: &str, masked: bool) -> Result<(), WebSocketError> {
+        let frame = WebSocketFrame::new_text(text.to_string(), true);
+        self.send(frame, masked).await?;
+        Ok(())
+    }
+
+    /// Write a binary message, masked if `masked` is set or this is a client
+    /// encoder.
+    pub async fn write_binary(&self, data: &[u8], masked: bool) -> Result<(), WebSocketError> {
+        let frame = WebSocketFrame::new_binary(data.to_vec(), true);
+        self.send(frame, masked).await?;
         Ok(())
     }
 
     /// Send a close frame with optional code and reason, then close the stream.
+    ///
+    /// Client encoders mask the frame.
     pub async fn write_close(
         &self,
         code: Option<u16>,
         reason: Option<&str>,
     ) -> Result<(), WebSocketError> {
+        // The in-progress check only applies to data frames, not close
         let frame = WebSocketFrame::new_close(code, reason);
-        let mut buffer = BytesMut::new();
-
-        // Lock the codec to encode the frame
-        let mut codec = self.codec.lock().await;
-        codec.encode(frame, &mut buffer)?;
-        drop(codec); // Release lock early
-
-        let mut writer = self.writer.lock().await;
-        writer
-            .write_all(&buffer)
-            .await
-            .map_err(|e| WebSocketError::IoError(e.to_string()))?;
+        let mut writer = self.send_unchecked(frame, false).await?;
+        self.close_sent.store(true, Ordering::Release);
 
         // Shutdown the stream
-        writer
-            .shutdown()
-            .await
-            .map_err(|e| WebSocketError::IoError(e.to_string()))?;
+        writer.shutdown().await?;
 
         Ok(())
     }
@@ -143,17 +460,67 @@ impl<W: AsyncWriteExt + Unpin + Send> WebSocketEncoder<W> {
     /// Close the encoder stream without sending a close frame.
     pub async fn end(&self) -> Result<(), WebSocketError> {
         let mut writer = self.writer.lock().await;
-        writer
-            .shutdown()
-            .await
-            .map_err(|e| WebSocketError::IoError(e.to_string()))?;
+        writer.shutdown().await?;
+        Ok(())
+    }
+}
+
+/// A message being sent in fragments, started with
+/// `WebSocketEncoder::begin_text` or `begin_binary`.
+///
+/// The first fragment carries the message opcode and the rest are
+/// continuation frames, all with FIN clear until `finish` sends the final
+/// frame. Until then the encoder refuses other data messages, while control
+/// frames such as pings can still be sent in between.
+///
+/// Dropping the message before any fragment was sent abandons it. Dropping
+/// it after that leaves the peer waiting for the rest, so the encoder keeps
+/// refusing data messages and the connection should be closed.
+pub struct FragmentedMessage<'a, W: AsyncWriteExt + Unpin + Send> {
+    encoder: &'a WebSocketEncoder<W>,
+    opcode: WebSocketOpcode,
+    started: bool,
+}
+
+impl<W: AsyncWriteExt + Unpin + Send> FragmentedMessage<'_, W> {
+    /// Send the next fragment of the message.
+    pub async fn send_fragment(&mut self, data: &[u8]) -> Result<(), WebSocketError> {
+        self.write(data, false).await
+    }
+
+    /// Send the final frame, completing the message.
+    pub async fn finish(mut self) -> Result<(), WebSocketError> {
+        self.write(&[], true).await?;
+        self.encoder.fragmenting.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    async fn write(&mut self, data: &[u8], fin: bool) -> Result<(), WebSocketError> {
+        let opcode = if self.started {
+            WebSocketOpcode::Continuation
+        } else {
+            self.opcode
+        };
+        let frame = WebSocketFrame::new_data(opcode, data.to_vec(), fin)?;
+        self.encoder.send_unchecked(frame, false).await.map(drop)?;
+        self.started = true;
+
         Ok(())
     }
 }
 
+impl<W: AsyncWriteExt + Unpin + Send> Drop for FragmentedMessage<'_, W> {
+    fn drop(&mut self) {
+        if !self.started {
+            self.encoder.fragmenting.store(false, Ordering::Release);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::websocket::WebSocketOpcode;
     use tokio::io::duplex;
 
     #[tokio::test]
@@ -197,6 +564,325 @@ mod tests {
         assert_eq!(frame.payload, data);
     }
 
+    #[tokio::test]
+    async fn test_fragmented_text_message() {
+        let (client, server) = duplex(1024);
+
+        let encoder = WebSocketEncoder::new(client);
+        let mut decoder = WebSocketDecoder::new(server);
+
+        let mut message = encoder.begin_text().unwrap();
+        message.send_fragment(b"Hello").await.unwrap();
+        encoder
+            .write_frame(WebSocketFrame::new_ping(b"still here".to_vec()))
+            .await
+            .unwrap();
+        message.send_fragment(b", ").await.unwrap();
+
+        // No other data message may start until this one is finished
+        assert!(matches!(
+            encoder.begin_binary(),
+            Err(WebSocketError::MessageInProgress)
+        ));
+        assert!(matches!(
+            encoder.write_text("interleaved", false).await,
+            Err(WebSocketError::MessageInProgress)
+        ));
+
+        message.send_fragment(b"world").await.unwrap();
+        message.finish().await.unwrap();
+        encoder.write_binary(&[1], false).await.unwrap();
+
+        // The ping arrives as soon as it was sent, then the whole message
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.opcode, WebSocketOpcode::Ping);
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert!(frame.fin);
+        assert_eq!(frame.payload_as_text().unwrap(), "Hello, world");
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.payload, [1]);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_fragmented_message() {
+        let (client, _server) = duplex(1024);
+        let encoder = WebSocketEncoder::new(client);
+
+        drop(encoder.begin_binary().unwrap());
+        let mut message = encoder.begin_text().unwrap();
+        message.send_fragment(b"partial").await.unwrap();
+        drop(message);
+
+        // Once part of a message was sent, its end is still owed
+        assert!(matches!(
+            encoder.write_binary(&[1], false).await,
+            Err(WebSocketError::MessageInProgress)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_pings_while_pongs_arrive() {
+        let (client, server) = duplex(1024);
+        let encoder = WebSocketEncoder::new(client)
+            .with_keepalive(Duration::from_secs(30), Duration::from_secs(5));
+        let keepalive = encoder.keepalive().unwrap();
+        let mut decoder = WebSocketDecoder::new(server);
+
+        for _ in 0..3 {
+            let start = tokio::time::Instant::now();
+            let frame = decoder.read_message().await.unwrap().unwrap();
+            assert_eq!(frame.opcode, WebSocketOpcode::Ping);
+            assert_eq!(start.elapsed(), Duration::from_secs(30));
+            keepalive.pong_received(&frame.payload);
+        }
+        assert!(!encoder.close_sent());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_closes_without_pong() {
+        let (client, server) = duplex(1024);
+        let (pong_tx, pong_rx) = duplex(1024);
+        let encoder = WebSocketEncoder::new(client)
+            .with_keepalive(Duration::from_secs(30), Duration::from_secs(5));
+        let mut decoder = WebSocketDecoder::new(server);
+
+        // Pongs read by a decoder sharing the keepalive count
+        let peer = WebSocketEncoder::new(pong_tx);
+        let mut pongs = WebSocketDecoder::new(pong_rx).with_keepalive(encoder.keepalive().unwrap());
+
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.opcode, WebSocketOpcode::Ping);
+        peer.write_frame(WebSocketFrame::new_pong(frame.payload))
+            .await
+            .unwrap();
+        pongs.read_message().await.unwrap().unwrap();
+
+        // The second ping goes unanswered
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.opcode, WebSocketOpcode::Ping);
+        let start = tokio::time::Instant::now();
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        assert_eq!(
+            frame.parse_close_payload(),
+            Some((1011, "keepalive timeout".to_string()))
+        );
+        assert!(decoder.read_message().await.unwrap().is_none());
+        assert!(encoder.close_sent());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_ignores_unsolicited_pong() {
+        let (client, server) = duplex(1024);
+        let encoder = WebSocketEncoder::new(client)
+            .with_keepalive(Duration::from_secs(30), Duration::from_secs(5));
+        let keepalive = encoder.keepalive().unwrap();
+        let mut decoder = WebSocketDecoder::new(server);
+
+        // Pongs that don't answer the coming ping, then silence
+        keepalive.pong_received(&[]);
+        keepalive.pong_received(&0u64.to_be_bytes());
+
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.opcode, WebSocketOpcode::Ping);
+        let start = tokio::time::Instant::now();
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        assert_eq!(
+            frame.parse_close_payload(),
+            Some((1011, "keepalive timeout".to_string()))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_on_deflate_client() {
+        let (client, mut server) = duplex(1024);
+        let encoder = WebSocketEncoder::new_client(client)
+            .with_deflate(DeflateConfig::default())
+            .with_keepalive(Duration::from_secs(30), Duration::from_secs(5));
+
+        // The ping is masked, as every frame a client sends
+        let mut header = [0u8; 2];
+        server.read_exact(&mut header).await.unwrap();
+        assert_eq!(header[0], 0x80 | WebSocketOpcode::Ping as u8);
+        assert_eq!(header[1], 0x80 | 8);
+        assert!(!encoder.close_sent());
+    }
+
+    #[tokio::test]
+    async fn test_read_typed() {
+        let (client, server) = duplex(1024);
+
+        let encoder = WebSocketEncoder::new(client);
+        let mut decoder = WebSocketDecoder::new(server);
+
+        encoder.write_text("Hello", false).await.unwrap();
+        encoder
+            .write_frame(WebSocketFrame::new_ping(b"ping".to_vec()))
+            .await
+            .unwrap();
+        encoder.write_binary(&[1, 2], false).await.unwrap();
+        encoder.write_close(Some(1000), Some("done")).await.unwrap();
+
+        let mut messages = Vec::new();
+        while let Some(message) = decoder.read_typed().await.unwrap() {
+            messages.push(message);
+        }
+        assert_eq!(
+            messages,
+            [
+                WebSocketMessage::Text("Hello".to_string()),
+                WebSocketMessage::Ping(b"ping".to_vec()),
+                WebSocketMessage::Binary(vec![1, 2]),
+                WebSocketMessage::Close {
+                    code: Some(1000),
+                    reason: "done".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_encoder_masks_frames() {
+        let (client, mut server) = duplex(1024);
+        let encoder = WebSocketEncoder::new_client(client);
+
+        encoder.write_text("Hello", false).await.unwrap();
+        encoder.write_close(Some(1000), None).await.unwrap();
+
+        let mut data = Vec::new();
+        server.read_to_end(&mut data).await.unwrap();
+
+        // MASK bit set, followed by the 4-byte key and the masked payload
+        assert_eq!(data[0], 0x81);
+        assert_eq!(data[1], 0x80 | 5);
+        assert_ne!(&data[6..11], b"Hello");
+        assert_eq!(data[11], 0x88);
+        assert_eq!(data[12], 0x80 | 2);
+
+        let mut decoder = WebSocketDecoder::new(&data[..]);
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.payload_as_text().unwrap(), "Hello");
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.opcode, WebSocketOpcode::Close);
+        assert_eq!(frame.payload, 1000u16.to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_masked_write_on_server_encoder() {
+        let (client, mut server) = duplex(1024);
+        let encoder = WebSocketEncoder::new(client);
+
+        encoder.write_binary(&[1, 2, 3], true).await.unwrap();
+        encoder.write_binary(&[4, 5, 6], false).await.unwrap();
+        encoder.end().await.unwrap();
+
+        let mut data = Vec::new();
+        server.read_to_end(&mut data).await.unwrap();
+
+        // Only the frame written with `masked` set carries a key
+        assert_eq!(data[1], 0x80 | 3);
+        assert_eq!(data[10], 3);
+
+        let mut decoder = WebSocketDecoder::new(&data[..]);
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.payload, [1, 2, 3]);
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.payload, [4, 5, 6]);
+    }
+
+    #[tokio::test]
+    async fn test_write_frame_rejects_large_ping() {
+        let (client, server) = duplex(1024);
+
+        let encoder = WebSocketEncoder::new(client);
+        let mut decoder = WebSocketDecoder::new(server);
+
+        let result = encoder
+            .write_frame(WebSocketFrame::new_ping(vec![0; 200]))
+            .await;
+        assert!(matches!(result, Err(WebSocketError::ControlFrameTooLarge)));
+
+        // Nothing was written, the next frame arrives intact
+        encoder
+            .write_frame(WebSocketFrame::new_ping(b"ok".to_vec()))
+            .await
+            .unwrap();
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.opcode, WebSocketOpcode::Ping);
+        assert_eq!(frame.payload, b"ok");
+    }
+
+    #[tokio::test]
+    async fn test_deflate_compresses_only_large_messages() {
+        let (client, mut server) = duplex(64 * 1024);
+        let encoder = WebSocketEncoder::new(client).with_deflate(DeflateConfig::new());
+
+        let large = "compressible ".repeat(100);
+        encoder.write_text("small", false).await.unwrap();
+        encoder.write_text(&large, false).await.unwrap();
+        encoder.end().await.unwrap();
+
+        let mut data = Vec::new();
+        server.read_to_end(&mut data).await.unwrap();
+
+        // Small message: FIN and text opcode, RSV1 clear, sent as is
+        assert_eq!(data[0], 0x81);
+        assert_eq!(&data[2..7], b"small");
+
+        // Large message: RSV1 set and shorter than the original
+        let large_frame = &data[7..];
+        assert_eq!(large_frame[0], 0xc1);
+        assert!(large_frame.len() < large.len());
+
+        // A decoder with the extension inflates it again
+        let mut decoder = WebSocketDecoder::new(&data[..]).with_deflate(DeflateConfig::new());
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.payload, b"small");
+        let frame = decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.payload_as_text().unwrap(), large);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_deflate_writes_stay_in_order() {
+        // A small buffer makes writers wait on the stream while others encode
+        let (client, server) = duplex(256);
+        let encoder = Arc::new(WebSocketEncoder::new(client).with_deflate(DeflateConfig::new()));
+
+        let messages: Vec<String> = (0..200)
+            .map(|i| format!("message {} ", i).repeat(100 + i % 20))
+            .collect();
+        let writers: Vec<_> = messages
+            .iter()
+            .cloned()
+            .map(|message| {
+                let encoder = Arc::clone(&encoder);
+                tokio::spawn(async move { encoder.write_text(&message, false).await.unwrap() })
+            })
+            .collect();
+
+        // Every message inflates with the shared window, whatever the order
+        let reader = tokio::spawn(async move {
+            let mut decoder = WebSocketDecoder::new(server).with_deflate(DeflateConfig::new());
+            let mut received = Vec::new();
+            while let Some(frame) = decoder.read_message().await.unwrap() {
+                received.push(frame.payload_as_text().unwrap().to_string());
+            }
+            received
+        });
+
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        encoder.end().await.unwrap();
+
+        let mut received = reader.await.unwrap();
+        received.sort();
+        let mut expected = messages;
+        expected.sort();
+        assert_eq!(received, expected);
+    }
+
     #[tokio::test]
     async fn test_write_close_shuts_down_stream() {
         let (client, server) = duplex(1024);
@@ -277,6 +963,94 @@ mod tests {
         assert!(decoder.read_message().await.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn test_close_echo() {
+        let (client, server_reader) = duplex(1024);
+        let (server_writer, client_reader) = duplex(1024);
+
+        let client_encoder = WebSocketEncoder::new(client);
+        let mut client_decoder = WebSocketDecoder::new(client_reader);
+        let server_encoder = Arc::new(WebSocketEncoder::new(server_writer));
+        let mut server_decoder =
+            WebSocketDecoder::new(server_reader).with_close_echo(Arc::clone(&server_encoder));
+
+        client_encoder.write_text("bye", false).await.unwrap();
+        client_encoder
+            .write_close(Some(1001), Some("Going away"))
+            .await
+            .unwrap();
+
+        let frame = server_decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.payload_as_text().unwrap(), "bye");
+
+        // The close is returned to the caller and echoed to the peer
+        let frame = server_decoder.read_message().await.unwrap().unwrap();
+        assert!(frame.is_close());
+        assert!(server_encoder.close_sent());
+
+        let echo = client_decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(echo.parse_close_payload(), Some((1001, String::new())));
+        assert!(client_decoder.read_message().await.unwrap().is_none());
+
+        // Reads after the close end the stream
+        assert!(server_decoder.read_message().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_auto_pong() {
+        let (client, server_reader) = duplex(1024);
+        let (server_writer, client_reader) = duplex(1024);
+
+        let client_encoder = WebSocketEncoder::new_client(client);
+        let mut client_decoder = WebSocketDecoder::new(client_reader);
+        let server_encoder = Arc::new(WebSocketEncoder::new(server_writer));
+        let mut server_decoder = WebSocketDecoder::with_auto_pong(server_reader, server_encoder);
+
+        let payload = vec![0xab; 125];
+        client_encoder
+            .write_frame(WebSocketFrame::new_ping(payload.clone()))
+            .await
+            .unwrap();
+        client_encoder
+            .write_frame(WebSocketFrame::new_pong(b"unsolicited".to_vec()))
+            .await
+            .unwrap();
+        client_encoder.write_text("hello", false).await.unwrap();
+        client_encoder.write_close(Some(1000), None).await.unwrap();
+
+        // Only the data and close frames reach the caller
+        let frame = server_decoder.read_data_message().await.unwrap().unwrap();
+        assert_eq!(frame.payload_as_text().unwrap(), "hello");
+        let frame = server_decoder.read_data_message().await.unwrap().unwrap();
+        assert!(frame.is_close());
+        assert_eq!(frame.parse_close_payload(), Some((1000, String::new())));
+
+        // The ping was answered with its payload
+        let frame = client_decoder.read_message().await.unwrap().unwrap();
+        assert_eq!(frame.opcode, WebSocketOpcode::Pong);
+        assert_eq!(frame.payload, payload);
+    }
+
+    #[tokio::test]
+    async fn test_close_echo_skipped_after_sending_close() {
+        let (client, server_reader) = duplex(1024);
+        let (server_writer, _client_reader) = duplex(1024);
+
+        let client_encoder = WebSocketEncoder::new(client);
+        let server_encoder = Arc::new(WebSocketEncoder::new(server_writer));
+        let mut server_decoder =
+            WebSocketDecoder::new(server_reader).with_close_echo(Arc::clone(&server_encoder));
+
+        // The server starts the closing handshake, and the client replies
+        server_encoder.write_close(Some(1000), None).await.unwrap();
+        client_encoder.write_close(Some(1000), None).await.unwrap();
+
+        // Replying again would fail, as the server's stream is shut down
+        let frame = server_decoder.read_message().await.unwrap().unwrap();
+        assert!(frame.is_close());
+        assert!(server_decoder.read_message().await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn test_close_cannot_be_called_twice() {
         let (client, _server) = duplex(1024);
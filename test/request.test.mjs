@@ -123,6 +123,43 @@ test('Request', async t => {
     strictEqual(request.docroot, '/new/docroot', 'should allow docroot to be changed')
   })
 
+  await t.test('socket', () => {
+    const request = new Request({
+      url: 'https://example.com/test',
+      socket: {
+        localAddress: '127.0.0.1',
+        localPort: 8080,
+        localFamily: 'IPv4',
+        remoteAddress: '::1',
+        remotePort: 54321,
+        remoteFamily: 'IPv6'
+      }
+    })
+
+    strictEqual(request.socket.localAddress, '127.0.0.1', 'should set the local address')
+    strictEqual(request.socket.localPort, 8080, 'should set the local port')
+    strictEqual(request.socket.remoteAddress, '::1', 'should set the remote address')
+    strictEqual(request.socket.remoteFamily, 'IPv6', 'should set the remote family')
+
+    strictEqual(new Request({ url: '/test' }).socket, undefined, 'should be undefined without socket info')
+  })
+
+  await t.test('socket with only a remote address', () => {
+    const request = new Request({
+      url: 'https://example.com/test',
+      socket: {
+        remoteAddress: '10.0.0.1',
+        remotePort: 54321
+      }
+    })
+
+    strictEqual(request.socket.remoteAddress, '10.0.0.1', 'should set the remote address')
+    strictEqual(request.socket.remotePort, 54321, 'should set the remote port')
+    strictEqual(request.socket.remoteFamily, 'IPv4', 'should infer the remote family')
+    strictEqual(request.socket.localAddress, undefined, 'should leave the local address unset')
+    strictEqual(request.socket.localPort, undefined, 'should leave the local port unset')
+  })
+
   await t.test('body', () => {
     const body = Buffer.from('Hello, World!')
     const request = new Request({